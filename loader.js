@@ -24,9 +24,35 @@ import {
    give_current_height,
    init_from_csv_buffer,
    get_total_energy_in_one,
-   writeStateAsCSV
+   writeStateAsCSV,
+   start_chunked_run,
+   run_a_chunk,
+   cancel_chunked_run,
+   set_integrator,
+   set_color_range,
+   set_view_field,
+   set_conductivity,
+   set_isotherm_overlay,
+   start_accumulators,
+   get_sim_time,
+   reset_clock,
+   run_until,
+   subscribe_events,
+   get_field_npy,
+   get_fields_npz,
+   load_numpy,
+   get_vti,
+   add_vtk_frame,
+   get_vtk_pvd
 } from "./pkg/pet_webgpusolver.js";
 
+// the solver tells us about gpu errors as they happen rather than only in the console
+subscribe_events((name, detail) => {
+   if (name === "error") {
+      showMessage(`gpu error: ${detail}`);
+   }
+});
+
 async function init_energy() {
    var init_energy_val = await get_total_energy_in_one();
    document.getElementById("total_energy_goes_here").textContent = init_energy_val;
@@ -88,6 +114,56 @@ document.getElementById("auto_delta_t").addEventListener("click", (event) => {
    document.getElementById("delta_t").value = safety_factor / (2 * kappa * (width ** 2 + height ** 2))
 })
 
+document.getElementById("integrator").addEventListener("change", (event) => {
+   set_integrator(event.target.value);
+})
+
+function apply_color_range() {
+   set_color_range(document.getElementById("color_range").value);
+}
+
+document.getElementById("color_range").addEventListener("change", (event) => {
+   apply_color_range();
+})
+
+function apply_view_field() {
+   set_conductivity(document.getElementById("conductivity").value);
+   try {
+      set_view_field(document.getElementById("view_field").value);
+   } catch (e) {
+      // the accumulated views need their maps started first
+      showMessage(e);
+      document.getElementById("view_field").value = "temperature";
+      set_view_field("temperature");
+   }
+}
+
+document.getElementById("start_accumulators").addEventListener("click", (event) => {
+   start_accumulators(document.getElementById("accumulate_threshold").value);
+   apply_view_field();
+})
+
+function apply_isotherm_overlay() {
+   var levels = document.getElementById("isotherm_levels").value
+      .split(",").map((x) => parseFloat(x)).filter((x) => !isNaN(x));
+   try {
+      set_isotherm_overlay(new Float32Array(levels));
+   } catch (e) {
+      showMessage(e);
+   }
+}
+
+document.getElementById("isotherm_levels").addEventListener("change", (event) => {
+   apply_isotherm_overlay();
+})
+
+document.getElementById("view_field").addEventListener("change", (event) => {
+   apply_view_field();
+})
+document.getElementById("conductivity").addEventListener("change", (event) => {
+   apply_view_field();
+})
+
 document.getElementById("auto_max_N").addEventListener("click", (event) => {
    var theval = Math.ceil(document.getElementById("max_time").value / parseFloat(document.getElementById("delta_t").value));
    document.getElementById("max_N").value = theval;
@@ -96,13 +172,16 @@ document.getElementById("auto_max_N").addEventListener("click", (event) => {
 var max_N = 52488;
 var N_add = 100;
 var current_N = 0;
-var current_time = 0;
-var current_delta_t = parseFloat(document.getElementById("delta_t").value);
 var stop_compute = false;
 var get_total_temp = true;
 
+// the solver keeps the clock, every step however it was run moves it on
+function show_time() {
+   document.getElementById("total_time_goes_here").textContent = `${get_sim_time()}`
+}
+
 function run_compute() {
-   run_a_compute_iter();
+   let steps_done = run_a_chunk();
    if (get_total_temp) {
       send_output_to_export();
       render_a_frame();
@@ -112,35 +191,59 @@ function run_compute() {
       render_a_frame();
    }
 
-   current_N = current_N + N_add
-   current_time = current_time + current_delta_t * N_add;
+   // the run is over once run_a_chunk says -1, and current_N keeps the last count it gave us
+   let run_over = steps_done < 0;
+   if (!run_over) {
+      current_N = steps_done;
+   }
 
    let receiver_response = is_receiver_ready();
    if (receiver_response) {
       //console.log(`tried to export num with receiver ${receiver_response}`)
       let total_energy = get_export_to_num();
       document.getElementById("total_energy_goes_here").textContent = `${total_energy}`
-      document.getElementById("total_time_goes_here").textContent = `${current_time}`
+      show_time();
       get_total_temp = true;
    }
 
-   if ((current_N < max_N) & (~stop_compute)) {requestAnimationFrame(run_compute);}
+   if (run_over) {
+      show_time();
+   } else {
+      requestAnimationFrame(run_compute);
+   }
 }
 
 document.getElementById("compute").addEventListener("click", (event) => {
    current_N = 0;
    stop_compute = false;
+   start_chunked_run(max_N);
+   requestAnimationFrame(run_compute);
+})
+
+// the solver decides when this run ends, run_compute keeps going until it says -1
+document.getElementById("run_until").addEventListener("click", async (event) => {
+   current_N = 0;
+   stop_compute = false;
+   let done = run_until(document.getElementById("run_until_spec").value);
    requestAnimationFrame(run_compute);
+   try {
+      let report = await done;
+      let reason = report.cancelled ? "cancelled" : report.reason;
+      showMessage(`run stopped (${reason}) after ${report.steps} steps at t = ${report.sim_time}`);
+   } catch (e) {
+      showMessage(e);
+   }
+   show_time();
 })
 
 document.getElementById("break").addEventListener("click", (event) => {
    stop_compute = true;
+   cancel_chunked_run();
 })
 
 document.getElementById("update_vals").addEventListener("click", (event) => {
    max_N = parseInt(document.getElementById("max_N").value);
    N_add = parseInt(document.getElementById("N_add").value);
-   current_delta_t = parseFloat(document.getElementById("delta_t").value);
    update_values(
       document.getElementById("N_add").value,
       document.getElementById("kappa").value,
@@ -152,6 +255,7 @@ document.getElementById("update_vals").addEventListener("click", (event) => {
 
 async function reset_state_with_dims() {
    stop_compute = true;
+   cancel_chunked_run();
    junk_current_state();
    var width = document.getElementById("width_val").value;
    var height = document.getElementById("height_val").value;
@@ -165,8 +269,13 @@ async function reset_state_with_dims() {
       document.getElementById("min_T").value,
       document.getElementById("max_T").value,
    );
+   set_integrator(document.getElementById("integrator").value);
+   apply_view_field();
+   apply_color_range();
+   apply_isotherm_overlay();
    get_total_temp = true;
    current_grid_shape.textContent = `${width}x${height}`;
+   show_time();
 }
 
 function showMessage(thestring) {
@@ -175,7 +284,11 @@ function showMessage(thestring) {
 
 document.getElementById("send_xy").addEventListener("click", (event) => {
    reset_state_with_dims();
-   current_time = 0;
+})
+
+document.getElementById("reset_clock").addEventListener("click", (event) => {
+   reset_clock();
+   show_time();
 })
 
 async function do_csv_process() {
@@ -192,12 +305,18 @@ async function do_csv_process() {
    var result = "not done yet";
    reader.onload = async () => {
       stop_compute = true;
+      cancel_chunked_run();
       result = parse_csv(reader.result);
       showMessage(result);
       if (result.startsWith("success!")) {
          junk_current_state();
          await init_from_csv_buffer();
+         set_integrator(document.getElementById("integrator").value);
+         apply_view_field();
+         apply_color_range();
+         apply_isotherm_overlay();
          document.getElementById("total_energy_goes_here").textContent = await (get_total_energy_in_one());
+         show_time();
          var width = give_current_width();
          var height = give_current_height();
          document.getElementById("width_val").value = width;
@@ -240,3 +359,89 @@ document.getElementById("export_csv").addEventListener("click", async (event) =>
    link.href = window.URL.createObjectURL(blob);
    link.click();
 })
+
+function export_numpy(the_data, name) {
+   var link = document.createElement('a');
+   link.download = name;
+   var blob = new Blob([the_data], { type: 'application/octet-stream' });
+   link.href = window.URL.createObjectURL(blob);
+   link.click();
+}
+
+document.getElementById("export_npy").addEventListener("click", async (event) => {
+   export_numpy(await get_field_npy(document.getElementById("numpy_dtype").value), 'heateq_state.npy');
+})
+
+document.getElementById("export_npz").addEventListener("click", async (event) => {
+   export_numpy(await get_fields_npz(document.getElementById("numpy_dtype").value), 'heateq_state.npz');
+})
+
+function vtk_lengths() {
+   return [
+      Number(document.getElementById("vtk_x_length").value),
+      Number(document.getElementById("vtk_y_length").value),
+   ];
+}
+
+document.getElementById("export_vti").addEventListener("click", async (event) => {
+   try {
+      export_numpy(await get_vti(...vtk_lengths()), 'heateq_state.vti');
+   } catch (e) {
+      showMessage(e);
+   }
+})
+
+// every frame downloads as it is taken, and the .pvd lists them for paraview
+document.getElementById("add_vtk_frame").addEventListener("click", async (event) => {
+   try {
+      const frame = await add_vtk_frame(document.getElementById("vtk_prefix").value, ...vtk_lengths());
+      export_numpy(frame.bytes, frame.name);
+   } catch (e) {
+      showMessage(e);
+   }
+})
+
+document.getElementById("export_pvd").addEventListener("click", async (event) => {
+   try {
+      const pvd = get_vtk_pvd();
+      export_numpy(pvd.bytes, pvd.name);
+   } catch (e) {
+      showMessage(e);
+   }
+})
+
+document.getElementById("send_numpy_to_gpu").addEventListener("click", async (event) => {
+   var file = document.getElementById("take_in_numpy").files[0];
+   if (!file) {
+      showMessage("no file!")
+      return;
+   }
+   stop_compute = true;
+   cancel_chunked_run();
+   try {
+      var kappa = await load_numpy(new Uint8Array(await file.arrayBuffer()));
+   } catch (e) {
+      showMessage(e);
+      return;
+   }
+   document.getElementById("kappa").value = kappa;
+   update_values(
+      document.getElementById("N_add").value,
+      document.getElementById("kappa").value,
+      document.getElementById("delta_t").value,
+      document.getElementById("min_T").value,
+      document.getElementById("max_T").value,
+   );
+   set_integrator(document.getElementById("integrator").value);
+   apply_view_field();
+   apply_color_range();
+   apply_isotherm_overlay();
+   var width = give_current_width();
+   var height = give_current_height();
+   document.getElementById("width_val").value = width;
+   document.getElementById("height_val").value = height;
+   current_grid_shape.textContent = `${width}x${height}`;
+   document.getElementById("total_energy_goes_here").textContent = await (get_total_energy_in_one());
+   show_time();
+   showMessage(`loaded ${file.name}`);
+})
@@ -0,0 +1,39 @@
+struct AccumulateParams {
+   width: u32,
+   height: u32,
+   threshold: f32,
+   // the step that just finished, 0 when we are only starting the maps off
+   delta_t: f32,
+}
+
+@group(0) @binding(0) var<storage, read> data: array<f32>;
+@group(0) @binding(1) var<storage, read_write> peak: array<f32>;
+@group(0) @binding(2) var<storage, read_write> time_above: array<f32>;
+// -1 until the cell first reaches the threshold
+@group(0) @binding(3) var<storage, read_write> first_arrival: array<f32>;
+// simulated time before the step that just finished, and its kahan compensation
+@group(0) @binding(4) var<storage, read> clock: array<f32, 2>;
+@group(0) @binding(5) var<uniform> params: AccumulateParams;
+
+// invocations in a row of workgroups, see linear_workgroups in webgpuheat.rs
+const ROW_LENGTH: u32 = 4194240u;
+
+// folds the field as it is at the end of a step into the maps. time above the threshold
+//    counts the whole step if the cell ends it at or above the threshold
+@compute
+@workgroup_size(64,1,1)
+fn main(
+   @builtin(global_invocation_id) gid: vec3<u32>
+) {
+   let cell = gid.x + gid.y * ROW_LENGTH;
+   if (cell >= params.width * params.height) {return;}
+
+   let here = data[cell];
+   peak[cell] = max(peak[cell], here);
+   if (here >= params.threshold) {
+      time_above[cell] += params.delta_t;
+      if (first_arrival[cell] < 0.0f) {
+         first_arrival[cell] = clock[0] + params.delta_t;
+      }
+   }
+}
@@ -0,0 +1,153 @@
+use bytemuck::cast_slice;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::flux::ViewField;
+use crate::webgpuheat::{HeatComputer, helper_basic_compute_shader, helper_compute_bind_group, helper_compute_interim_data_buffer};
+
+/// Per cell maps built up over a run, one step at a time: the hottest each cell has been,
+///    how long it has spent at or above `threshold`, and the simulated time it first got
+///    there (-1 if it never has). They keep their own clock on the gpu, started at the sim
+///    time they were made, so arrival times never wait on the cpu.
+pub struct AccumulatorMaps {
+   pub threshold: f32,
+   pub delta_t: f32,
+   pub accumulate_pipeline: wgpu::ComputePipeline,
+   pub tick_pipeline: wgpu::ComputePipeline,
+   pub peak_buffer: wgpu::Buffer,
+   pub time_above_buffer: wgpu::Buffer,
+   pub first_arrival_buffer: wgpu::Buffer,
+   pub clock_buffer: wgpu::Buffer,
+   pub param_buffer: wgpu::Buffer,
+   pub accumulate_bind_group: wgpu::BindGroup,
+   pub tick_bind_group: wgpu::BindGroup,
+   // the heat hue pipeline reading each of the three instead of data
+   pub heat_hue_bind_groups: [wgpu::BindGroup; 3],
+}
+
+impl AccumulatorMaps {
+   /// maps starting from the field as it is now. Takes the queue to start them off, so
+   ///    anything still in a pending queue should be submitted first.
+   pub fn new(heateq: &HeatComputer, device: &wgpu::Device, queue: &wgpu::Queue, threshold: f32) -> Self {
+      let cells = (heateq.width * heateq.height) as usize;
+      let size = heateq.data_buffer.size();
+
+      let accumulate_shader = device.create_shader_module(wgpu::include_wgsl!("accumulate.wgsl"));
+      let tick_shader = device.create_shader_module(wgpu::include_wgsl!("clock_tick.wgsl"));
+      let accumulate_pipeline = helper_basic_compute_shader(device, Some("Accumulate Pipeline"), &accumulate_shader);
+      let tick_pipeline = helper_basic_compute_shader(device, Some("Clock Tick Pipeline"), &tick_shader);
+
+      let peak_buffer = helper_compute_interim_data_buffer(device, Some("peak temperature"), size);
+      let time_above_buffer = helper_compute_interim_data_buffer(device, Some("time above threshold"), size);
+      let first_arrival_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("first arrival"),
+         contents: cast_slice(&vec![-1f32; cells]),
+         usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::STORAGE,
+      });
+      let clock_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("accumulator clock"),
+         contents: cast_slice(&[heateq.sim_time as f32, 0.]),
+         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+      });
+      // 0 to start with, so starting the maps off adds no time
+      let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("accumulate params"),
+         contents: &Self::params(heateq, threshold, 0.),
+         usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      });
+
+      let accumulate_bind_group = helper_compute_bind_group(
+         device, None, &accumulate_pipeline,
+         &[&heateq.data_buffer, &peak_buffer, &time_above_buffer, &first_arrival_buffer, &clock_buffer, &param_buffer]
+      );
+      let tick_bind_group = helper_compute_bind_group(
+         device, None, &tick_pipeline, &[&clock_buffer, &param_buffer]
+      );
+      let heat_hue_bind_groups = [&peak_buffer, &time_above_buffer, &first_arrival_buffer].map(|buffer|
+         helper_compute_bind_group(
+            device, None, &heateq.heat_hue_pipeline,
+            &[buffer, &heateq.heat_map_buffer, &heateq.vis_minT_buffer, &heateq.vis_maxT_buffer,
+               &heateq.width_buffer, &heateq.height_buffer, &heateq.pad_buffer]
+         )
+      );
+
+      let maps = Self {
+         threshold,
+         delta_t: 0.,
+         accumulate_pipeline,
+         tick_pipeline,
+         peak_buffer,
+         time_above_buffer,
+         first_arrival_buffer,
+         clock_buffer,
+         param_buffer,
+         accumulate_bind_group,
+         tick_bind_group,
+         heat_hue_bind_groups,
+      };
+
+      // peak starts as the field, and anything already at the threshold arrives now
+      let mut encoder = device.create_command_encoder(&Default::default());
+      encoder.copy_buffer_to_buffer(&heateq.data_buffer, 0, &maps.peak_buffer, 0, size);
+      {
+         let mut gputodo = encoder.begin_compute_pass(&Default::default());
+         gputodo.set_pipeline(&maps.accumulate_pipeline);
+         gputodo.set_bind_group(0, &maps.accumulate_bind_group, &[]);
+         let (row_workgroups, rows) = heateq.workgroup_quantity;
+         gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+      }
+      queue.submit([encoder.finish()]);
+
+      maps
+   }
+
+   fn params(heateq: &HeatComputer, threshold: f32, delta_t: f32) -> Vec<u8> {
+      let mut bytes: Vec<u8> = Vec::new();
+      bytes.extend_from_slice(cast_slice(&[heateq.width, heateq.height]));
+      bytes.extend_from_slice(cast_slice(&[threshold, delta_t]));
+      bytes
+   }
+
+   /// the step length the next records add, only written when it changed
+   pub fn set_delta_t(&mut self, heateq: &HeatComputer, queue: &wgpu::Queue, delta_t: f32) {
+      if self.delta_t == delta_t {return}
+      self.delta_t = delta_t;
+      queue.write_buffer(&self.param_buffer, 0, &Self::params(heateq, self.threshold, delta_t));
+   }
+
+   /// sets the gpu clock, for when the computer's clock is reset. Arrival times already in
+   ///    the map keep the clock they were taken with.
+   pub fn set_clock(&self, queue: &wgpu::Queue, sim_time: f64) {
+      queue.write_buffer(&self.clock_buffer, 0, cast_slice(&[sim_time as f32, 0.]));
+   }
+
+   /// adds the step that just finished and moves the clock on past it
+   pub fn record(&self, heateq: &HeatComputer, gputodo: &mut wgpu::ComputePass) {
+      gputodo.set_pipeline(&self.accumulate_pipeline);
+      gputodo.set_bind_group(0, &self.accumulate_bind_group, &[]);
+      let (row_workgroups, rows) = heateq.workgroup_quantity;
+      gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+
+      gputodo.set_pipeline(&self.tick_pipeline);
+      gputodo.set_bind_group(0, &self.tick_bind_group, &[]);
+      gputodo.dispatch_workgroups(1, 1, 1);
+   }
+
+   /// where an accumulated view lives, None for the others
+   pub fn buffer(&self, view: ViewField) -> Option<&wgpu::Buffer> {
+      match view {
+         ViewField::PeakTemperature => Some(&self.peak_buffer),
+         ViewField::TimeAbove => Some(&self.time_above_buffer),
+         ViewField::FirstArrival => Some(&self.first_arrival_buffer),
+         _ => None,
+      }
+   }
+
+   pub fn heat_hue_bind_group(&self, view: ViewField) -> Option<&wgpu::BindGroup> {
+      match view {
+         ViewField::PeakTemperature => Some(&self.heat_hue_bind_groups[0]),
+         ViewField::TimeAbove => Some(&self.heat_hue_bind_groups[1]),
+         ViewField::FirstArrival => Some(&self.heat_hue_bind_groups[2]),
+         _ => None,
+      }
+   }
+}
@@ -0,0 +1,181 @@
+use bytemuck::cast_slice;
+
+use crate::supertimestep::explicit_stable_delta_t;
+use crate::webgpuheat::{HeatComputer, helper_basic_compute_shader, helper_compute_bind_group, helper_param_buffer};
+
+// step size control for a pair whose lower order member is first order
+const SAFETY: f64 = 0.9;
+const MIN_FACTOR: f64 = 0.2;
+const MAX_FACTOR: f64 = 5.0;
+
+/// What happened to one attempted adaptive step.
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveOutcome {
+   pub accepted: bool,
+   pub delta_t: f64,
+   /// the largest error anywhere, scaled so that 1 is exactly on tolerance
+   pub error: f32,
+   /// what the next attempt will try
+   pub next_delta_t: f64,
+}
+
+/// Adaptive stepping with the embedded Heun-Euler pair. Each attempt takes an euler
+///    step into the midpoint buffer as a predictor, then heun's step into the output
+///    buffer, and the gpu reduces the difference of the two to a single max norm scaled
+///    by atol + rtol*|T|. Accepted steps move output into data, rejected ones are rolled
+///    back by just never doing that move. delta_t is then steered toward the tolerance,
+///    but never past the explicit limit since heun is no more stable than euler.
+pub struct AdaptiveStepper {
+   pub atol: f32,
+   pub rtol: f32,
+   /// what the next attempt will try
+   pub delta_t: f64,
+   pub error_pipeline: wgpu::ComputePipeline,
+   pub predictor_delta_t_buffer: wgpu::Buffer,
+   pub heun_param_buffer: wgpu::Buffer,
+   pub error_buffer: wgpu::Buffer,
+   pub error_readback_buffer: wgpu::Buffer,
+   pub predictor_bind_group: wgpu::BindGroup,
+   pub error_bind_group: wgpu::BindGroup,
+}
+
+impl AdaptiveStepper {
+   pub fn new(heateq: &HeatComputer, device: &wgpu::Device, atol: f32, rtol: f32) -> Self {
+      let error_shader = device.create_shader_module(wgpu::include_wgsl!("heun_error.wgsl"));
+      let error_pipeline = helper_basic_compute_shader(device, Some("Heun Error Pipeline"), &error_shader);
+
+      let predictor_delta_t_buffer = helper_param_buffer(device, Some("adaptive predictor delta_t"), 4);
+      let heun_param_buffer = helper_param_buffer(device, Some("adaptive heun params"), 16);
+      let error_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("adaptive error buffer"),
+         size: 4,
+         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+         mapped_at_creation: false,
+      });
+      let error_readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("adaptive error readback"),
+         size: 4,
+         usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+         mapped_at_creation: false,
+      });
+
+      // euler predictor, the same as the RK2 midpoint but with its own delta_t
+      let predictor_bind_group = helper_compute_bind_group(
+         device, None, &heateq.iterate_pipeline,
+         &[&heateq.data_buffer, &heateq.laplacian_buffer, &heateq.midpoint_buffer, &heateq.width_buffer,
+            &heateq.height_buffer, &heateq.kappa_buffer, &predictor_delta_t_buffer]
+      );
+      let error_bind_group = helper_compute_bind_group(
+         device, None, &error_pipeline,
+         &[&heateq.data_buffer, &heateq.laplacian_buffer, &heateq.midpoint_laplacian_buffer, &heateq.output_buffer,
+            &error_buffer, &heateq.width_buffer, &heateq.height_buffer, &heun_param_buffer]
+      );
+
+      Self {
+         atol,
+         rtol,
+         // a quarter of the limit is a cautious first guess, the controller fixes it quickly
+         delta_t: Self::max_delta_t(heateq) / 4.,
+         error_pipeline,
+         predictor_delta_t_buffer,
+         heun_param_buffer,
+         error_buffer,
+         error_readback_buffer,
+         predictor_bind_group,
+         error_bind_group,
+      }
+   }
+
+   /// the largest delta_t the controller will ever try. Without any diffusion there is no
+   ///    limit, so the fixed delta_t stands in for one rather than letting the clock jump to
+   ///    infinity.
+   pub fn max_delta_t(heateq: &HeatComputer) -> f64 {
+      let limit = explicit_stable_delta_t(heateq.kappa, heateq.width, heateq.height);
+      if limit.is_finite() {limit} else {heateq.delta_t as f64}
+   }
+
+   /// where the controller goes next given the scaled error of a step of delta_t
+   pub fn next_delta_t(delta_t: f64, error: f32, max_delta_t: f64) -> f64 {
+      let factor = if error.is_nan() {
+         MIN_FACTOR
+      } else if error <= 0. {
+         MAX_FACTOR
+      } else {
+         (SAFETY * (error as f64).powf(-0.5)).clamp(MIN_FACTOR, MAX_FACTOR)
+      };
+      let factor = if error > 1. {factor.min(1.)} else {factor};
+      (delta_t * factor).min(max_delta_t)
+   }
+
+   /// tries one step of delta_t from the current data buffer, waiting for the gpu to say how
+   ///    big the error was. data is only touched (apart from the boundary conditions) if the
+   ///    step is accepted.
+   pub async fn attempt(
+      &self,
+      heateq: &HeatComputer,
+      device: &wgpu::Device,
+      queue: &wgpu::Queue,
+      delta_t: f64,
+   ) -> anyhow::Result<(bool, f32)> {
+      let (row_workgroups, rows) = heateq.workgroup_quantity;
+      let x_workgroup_quantity = heateq.width.div_ceil(8);
+      let y_workgroup_quantity = heateq.height.div_ceil(8);
+
+      queue.write_buffer(&self.predictor_delta_t_buffer, 0, cast_slice(&[delta_t as f32]));
+      queue.write_buffer(&self.heun_param_buffer, 0,
+         cast_slice(&[heateq.kappa * delta_t as f32 / 2., self.atol, self.rtol, 0.]));
+
+      let mut encoder = device.create_command_encoder(&Default::default());
+      encoder.clear_buffer(&self.error_buffer, 0, None);
+      {
+         let mut gputodo = encoder.begin_compute_pass(&Default::default());
+
+         heateq.record_boundary_conditions(&mut gputodo);
+
+         gputodo.set_pipeline(&heateq.laplacian_pipeline);
+         gputodo.set_bind_group(0, &heateq.stage_one_bind_group, &[]);
+         gputodo.dispatch_workgroups(x_workgroup_quantity, y_workgroup_quantity, 1);
+
+         gputodo.set_pipeline(&heateq.iterate_pipeline);
+         gputodo.set_bind_group(0, &self.predictor_bind_group, &[]);
+         gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+         heateq.record_midpoint_boundary_conditions(&mut gputodo);
+
+         gputodo.set_pipeline(&heateq.laplacian_pipeline);
+         gputodo.set_bind_group(0, &heateq.stage_three_bind_group, &[]);
+         gputodo.dispatch_workgroups(x_workgroup_quantity, y_workgroup_quantity, 1);
+
+         gputodo.set_pipeline(&self.error_pipeline);
+         gputodo.set_bind_group(0, &self.error_bind_group, &[]);
+         gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+      }
+      encoder.copy_buffer_to_buffer(&self.error_buffer, 0, &self.error_readback_buffer, 0, 4);
+      let (sender, receiver) = tokio::sync::oneshot::channel();
+      encoder.map_buffer_on_submit(&self.error_readback_buffer, wgpu::MapMode::Read, ..,
+         move |result| {_ = sender.send(result);});
+      queue.submit([encoder.finish()]);
+
+      device.poll(wgpu::PollType::wait_indefinitely())?;
+      receiver.await??;
+
+      let error = {
+         let mapped = self.error_readback_buffer.get_mapped_range(..);
+         f32::from_bits(cast_slice::<u8, u32>(&mapped)[0])
+      };
+      self.error_readback_buffer.unmap();
+
+      let accepted = error <= 1.;
+      if accepted {
+         let mut encoder = device.create_command_encoder(&Default::default());
+         {
+            let mut gputodo = encoder.begin_compute_pass(&Default::default());
+            gputodo.set_pipeline(&heateq.buffer_move_pipeline);
+            gputodo.set_bind_group(0, &heateq.stage_five_bind_group, &[]);
+            gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+         }
+         queue.submit([encoder.finish()]);
+      }
+
+      Ok((accepted, error))
+   }
+}
@@ -0,0 +1,111 @@
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::tridiagonal::{BatchedTridiagonalSolver, TridiagonalLayout};
+use crate::webgpuheat::{HeatComputer, helper_basic_compute_shader, helper_compute_bind_group};
+
+/// Peaceman-Rachford alternating direction implicit stepping. Each step of delta_t is a
+///    half step implicit in x and explicit in y into the midpoint buffer, then a half step
+///    implicit in y and explicit in x back into data, each implicit part being a batch of
+///    tridiagonal solves. Second order and unconditionally stable, so delta_t is only
+///    limited by how accurate we want to be. Built for one kappa and delta_t.
+pub struct AdiStepper {
+   pub kappa: f32,
+   pub delta_t: f32,
+   pub rhs_pipeline: wgpu::ComputePipeline,
+   pub x_param_buffer: wgpu::Buffer,
+   pub y_param_buffer: wgpu::Buffer,
+   pub x_rhs_bind_group: wgpu::BindGroup,
+   pub y_rhs_bind_group: wgpu::BindGroup,
+   // insulating the midpoint between the two half steps
+   pub midpoint_boundary_bind_group: wgpu::BindGroup,
+   pub row_solver: BatchedTridiagonalSolver,
+   pub column_solver: BatchedTridiagonalSolver,
+}
+
+fn adi_params(width: u32, height: u32, implicit_along_x: bool, r_implicit: f32, r_explicit: f32) -> Vec<u8> {
+   let mut bytes: Vec<u8> = Vec::new();
+   bytes.extend_from_slice(bytemuck::cast_slice(&[width, height, implicit_along_x as u32, 0]));
+   bytes.extend_from_slice(bytemuck::cast_slice(&[r_implicit, r_explicit, 0., 0.]));
+   bytes
+}
+
+impl AdiStepper {
+   pub fn new(heateq: &HeatComputer, device: &wgpu::Device, kappa: f32, delta_t: f32) -> Self {
+      let (width, height) = (heateq.width, heateq.height);
+      // same spacing as laplacian.wgsl, delta_x = 1/width and delta_y = 1/height
+      let r_x = kappa * delta_t / 2. * (width as f32).powi(2);
+      let r_y = kappa * delta_t / 2. * (height as f32).powi(2);
+
+      let rhs_shader = device.create_shader_module(wgpu::include_wgsl!("adi_rhs.wgsl"));
+      let rhs_pipeline = helper_basic_compute_shader(device, Some("ADI Systems Pipeline"), &rhs_shader);
+
+      let x_param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("adi x sweep params"),
+         contents: &adi_params(width, height, true, r_x, r_y),
+         usage: wgpu::BufferUsages::UNIFORM,
+      });
+      let y_param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("adi y sweep params"),
+         contents: &adi_params(width, height, false, r_y, r_x),
+         usage: wgpu::BufferUsages::UNIFORM,
+      });
+
+      let row_solver = BatchedTridiagonalSolver::new(
+         device, TridiagonalLayout::rows(width, height), &heateq.midpoint_buffer
+      );
+      let column_solver = BatchedTridiagonalSolver::new_sharing(
+         device, TridiagonalLayout::columns(width, height),
+         &row_solver.system_buffer, &row_solver.scratch_buffer, &heateq.data_buffer
+      );
+
+      let x_rhs_bind_group = helper_compute_bind_group(
+         device, None, &rhs_pipeline,
+         &[&heateq.data_buffer, &row_solver.system_buffer, &x_param_buffer, &heateq.boundary_buffer]
+      );
+      let y_rhs_bind_group = helper_compute_bind_group(
+         device, None, &rhs_pipeline,
+         &[&heateq.midpoint_buffer, &column_solver.system_buffer, &y_param_buffer, &heateq.boundary_buffer]
+      );
+      let midpoint_boundary_bind_group = helper_compute_bind_group(
+         device, None, &heateq.fix_boundary_conditions_ppln,
+         &[&heateq.midpoint_buffer, &heateq.width_buffer, &heateq.height_buffer, &heateq.boundary_buffer]
+      );
+
+      Self {
+         kappa,
+         delta_t,
+         rhs_pipeline,
+         x_param_buffer,
+         y_param_buffer,
+         x_rhs_bind_group,
+         y_rhs_bind_group,
+         midpoint_boundary_bind_group,
+         row_solver,
+         column_solver,
+      }
+   }
+
+   pub fn record_step(&self, heateq: &HeatComputer, gputodo: &mut wgpu::ComputePass) {
+      let x_workgroup_quantity = heateq.width.div_ceil(8);
+      let y_workgroup_quantity = heateq.height.div_ceil(8);
+      let boundary_conds_wg_quant = (heateq.width*2 + heateq.height*2).div_ceil(heateq.workgroup_size);
+
+      heateq.record_boundary_conditions(gputodo);
+
+      // implicit in x, data into midpoint
+      gputodo.set_pipeline(&self.rhs_pipeline);
+      gputodo.set_bind_group(0, &self.x_rhs_bind_group, &[]);
+      gputodo.dispatch_workgroups(x_workgroup_quantity, y_workgroup_quantity, 1);
+      self.row_solver.record_solve(gputodo);
+
+      gputodo.set_pipeline(&heateq.fix_boundary_conditions_ppln);
+      gputodo.set_bind_group(0, &self.midpoint_boundary_bind_group, &[]);
+      gputodo.dispatch_workgroups(boundary_conds_wg_quant, 1 , 1);
+
+      // implicit in y, midpoint back into data
+      gputodo.set_pipeline(&self.rhs_pipeline);
+      gputodo.set_bind_group(0, &self.y_rhs_bind_group, &[]);
+      gputodo.dispatch_workgroups(x_workgroup_quantity, y_workgroup_quantity, 1);
+      self.column_solver.record_solve(gputodo);
+   }
+}
@@ -0,0 +1,80 @@
+struct AdiParams {
+   width: u32,
+   height: u32,
+   // 1 when this half step is implicit along x (so the systems are rows), 0 for columns
+   implicit_along_x: u32,
+   _pad: u32,
+   // kappa * delta_t / 2 / delta^2 in the implicit and the explicit direction
+   r_implicit: f32,
+   r_explicit: f32,
+   _pad1: f32,
+   _pad2: f32,
+}
+
+@group(0) @binding(0) var<storage, read> data: array<f32>;
+@group(0) @binding(1) var<storage, read_write> systems: array<vec4<f32>>;
+@group(0) @binding(2) var<uniform> params: AdiParams;
+// the same edge conditions as boundary_cond.wgsl, in the order y=0, x=0, y=1, x=1
+@group(0) @binding(3) var<uniform> edges: EdgeConditions;
+
+struct EdgeConditions {
+   kinds: vec4<u32>,
+   values: vec4<f32>,
+}
+
+// builds one tridiagonal equation per cell for a Peaceman-Rachford half step,
+//    (1 - r_i delta_i^2) T* = (1 + r_e delta_e^2) T
+@compute
+@workgroup_size(8,8,1)
+fn main(
+   @builtin(global_invocation_id) gid: vec3<u32>
+) {
+   if ((gid.x >= params.width) | (gid.y >= params.height)) {return;}
+
+   let index = gid.x + gid.y * params.width;
+
+   // k runs along the implicit line, m across it
+   var k = gid.x;
+   var n = params.width;
+   var m = gid.y;
+   var m_len = params.height;
+   var explicit_step = params.width;
+   // which edges the two ends of the implicit line are on
+   var first_edge = 1u;
+   var last_edge = 3u;
+   if (params.implicit_along_x == 0) {
+      first_edge = 0u;
+      last_edge = 2u;
+      k = gid.y;
+      n = params.height;
+      m = gid.x;
+      m_len = params.width;
+      explicit_step = 1u;
+   }
+
+   let here = data[index];
+
+   if ((m == 0) | (m >= m_len - 1)) {
+      // whole lines on the edge are left alone, the boundary shader fixes them afterwards
+      systems[index] = vec4<f32>(0.0f, 1.0f, 0.0f, here);
+   } else if ((k == 0) & (edges.kinds[first_edge] == 1u)) {
+      systems[index] = vec4<f32>(0.0f, 1.0f, 0.0f, edges.values[first_edge]);
+   } else if (k == 0) {
+      // insulated, the edge cell takes the value of its neighbour
+      systems[index] = vec4<f32>(0.0f, 1.0f, -1.0f, 0.0f);
+   } else if ((k >= n - 1) & (edges.kinds[last_edge] == 1u)) {
+      systems[index] = vec4<f32>(0.0f, 1.0f, 0.0f, edges.values[last_edge]);
+   } else if (k >= n - 1) {
+      systems[index] = vec4<f32>(-1.0f, 1.0f, 0.0f, 0.0f);
+   } else {
+      let explicit_part = here + params.r_explicit * (
+         data[index + explicit_step] - 2.0f * here + data[index - explicit_step]
+      );
+      systems[index] = vec4<f32>(
+         -params.r_implicit,
+         1.0f + 2.0f * params.r_implicit,
+         -params.r_implicit,
+         explicit_part
+      );
+   }
+}
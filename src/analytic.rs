@@ -0,0 +1,142 @@
+use std::f64::consts::PI;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::rectgrid::RectGrid;
+
+/// Exact solutions of T_t = kappa lap T for checking the solver against.
+///
+/// They live on the unit square the way the solver actually sees it. The insulated walls
+///    sit halfway between each edge cell and its neighbour (that is where copying the
+///    neighbour in puts them), and the laplacian spaces cells 1/width apart, so the
+///    insulated domain is really (width-2)/width by (height-2)/height. Positions here run
+///    from 0 to 1 between the walls, and kappa is scaled up per axis to match, so a
+///    solution is compared with the solver on the solver's own geometry and the only error
+///    left is the scheme's.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnalyticSolution {
+   /// offset + amplitude cos(m pi x) cos(n pi y), decaying at kappa pi^2 (m^2 + n^2).
+   ///    Exact for the insulated square.
+   CosineMode { m: u32, n: u32, amplitude: f64, offset: f64 },
+   /// a gaussian of width sigma about (x0,y0) spreading on an infinite plane. Only close
+   ///    to the insulated square while it is still well clear of the walls.
+   Gaussian { sigma: f64, amplitude: f64, x0: f64, y0: f64 },
+   /// a step from `left` to `right` at x0 (or y0) smoothing out as an erf. Only close to
+   ///    the insulated square until the smoothing reaches the walls.
+   ErfX { x0: f64, left: f64, right: f64 },
+   ErfY { y0: f64, left: f64, right: f64 },
+}
+
+/// L2 is over the area, so sqrt of the mean square error on the unit square. Only the
+///    cells between the walls count, the edge cells are copies.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErrorNorms {
+   pub l2: f64,
+   pub linf: f64,
+}
+
+/// solutions only go on grids with a cell between the walls, so 3x3 and up
+pub fn check_grid_size(width: usize, height: usize) -> Result<(), String> {
+   match width >= 3 && height >= 3 {
+      true => Ok(()),
+      false => Err(format!("a {}x{} grid has no cells between the walls to compare", width, height))
+   }
+}
+
+/// where cell (i,j) of a width x height solver grid is, from 0 to 1 between the walls
+pub fn solver_position(i: usize, j: usize, width: usize, height: usize) -> (f64, f64) {
+   ((i as f64 - 0.5) / (width - 2) as f64, (j as f64 - 0.5) / (height - 2) as f64)
+}
+
+/// kappa in the coordinates of solver_position, which stretch each axis by a different amount
+pub fn solver_diffusivity(kappa: f64, width: usize, height: usize) -> (f64, f64) {
+   let length_x = (width - 2) as f64 / width as f64;
+   let length_y = (height - 2) as f64 / height as f64;
+   (kappa / (length_x * length_x), kappa / (length_y * length_y))
+}
+
+impl AnalyticSolution {
+   /// parses a name and up to four parameters, anything left out takes a default
+   pub fn from_parts(name: &str, params: &[f64]) -> Result<Self, String> {
+      let param = |n: usize, default: f64| params.get(n).copied().unwrap_or(default);
+      match name {
+         "cosine" => Ok(AnalyticSolution::CosineMode {
+            m: param(0, 1.) as u32, n: param(1, 1.) as u32, amplitude: param(2, 100.), offset: param(3, 100.)
+         }),
+         "gaussian" => Ok(AnalyticSolution::Gaussian {
+            sigma: param(0, 0.05), amplitude: param(1, 400.), x0: param(2, 0.5), y0: param(3, 0.5)
+         }),
+         "erf_x" => Ok(AnalyticSolution::ErfX { x0: param(0, 0.5), left: param(1, 0.), right: param(2, 400.) }),
+         "erf_y" => Ok(AnalyticSolution::ErfY { y0: param(0, 0.5), left: param(1, 0.), right: param(2, 400.) }),
+         other => Err(format!("unknown solution {}, expected cosine, gaussian, erf_x or erf_y", other))
+      }
+   }
+
+   /// T at (x,y) and time t, with kappa_x and kappa_y from solver_diffusivity
+   pub fn evaluate(&self, x: f64, y: f64, t: f64, kappa_x: f64, kappa_y: f64) -> f64 {
+      match *self {
+         AnalyticSolution::CosineMode { m, n, amplitude, offset } => {
+            let (m, n) = (m as f64, n as f64);
+            let rate = PI * PI * (m * m * kappa_x + n * n * kappa_y);
+            offset + amplitude * (m * PI * x).cos() * (n * PI * y).cos() * (-rate * t).exp()
+         }
+         AnalyticSolution::Gaussian { sigma, amplitude, x0, y0 } => {
+            // each axis spreads on its own, variance sigma^2 + 2 kappa t
+            let variance_x = sigma * sigma + 2. * kappa_x * t;
+            let variance_y = sigma * sigma + 2. * kappa_y * t;
+            amplitude * sigma * sigma / (variance_x * variance_y).sqrt()
+               * (-(x - x0).powi(2) / (2. * variance_x) - (y - y0).powi(2) / (2. * variance_y)).exp()
+         }
+         AnalyticSolution::ErfX { x0, left, right } => erf_step(x - x0, t, kappa_x, left, right),
+         AnalyticSolution::ErfY { y0, left, right } => erf_step(y - y0, t, kappa_y, left, right),
+      }
+   }
+
+   /// the solution at time t on a width x height grid laid out like the solver's, edge
+   ///    cells included
+   pub fn on_grid(&self, width: usize, height: usize, t: f64, kappa: f64) -> RectGrid {
+      let (kappa_x, kappa_y) = solver_diffusivity(kappa, width, height);
+      let data = (0..width * height).map(|n| {
+         let (x, y) = solver_position(n % width, n / width, width, height);
+         self.evaluate(x, y, t, kappa_x, kappa_y) as f32
+      }).collect();
+      RectGrid::newfromdata(width, height, data)
+   }
+
+   /// how far `solution` is from this solution at time t
+   pub fn error(&self, solution: &RectGrid, t: f64, kappa: f64) -> ErrorNorms {
+      error_norms(solution, &self.on_grid(solution.width(), solution.height(), t, kappa))
+   }
+}
+
+fn erf_step(distance: f64, t: f64, kappa: f64, left: f64, right: f64) -> f64 {
+   let shape = if t > 0. {erf(distance / (2. * (kappa * t).sqrt()))} else {distance.signum()};
+   0.5 * (left + right) + 0.5 * (right - left) * shape
+}
+
+/// erf to about 1e-7, Abramowitz and Stegun 7.1.26, which is plenty next to f32 fields
+pub fn erf(x: f64) -> f64 {
+   let t = 1. / (1. + 0.3275911 * x.abs());
+   let polynomial = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+   (1. - polynomial * (-x * x).exp()).copysign(x)
+}
+
+/// error of `solution` against `exact` over the cells between the walls
+pub fn error_norms(solution: &RectGrid, exact: &RectGrid) -> ErrorNorms {
+   assert_eq!((solution.width(), solution.height()), (exact.width(), exact.height()));
+   let (width, height) = (solution.width(), solution.height());
+
+   let mut sum_squares = 0.;
+   let mut linf: f64 = 0.;
+   for j in 1..height - 1 {
+      for i in 1..width - 1 {
+         let error = (solution.getelement(i, j) - exact.getelement(i, j)) as f64;
+         sum_squares += error * error;
+         linf = linf.max(error.abs());
+      }
+   }
+
+   ErrorNorms { l2: (sum_squares / ((width - 2) * (height - 2)) as f64).sqrt(), linf }
+}
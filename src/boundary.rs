@@ -0,0 +1,222 @@
+use bytemuck::cast_slice;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::webgpuheat::{HeatComputer, helper_basic_compute_shader, helper_compute_bind_group};
+
+/// One of the four edges of the unit square, in the order the shaders keep them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+   Y0,
+   X0,
+   Y1,
+   X1,
+}
+
+impl Edge {
+   pub const ALL: [Edge; 4] = [Edge::Y0, Edge::X0, Edge::Y1, Edge::X1];
+
+   pub fn index(&self) -> usize {
+      match self {
+         Edge::Y0 => 0,
+         Edge::X0 => 1,
+         Edge::Y1 => 2,
+         Edge::X1 => 3,
+      }
+   }
+}
+
+impl std::str::FromStr for Edge {
+   type Err = String;
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      match s {
+         "y0" => Ok(Edge::Y0),
+         "x0" => Ok(Edge::X0),
+         "y1" => Ok(Edge::Y1),
+         "x1" => Ok(Edge::X1),
+         other => Err(format!("unknown edge {}, expected y0, x0, y1 or x1", other))
+      }
+   }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoundaryCondition {
+   /// no heat crosses the edge, the edge cells copy their inward neighbours
+   Insulated,
+   /// the edge cells are held at this temperature
+   Fixed(f32),
+}
+
+impl BoundaryCondition {
+   /// parses the kind name, with value as the temperature of a fixed edge
+   pub fn from_parts(kind: &str, value: Option<f32>) -> Result<Self, String> {
+      match (kind, value) {
+         ("insulated", _) => Ok(BoundaryCondition::Insulated),
+         ("fixed", Some(value)) => Ok(BoundaryCondition::Fixed(value)),
+         ("fixed", None) => Err("a fixed edge needs a temperature".to_string()),
+         (other, _) => Err(format!("unknown boundary condition {}, expected insulated or fixed", other))
+      }
+   }
+
+   /// the 32 bytes of EdgeConditions in boundary_cond.wgsl and adi_rhs.wgsl
+   pub fn as_uniform(conditions: &[BoundaryCondition; 4]) -> Vec<u8> {
+      let kinds = conditions.map(|condition| match condition {
+         BoundaryCondition::Insulated => 0u32,
+         BoundaryCondition::Fixed(_) => 1,
+      });
+      let values = conditions.map(|condition| match condition {
+         BoundaryCondition::Insulated => 0f32,
+         BoundaryCondition::Fixed(value) => value,
+      });
+      let mut bytes: Vec<u8> = Vec::new();
+      bytes.extend_from_slice(cast_slice(&kinds));
+      bytes.extend_from_slice(cast_slice(&values));
+      bytes
+   }
+}
+
+/// Heat through each edge, positive when it leaves the grid, in the units where the heat
+///    equation is T_t = kappa lap T, so the energy of the grid is the integral of T.
+///    Totals count from when tracking started, as does energy_change, so energy_change +
+///    net_total is what the scheme failed to conserve and should sit near 0.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeFluxReport {
+   pub rate_y0: f32,
+   pub rate_x0: f32,
+   pub rate_y1: f32,
+   pub rate_x1: f32,
+   pub total_y0: f32,
+   pub total_x0: f32,
+   pub total_y1: f32,
+   pub total_x1: f32,
+   pub net_rate: f32,
+   pub net_total: f32,
+   /// the integral of T over the cells inside the edges, which are the only ones that evolve
+   pub interior_energy: f32,
+   pub energy_change: f32,
+   pub residual: f32,
+}
+
+impl EdgeFluxReport {
+   /// from the tally edge_flux.wgsl keeps and the sum of the whole field
+   pub fn from_tally(tally: &[f32], field_sum: f32, cell_area: f32, initial_interior_energy: f32) -> Self {
+      let interior_energy = (field_sum - tally[12]) * cell_area;
+      let net_total = tally[4] + tally[5] + tally[6] + tally[7];
+      let energy_change = interior_energy - initial_interior_energy;
+      Self {
+         rate_y0: tally[0],
+         rate_x0: tally[1],
+         rate_y1: tally[2],
+         rate_x1: tally[3],
+         total_y0: tally[4],
+         total_x0: tally[5],
+         total_y1: tally[6],
+         total_x1: tally[7],
+         net_rate: tally[0] + tally[1] + tally[2] + tally[3],
+         net_total,
+         interior_energy,
+         energy_change,
+         residual: energy_change + net_total,
+      }
+   }
+}
+
+/// Integrates the heat leaving through each edge over the run. After every step the edge
+///    conditions are put back and one workgroup takes the differences across each edge,
+///    adding the trapezoid between this step's rate and the last one to the totals, so the
+///    totals are second order in delta_t whatever integrator took the steps.
+pub struct EdgeFluxTracker {
+   pub kappa: f32,
+   pub delta_t: f32,
+   /// interior energy when tracking started, which energy_change is measured from
+   pub initial_interior_energy: f32,
+   pub flux_pipeline: wgpu::ComputePipeline,
+   pub param_buffer: wgpu::Buffer,
+   pub tally_buffer: wgpu::Buffer,
+   pub readback_buffer: wgpu::Buffer,
+   pub flux_bind_group: wgpu::BindGroup,
+}
+
+impl EdgeFluxTracker {
+   /// the totals start at 0, and the first record only takes the rates
+   pub fn new(heateq: &HeatComputer, device: &wgpu::Device) -> Self {
+      let flux_shader = device.create_shader_module(wgpu::include_wgsl!("edge_flux.wgsl"));
+      let flux_pipeline = helper_basic_compute_shader(device, Some("Edge Flux Pipeline"), &flux_shader);
+
+      let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("edge flux params"),
+         contents: &Self::params(heateq, heateq.kappa, heateq.delta_t),
+         usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      });
+      let tally_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("edge flux tally"),
+         contents: cast_slice(&[0f32; 16]),
+         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+      });
+      let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("edge flux readback"),
+         size: 64,
+         usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+         mapped_at_creation: false,
+      });
+
+      let flux_bind_group = helper_compute_bind_group(
+         device, None, &flux_pipeline, &[&heateq.data_buffer, &tally_buffer, &param_buffer]
+      );
+
+      Self {
+         kappa: heateq.kappa,
+         delta_t: heateq.delta_t,
+         initial_interior_energy: 0.,
+         flux_pipeline,
+         param_buffer,
+         tally_buffer,
+         readback_buffer,
+         flux_bind_group,
+      }
+   }
+
+   fn params(heateq: &HeatComputer, kappa: f32, delta_t: f32) -> Vec<u8> {
+      let mut bytes: Vec<u8> = Vec::new();
+      bytes.extend_from_slice(cast_slice(&[heateq.width, heateq.height, 0, 0]));
+      bytes.extend_from_slice(cast_slice(&[kappa, delta_t, 0., 0.]));
+      bytes
+   }
+
+   /// what the next records integrate with, only written when it changed
+   pub fn set_params(&mut self, heateq: &HeatComputer, queue: &wgpu::Queue, kappa: f32, delta_t: f32) {
+      if self.kappa == kappa && self.delta_t == delta_t {return}
+      self.kappa = kappa;
+      self.delta_t = delta_t;
+      queue.write_buffer(&self.param_buffer, 0, &Self::params(heateq, kappa, delta_t));
+   }
+
+   /// adds the step that just finished. The edge conditions have to be freshly applied to
+   ///    data, or an insulated edge would look like it leaks.
+   pub fn record(&self, gputodo: &mut wgpu::ComputePass) {
+      gputodo.set_pipeline(&self.flux_pipeline);
+      gputodo.set_bind_group(0, &self.flux_bind_group, &[]);
+      gputodo.dispatch_workgroups(1, 1, 1);
+   }
+
+   /// the raw 16 floats of edge_flux.wgsl
+   pub async fn read_tally(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<Vec<f32>> {
+      let mut encoder = device.create_command_encoder(&Default::default());
+      encoder.copy_buffer_to_buffer(&self.tally_buffer, 0, &self.readback_buffer, 0, 64);
+      let (sender, receiver) = tokio::sync::oneshot::channel();
+      encoder.map_buffer_on_submit(&self.readback_buffer, wgpu::MapMode::Read, ..,
+         move |result| {_ = sender.send(result);});
+      queue.submit([encoder.finish()]);
+
+      device.poll(wgpu::PollType::wait_indefinitely())?;
+      receiver.await??;
+
+      let tally: Vec<f32> = cast_slice(&self.readback_buffer.get_mapped_range(..)).to_vec();
+      self.readback_buffer.unmap();
+
+      Ok(tally)
+   }
+}
@@ -1,6 +1,23 @@
 @group(0) @binding(0) var<storage, read_write> data: array<f32>;
 @group(0) @binding(1) var<uniform> width: u32;
 @group(0) @binding(2) var<uniform> height: u32;
+@group(0) @binding(3) var<uniform> edges: EdgeConditions;
+
+// edges in the order y=0, x=0, y=1, x=1. kind 0 is insulated (copy the neighbour in),
+//    kind 1 holds the edge at a fixed temperature
+struct EdgeConditions {
+   kinds: vec4<u32>,
+   values: vec4<f32>,
+}
+
+fn edge_cell(edge: u32, copied: f32) -> f32 {
+   return select(copied, edges.values[edge], edges.kinds[edge] == 1u);
+}
+
+// corners go with a fixed y edge first, then a fixed x edge, and otherwise are insulated
+fn corner_cell(y_edge: u32, x_edge: u32, copied: f32) -> f32 {
+   return edge_cell(y_edge, edge_cell(x_edge, copied));
+}
 
 @compute// Entrypoint
 @workgroup_size(64,1,1)
@@ -17,17 +34,21 @@ fn main(
    // we need 2 * width + 2 * height with different behaviours for each side
    // regard adding width as ostensibly y+=delta_y since each y coordinate position is separated by a width
 
+   // an ensemble keeps one field per member one after the other, gid.z says which member's
+   //    edges these are. a single field is dispatched with z of 1 so this is 0
+   let m = gid.z * width * height;
+
 
    if (gid.x < width) {                                               // side y=0 line
       if (gid.x == 0) {
          //corner, set (0,0) value to (delta_x,delta_y) value
-         data[0] = data[width + 1];
-      } else if (gid.x == width){
+         data[m] = corner_cell(0u, 1u, data[m + width + 1]);
+      } else if (gid.x == width - 1){
          //corner, set (1,0) value to (1 - delta_x,delta_y) value
-         data[width] = data[2 * width - 2];
+         data[m + width - 1] = corner_cell(0u, 3u, data[m + 2 * width - 2]);
       } else {
          // set (x,0) values to (x,delta_y) values
-         data[gid.x] = data[gid.x + width];
+         data[m + gid.x] = edge_cell(0u, data[m + gid.x + width]);
       }
       return;
    } else if (gid.x < width + height ){                                     // side 2
@@ -36,21 +57,21 @@ fn main(
          return; // corners, handled by sides 1 and 3
       }
       // set (0,y) values to (delta_x, y) values
-      let indexwecareabout = (gid.x - width) * width; // y axis
-      data[indexwecareabout] = data[indexwecareabout + 1];
+      let indexwecareabout = m + (gid.x - width) * width; // y axis
+      data[indexwecareabout] = edge_cell(1u, data[indexwecareabout + 1]);
       return;
    } else if (gid.x < (2*width) + height ){                                       // side 3
       // in these cases we must regard gid.x as x+width+height since we havent subtracted that
       if (gid.x == width + height) {
          // corner, set (0,1) value to (delta_x, 1 - delta_y) value
-         data[width * (height - 1)] = data[width * (height - 2) + 1];
+         data[m + width * (height - 1)] = corner_cell(2u, 1u, data[m + width * (height - 2) + 1]);
       } else if (gid.x == (2*width) + height - 1) {
          // corner, set (1,1) value to (1 - delta_x, 1 - delta_y) value
-         data[width * height] = data[width * (height - 1) - 2];
+         data[m + width * height - 1] = corner_cell(2u, 3u, data[m + width * (height - 1) - 2]);
       } else {
          // set (x,1) values to (x, 1 - delta_y) values
-         let indexwecareabout = (gid.x - width - height ) + (width * (height - 1));
-         data[indexwecareabout] = data[indexwecareabout - width];
+         let indexwecareabout = m + (gid.x - width - height ) + (width * (height - 1));
+         data[indexwecareabout] = edge_cell(2u, data[indexwecareabout - width]);
       }
       return;
    } else if (gid.x < 2 * width + 2 * height ){                                       // side 4
@@ -58,8 +79,8 @@ fn main(
          return; // corners, handled by sides 1 and 3
       } else {
          // the +1 before we multiply by width is so we are one more row than we want, then the -1 takes us to the y=1 side of the previous row
-         let indexwecareabout = (gid.x - ((2*width) + height) + 1) * width - 1;
-         data[indexwecareabout] = data[indexwecareabout - 1];
+         let indexwecareabout = m + (gid.x - ((2*width) + height) + 1) * width - 1;
+         data[indexwecareabout] = edge_cell(3u, data[indexwecareabout - 1]);
          return;
       }
    } else { return; }
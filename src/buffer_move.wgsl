@@ -3,6 +3,9 @@
 @group(0) @binding(2) var<uniform> width: u32;
 @group(0) @binding(3) var<uniform> height: u32;
 
+// invocations in a row of workgroups, see linear_workgroups in webgpuheat.rs
+const ROW_LENGTH: u32 = 4194240u;
+
 @compute// Entrypoint
 @workgroup_size(64,1,1)
 fn main(
@@ -17,7 +20,8 @@ fn main(
    // valuable reference: https://www.w3.org/TR/WGSL/#arithmetic-expr
 
    // exit if not on grid
-   if (gid.x > (width * height)) {return;}
+   let cell = gid.x + gid.y * ROW_LENGTH;
+   if (cell >= (width * height)) {return;}
 
-   out[gid.x] = data[gid.x];
+   out[cell] = data[cell];
 }
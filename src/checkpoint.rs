@@ -0,0 +1,253 @@
+use crate::boundary::BoundaryCondition;
+use crate::colorrange::ColorRange;
+use crate::rectgrid::RectGrid;
+use crate::webgpuheat::Integrator;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+const MAGIC: &[u8; 8] = b"HEATCKPT";
+const VERSION: u32 = 1;
+
+/// Everything a run needs to carry on from where it was saved: the field with its size, the
+///    parameters, the colormap range, the edges, and the clock. Written as one little endian
+///    binary file, a magic and version, then the numbers in the order below, then the field
+///    row by row as f32.
+pub struct Checkpoint {
+   pub width: u32,
+   pub height: u32,
+   pub iteration_quantity: u32,
+   pub max_steps_per_submission: u32,
+   pub kappa: f32,
+   pub delta_t: f32,
+   pub integrator: Integrator,
+   pub vis_min_t: f32,
+   pub vis_max_t: f32,
+   pub color_range: ColorRange,
+   /// in the order y=0, x=0, y=1, x=1
+   pub boundary_conditions: [BoundaryCondition; 4],
+   pub sim_time: f64,
+   pub accepted_steps: u64,
+   pub rejected_steps: u64,
+   pub field: RectGrid,
+}
+
+/// the parts of a checkpoint a front end keeps controls for, to set them to after a restore
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunSettings {
+   pub width: u32,
+   pub height: u32,
+   pub iteration_quantity: u32,
+   pub kappa: f32,
+   pub delta_t: f32,
+   pub vis_min_t: f32,
+   pub vis_max_t: f32,
+   pub sim_time: f64,
+   pub accepted_steps: u64,
+}
+
+impl Checkpoint {
+   #[cfg(target_arch = "wasm32")]
+   pub fn settings(&self) -> RunSettings {
+      RunSettings {
+         width: self.width,
+         height: self.height,
+         iteration_quantity: self.iteration_quantity,
+         kappa: self.kappa,
+         delta_t: self.delta_t,
+         vis_min_t: self.vis_min_t,
+         vis_max_t: self.vis_max_t,
+         sim_time: self.sim_time,
+         accepted_steps: self.accepted_steps,
+      }
+   }
+
+   pub fn to_bytes(&self) -> Vec<u8> {
+      let mut bytes: Vec<u8> = Vec::with_capacity(128 + self.field.getarray().len() * 4);
+      bytes.extend_from_slice(MAGIC);
+      let mut put = |word: &[u8]| bytes.extend_from_slice(word);
+      put(&VERSION.to_le_bytes());
+      put(&self.width.to_le_bytes());
+      put(&self.height.to_le_bytes());
+      put(&self.iteration_quantity.to_le_bytes());
+      put(&self.max_steps_per_submission.to_le_bytes());
+      put(&self.kappa.to_le_bytes());
+      put(&self.delta_t.to_le_bytes());
+      let integrator: u32 = match self.integrator {
+         Integrator::Rk2 => 0,
+         Integrator::Rkl2 => 1,
+         Integrator::Adi => 2,
+      };
+      put(&integrator.to_le_bytes());
+      put(&self.vis_min_t.to_le_bytes());
+      put(&self.vis_max_t.to_le_bytes());
+      let (mode, a, b): (u32, f32, f32) = match self.color_range {
+         ColorRange::Fixed => (0, 0., 0.),
+         ColorRange::Auto => (1, 0., 0.),
+         ColorRange::Hysteresis(pad) => (2, pad, 0.),
+         ColorRange::Percentile(low, high) => (3, low, high),
+      };
+      put(&mode.to_le_bytes());
+      put(&a.to_le_bytes());
+      put(&b.to_le_bytes());
+      for condition in self.boundary_conditions.iter() {
+         let (kind, value): (u32, f32) = match condition {
+            BoundaryCondition::Insulated => (0, 0.),
+            BoundaryCondition::Fixed(t) => (1, *t),
+         };
+         put(&kind.to_le_bytes());
+         put(&value.to_le_bytes());
+      }
+      put(&self.sim_time.to_le_bytes());
+      put(&self.accepted_steps.to_le_bytes());
+      put(&self.rejected_steps.to_le_bytes());
+      for value in self.field.getarray() {
+         put(&value.to_le_bytes());
+      }
+      bytes
+   }
+
+   pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+      let mut reader = Reader { bytes, at: 0 };
+      if reader.take::<8>()? != *MAGIC {anyhow::bail!("this is not a checkpoint")}
+      let version = reader.u32()?;
+      if version != VERSION {anyhow::bail!("checkpoint version {} is not one we read, only {}", version, VERSION)}
+
+      let width = reader.u32()?;
+      let height = reader.u32()?;
+      if width < 3 || height < 3 {anyhow::bail!("a {}x{} checkpoint is too small to hold a field", width, height)}
+      let iteration_quantity = reader.u32()?;
+      let max_steps_per_submission = reader.u32()?;
+      let kappa = reader.f32()?;
+      let delta_t = reader.f32()?;
+      let integrator = match reader.u32()? {
+         0 => Integrator::Rk2,
+         1 => Integrator::Rkl2,
+         2 => Integrator::Adi,
+         other => anyhow::bail!("unknown integrator {} in checkpoint", other)
+      };
+      let vis_min_t = reader.f32()?;
+      let vis_max_t = reader.f32()?;
+      let (mode, a, b) = (reader.u32()?, reader.f32()?, reader.f32()?);
+      let color_range = match mode {
+         0 => ColorRange::Fixed,
+         1 => ColorRange::Auto,
+         2 => ColorRange::Hysteresis(a),
+         3 => ColorRange::Percentile(a, b),
+         other => anyhow::bail!("unknown color range {} in checkpoint", other)
+      };
+      let mut boundary_conditions = [BoundaryCondition::Insulated; 4];
+      for condition in boundary_conditions.iter_mut() {
+         let (kind, value) = (reader.u32()?, reader.f32()?);
+         *condition = match kind {
+            0 => BoundaryCondition::Insulated,
+            1 => BoundaryCondition::Fixed(value),
+            other => anyhow::bail!("unknown edge condition {} in checkpoint", other)
+         };
+      }
+      let sim_time = reader.f64()?;
+      let accepted_steps = reader.u64()?;
+      let rejected_steps = reader.u64()?;
+
+      let cells = width as usize * height as usize;
+      if reader.bytes.len() - reader.at != cells * 4 {
+         anyhow::bail!("a {}x{} field needs {} bytes, the checkpoint has {} left", width, height, cells * 4, reader.bytes.len() - reader.at)
+      }
+      let field = (0..cells).map(|_| reader.f32()).collect::<anyhow::Result<Vec<f32>>>()?;
+
+      Ok(Self {
+         width, height, iteration_quantity, max_steps_per_submission, kappa, delta_t, integrator,
+         vis_min_t, vis_max_t, color_range, boundary_conditions, sim_time, accepted_steps, rejected_steps,
+         field: RectGrid::newfromdata(width as usize, height as usize, field),
+      })
+   }
+}
+
+// reads little endian numbers off the front of a checkpoint
+struct Reader<'a> {
+   bytes: &'a [u8],
+   at: usize,
+}
+
+impl Reader<'_> {
+   fn take<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+      let Some(word) = self.bytes.get(self.at..self.at + N) else {anyhow::bail!("the checkpoint ends early")};
+      self.at += N;
+      Ok(word.try_into()?)
+   }
+   fn u32(&mut self) -> anyhow::Result<u32> {Ok(u32::from_le_bytes(self.take()?))}
+   fn u64(&mut self) -> anyhow::Result<u64> {Ok(u64::from_le_bytes(self.take()?))}
+   fn f32(&mut self) -> anyhow::Result<f32> {Ok(f32::from_le_bytes(self.take()?))}
+   fn f64(&mut self) -> anyhow::Result<f64> {Ok(f64::from_le_bytes(self.take()?))}
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn checkpoint() -> Checkpoint {
+      Checkpoint {
+         width: 4,
+         height: 3,
+         iteration_quantity: 100,
+         max_steps_per_submission: 2000,
+         kappa: 0.5,
+         delta_t: 1e-4,
+         integrator: Integrator::Rkl2,
+         vis_min_t: -1.,
+         vis_max_t: 400.,
+         color_range: ColorRange::Percentile(0.05, 0.95),
+         boundary_conditions: [
+            BoundaryCondition::Insulated, BoundaryCondition::Fixed(50.),
+            BoundaryCondition::Fixed(-3.5), BoundaryCondition::Insulated,
+         ],
+         sim_time: 0.125,
+         accepted_steps: 1250,
+         rejected_steps: 7,
+         field: RectGrid::newfromdata(4, 3, (0..12).map(|n| n as f32 * 1.5 - 2.).collect()),
+      }
+   }
+
+   #[test]
+   fn round_trip() {
+      let saved = checkpoint();
+      let loaded = Checkpoint::from_bytes(&saved.to_bytes()).unwrap();
+      assert_eq!((loaded.width, loaded.height), (4, 3));
+      assert_eq!((loaded.iteration_quantity, loaded.max_steps_per_submission), (100, 2000));
+      assert_eq!((loaded.kappa, loaded.delta_t), (0.5, 1e-4));
+      assert_eq!(loaded.integrator, Integrator::Rkl2);
+      assert_eq!((loaded.vis_min_t, loaded.vis_max_t), (-1., 400.));
+      assert_eq!(loaded.color_range, ColorRange::Percentile(0.05, 0.95));
+      assert_eq!(loaded.boundary_conditions, saved.boundary_conditions);
+      assert_eq!((loaded.sim_time, loaded.accepted_steps, loaded.rejected_steps), (0.125, 1250, 7));
+      assert_eq!(loaded.field.getarray(), saved.field.getarray());
+   }
+
+   #[test]
+   fn truncated() {
+      let bytes = checkpoint().to_bytes();
+      for length in [0, 7, 8, 20, 100, bytes.len() - 1] {
+         assert!(Checkpoint::from_bytes(&bytes[..length]).is_err(), "{} bytes read", length);
+      }
+   }
+
+   #[test]
+   fn trailing_bytes() {
+      let mut bytes = checkpoint().to_bytes();
+      bytes.extend_from_slice(&[0; 4]);
+      assert!(Checkpoint::from_bytes(&bytes).is_err());
+   }
+
+   #[test]
+   fn bad_magic_and_version() {
+      let mut bytes = checkpoint().to_bytes();
+      bytes[0] = b'X';
+      assert_eq!(Checkpoint::from_bytes(&bytes).err().unwrap().to_string(), "this is not a checkpoint");
+
+      let mut bytes = checkpoint().to_bytes();
+      bytes[8..12].copy_from_slice(&(VERSION + 1).to_le_bytes());
+      assert!(Checkpoint::from_bytes(&bytes).is_err());
+   }
+}
@@ -0,0 +1,19 @@
+struct AccumulateParams {
+   width: u32,
+   height: u32,
+   threshold: f32,
+   delta_t: f32,
+}
+
+// simulated time and its kahan compensation, so many small steps add up properly
+@group(0) @binding(0) var<storage, read_write> clock: array<f32, 2>;
+@group(0) @binding(1) var<uniform> params: AccumulateParams;
+
+@compute
+@workgroup_size(1,1,1)
+fn main() {
+   let y = params.delta_t - clock[1];
+   let t = clock[0] + y;
+   clock[1] = (t - clock[0]) - y;
+   clock[0] = t;
+}
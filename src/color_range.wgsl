@@ -0,0 +1,73 @@
+// picks the colormap range from the field stats (and histogram, for percentile clipping).
+//    only ever run as one invocation, it is a handful of comparisons.
+
+struct RangeParams {
+   // 1 auto, 2 hysteresis, 3 percentile
+   mode: u32,
+   cells: u32,
+   hysteresis: f32,
+   low_fraction: f32,
+   high_fraction: f32,
+   _pad0: f32,
+   _pad1: f32,
+   _pad2: f32,
+}
+
+@group(0) @binding(0) var<storage, read> stats: array<f32>;
+@group(0) @binding(1) var<storage, read> bins: array<u32>;
+// [minT, maxT] as last shown, copied into the heat hue uniforms afterwards
+@group(0) @binding(2) var<storage, read_write> range: array<f32, 2>;
+@group(0) @binding(3) var<uniform> params: RangeParams;
+
+@compute
+@workgroup_size(1,1,1)
+fn main() {
+   let low = stats[1];
+   let high = stats[2];
+   var new_low = range[0];
+   var new_high = range[1];
+
+   switch params.mode {
+      case 1u: {
+         new_low = low;
+         new_high = high;
+      }
+      case 2u: {
+         // only move when the field leaves the range or has shrunk well inside it,
+         //    so slow drifts dont make the colors crawl every frame
+         let pad = params.hysteresis * (high - low);
+         if ((low < range[0]) | (high > range[1]) | (range[1] - range[0] > high - low + 4.0 * pad)) {
+            new_low = low - pad;
+            new_high = high + pad;
+         }
+      }
+      case 3u: {
+         let bin_count = arrayLength(&bins);
+         let bin_width = (high - low) / f32(bin_count);
+         let low_count = u32(params.low_fraction * f32(params.cells));
+         let high_count = u32(ceil(params.high_fraction * f32(params.cells)));
+         var seen = 0u;
+         var found_low = false;
+         new_high = high;
+         for (var n = 0u; n < bin_count; n++) {
+            seen += bins[n];
+            if (!found_low & (seen > low_count)) {
+               new_low = low + bin_width * f32(n);
+               found_low = true;
+            }
+            if (seen >= high_count) {
+               new_high = low + bin_width * f32(n + 1u);
+               break;
+            }
+         }
+      }
+      default: {}
+   }
+
+   // heatcolor divides by maxT - minT
+   if (new_high <= new_low) {
+      new_high = new_low + max(abs(new_low) * 1e-6, 1e-6);
+   }
+   range[0] = new_low;
+   range[1] = new_high;
+}
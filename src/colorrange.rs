@@ -0,0 +1,164 @@
+use bytemuck::cast_slice;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::reduction::FieldReducer;
+use crate::webgpuheat::{HeatComputer, helper_basic_compute_shader, helper_compute_bind_group};
+
+const HISTOGRAM_BINS: u64 = 256;
+// what the range starts as, inverted so the first hysteresis update always moves it
+const UNSET_RANGE: [f32; 2] = [3.4e38, -3.4e38];
+
+/// How vis_minT and vis_maxT are chosen each time the field is colored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorRange {
+   /// whatever update_values or `set max_T` last wrote
+   Fixed,
+   /// the field's min and max
+   Auto,
+   /// min and max padded by this fraction of the spread, only moved once the field
+   ///    leaves the range or shrinks well inside it
+   Hysteresis(f32),
+   /// from the first to the second fraction of cells, ignoring hot or cold outliers
+   Percentile(f32, f32),
+}
+
+impl ColorRange {
+   /// parses the mode name, with a and b as its parameters where it has them
+   pub fn from_parts(mode: &str, a: Option<f32>, b: Option<f32>) -> Result<Self, String> {
+      match mode {
+         "fixed" => Ok(ColorRange::Fixed),
+         "auto" => Ok(ColorRange::Auto),
+         "hysteresis" => Ok(ColorRange::Hysteresis(a.unwrap_or(0.1))),
+         "percentile" => {
+            let low = a.unwrap_or(0.01);
+            let high = b.unwrap_or(1. - low);
+            if !(0. ..=1.).contains(&low) || !(0. ..=1.).contains(&high) || low >= high {
+               return Err(format!("percentile needs 0 <= low < high <= 1, got {} and {}", low, high))
+            }
+            Ok(ColorRange::Percentile(low, high))
+         }
+         other => Err(format!("unknown color range {}, expected fixed, auto, hysteresis or percentile", other))
+      }
+   }
+
+   fn params(&self, cells: u32) -> Vec<u8> {
+      let (mode, hysteresis, low, high) = match *self {
+         ColorRange::Fixed => (0, 0., 0., 1.),
+         ColorRange::Auto => (1, 0., 0., 1.),
+         ColorRange::Hysteresis(h) => (2, h, 0., 1.),
+         ColorRange::Percentile(low, high) => (3, 0., low, high),
+      };
+      let mut bytes: Vec<u8> = Vec::new();
+      bytes.extend_from_slice(cast_slice(&[mode as u32, cells]));
+      bytes.extend_from_slice(cast_slice(&[hysteresis, low, high, 0., 0., 0.]));
+      bytes
+   }
+}
+
+/// Keeps the colormap range following a field without it ever leaving the gpu. Runs a
+///    stats reduction of the field, a histogram of values when clipping percentiles, and a
+///    single invocation picking the range, then copies that into the heat hue uniforms.
+pub struct AutoRanger {
+   pub mode: ColorRange,
+   pub cells: u32,
+   pub reducer: FieldReducer,
+   pub histogram_pipeline: wgpu::ComputePipeline,
+   pub range_pipeline: wgpu::ComputePipeline,
+   pub cells_buffer: wgpu::Buffer,
+   pub histogram_buffer: wgpu::Buffer,
+   pub range_buffer: wgpu::Buffer,
+   pub param_buffer: wgpu::Buffer,
+   pub histogram_bind_group: wgpu::BindGroup,
+   pub range_bind_group: wgpu::BindGroup,
+}
+
+impl AutoRanger {
+   /// follows `field`, which is one of the heat computer's width x height fields
+   pub fn new(heateq: &HeatComputer, field: &wgpu::Buffer, device: &wgpu::Device, mode: ColorRange) -> Self {
+      let cells = heateq.width * heateq.height;
+      let reducer = FieldReducer::new(device, heateq.width, heateq.height, field);
+
+      let histogram_shader = device.create_shader_module(wgpu::include_wgsl!("value_histogram.wgsl"));
+      let range_shader = device.create_shader_module(wgpu::include_wgsl!("color_range.wgsl"));
+      let histogram_pipeline = helper_basic_compute_shader(device, Some("Value Histogram Pipeline"), &histogram_shader);
+      let range_pipeline = helper_basic_compute_shader(device, Some("Color Range Pipeline"), &range_shader);
+
+      let cells_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("color range cells"),
+         contents: cast_slice(&[cells]),
+         usage: wgpu::BufferUsages::UNIFORM,
+      });
+      let histogram_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("value histogram"),
+         size: HISTOGRAM_BINS * 4,
+         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+         mapped_at_creation: false,
+      });
+      let range_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("color range"),
+         contents: cast_slice(&UNSET_RANGE),
+         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+      });
+      let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("color range params"),
+         contents: &mode.params(cells),
+         usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      });
+
+      let histogram_bind_group = helper_compute_bind_group(
+         device, None, &histogram_pipeline,
+         &[field, &reducer.result_buffer, &histogram_buffer, &cells_buffer]
+      );
+      let range_bind_group = helper_compute_bind_group(
+         device, None, &range_pipeline,
+         &[&reducer.result_buffer, &histogram_buffer, &range_buffer, &param_buffer]
+      );
+
+      Self {
+         mode,
+         cells,
+         reducer,
+         histogram_pipeline,
+         range_pipeline,
+         cells_buffer,
+         histogram_buffer,
+         range_buffer,
+         param_buffer,
+         histogram_bind_group,
+         range_bind_group,
+      }
+   }
+
+   /// switches mode, forgetting the range the last mode picked
+   pub fn set_mode(&mut self, queue: &wgpu::Queue, mode: ColorRange) {
+      self.mode = mode;
+      queue.write_buffer(&self.param_buffer, 0, &mode.params(self.cells));
+      queue.write_buffer(&self.range_buffer, 0, cast_slice(&UNSET_RANGE));
+   }
+
+   /// picks the range for the field as it is now and writes it into the heat hue uniforms.
+   ///    Has to go in before the color pass.
+   pub fn record(&self, heateq: &HeatComputer, encoder: &mut wgpu::CommandEncoder) {
+      let percentile = matches!(self.mode, ColorRange::Percentile(..));
+      if percentile {
+         encoder.clear_buffer(&self.histogram_buffer, 0, None);
+      }
+      {
+         let mut gputodo = encoder.begin_compute_pass(&Default::default());
+         self.reducer.record(&mut gputodo);
+
+         if percentile {
+            gputodo.set_pipeline(&self.histogram_pipeline);
+            gputodo.set_bind_group(0, &self.histogram_bind_group, &[]);
+            let (row_workgroups, rows) = heateq.workgroup_quantity;
+            gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+         }
+
+         gputodo.set_pipeline(&self.range_pipeline);
+         gputodo.set_bind_group(0, &self.range_bind_group, &[]);
+         gputodo.dispatch_workgroups(1, 1, 1);
+      }
+      encoder.copy_buffer_to_buffer(&self.range_buffer, 0, &heateq.vis_minT_buffer, 0, 4);
+      encoder.copy_buffer_to_buffer(&self.range_buffer, 4, &heateq.vis_maxT_buffer, 0, 4);
+   }
+}
@@ -0,0 +1,295 @@
+use crate::analytic::{AnalyticSolution, ErrorNorms, error_norms, solver_position};
+use crate::boundary::{BoundaryCondition, Edge};
+use crate::rectgrid::RectGrid;
+use crate::webgpuheat::{HeatComputer, Integrator};
+
+/// one run of a study, a square grid `resolution` cells across stepped at `delta_t`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConvergenceLevel {
+   pub resolution: u32,
+   pub delta_t: f32,
+}
+
+/// Runs one scenario at several resolutions up to the same time and compares them. Every
+///    level is resampled onto the cells of the coarsest, using the solver's own geometry
+///    (see analytic::solver_position), so the levels are compared at the same points and
+///    the differences between them shrink at the order of the scheme. kappa is the
+///    diffusivity with the walls a unit apart, each level's solver gets it scaled down to
+///    its own (width-2)/width, or the levels would each solve a slightly different problem.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvergenceStudy {
+   /// coarsest first, each finer than the last
+   pub levels: Vec<ConvergenceLevel>,
+   pub t_end: f64,
+   pub kappa: f32,
+   pub integrator: Integrator,
+   pub boundary_conditions: [BoundaryCondition; 4],
+}
+
+/// what one level came to. The differences are against the level before, so the first
+///    level has none, and an order needs two differences, so the first two have none.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelResult {
+   pub resolution: u32,
+   /// delta_t shrunk just enough to land on t_end
+   pub delta_t: f32,
+   pub steps: u64,
+   pub difference: Option<ErrorNorms>,
+   /// from how much the L2 difference shrank
+   pub observed_order: Option<f64>,
+   /// against the exact solution, when there is one
+   pub error: Option<ErrorNorms>,
+   pub error_order: Option<f64>,
+}
+
+pub struct ConvergenceReport {
+   pub levels: Vec<LevelResult>,
+   /// the order the extrapolation assumed, the last observed one or 2 if none was observed
+   pub extrapolation_order: f64,
+   /// the finest level against the extrapolation, an estimate of its error
+   pub estimated_error: Option<ErrorNorms>,
+   /// the extrapolation against the exact solution, when there is one
+   pub extrapolated_error: Option<ErrorNorms>,
+}
+
+impl ConvergenceStudy {
+   /// levels with delta_t proportional to the cell size squared, `diffusion_number` being
+   ///    kappa delta_t / dx^2, so the time error falls with the space error. Keep it under
+   ///    0.25 for rk2.
+   pub fn diffusive(
+      resolutions: &[u32],
+      t_end: f64,
+      kappa: f32,
+      diffusion_number: f32,
+      integrator: Integrator,
+      boundary_conditions: [BoundaryCondition; 4],
+   ) -> Self {
+      let levels = resolutions.iter().map(|&resolution| ConvergenceLevel {
+         resolution,
+         delta_t: diffusion_number / (kappa * (resolution * resolution) as f32),
+      }).collect();
+      Self { levels, t_end, kappa, integrator, boundary_conditions }
+   }
+
+   /// Runs every level from `initial`, which takes positions from 0 to 1 between the walls,
+   ///    and compares them. With `exact` each level is also checked against it, which only
+   ///    makes sense when the edges are insulated like the analytic solutions assume.
+   pub async fn run(
+      &self,
+      device: &wgpu::Device,
+      queue: &wgpu::Queue,
+      initial: impl Fn(f64, f64) -> f64,
+      exact: Option<AnalyticSolution>,
+   ) -> anyhow::Result<ConvergenceReport> {
+      if self.levels.is_empty() {anyhow::bail!("a convergence study needs at least one level")}
+      if self.levels.windows(2).any(|pair| pair[1].resolution <= pair[0].resolution) {
+         anyhow::bail!("the levels should get finer one after the other")
+      }
+      if self.levels[0].resolution < 3 {anyhow::bail!("a level needs at least one cell between the walls")}
+
+      let common = self.levels[0].resolution as usize;
+      let exact_on_common = exact.map(|solution| solution.on_grid(common, common, self.t_end, self.solver_kappa(common)));
+
+      let mut results: Vec<LevelResult> = Vec::new();
+      let mut resampled: Vec<RectGrid> = Vec::new();
+      for level in self.levels.iter() {
+         let (field, steps, delta_t) = self.run_level(device, queue, level, &initial).await?;
+         let on_common = resample_onto(&field, common, common);
+
+         let difference = resampled.last().map(|previous| error_norms(&on_common, previous));
+         let solver_kappa = self.solver_kappa(level.resolution as usize);
+         let error = exact.map(|solution| solution.error(&field, self.t_end, solver_kappa));
+         let ratio = |n: usize| level.resolution as f64 / self.levels[n].resolution as f64;
+         let n = results.len();
+         let observed_order = match (n >= 2, difference) {
+            (true, Some(difference)) => results[n - 1].difference
+               .map(|previous| order(previous.l2, difference.l2, ratio(n - 1))),
+            _ => None
+         };
+         let error_order = match (n >= 1, error) {
+            (true, Some(error)) => results[n - 1].error.map(|previous| order(previous.l2, error.l2, ratio(n - 1))),
+            _ => None
+         };
+
+         results.push(LevelResult {
+            resolution: level.resolution, delta_t, steps, difference, observed_order, error, error_order
+         });
+         resampled.push(on_common);
+      }
+
+      let extrapolation_order = results.iter().rev().find_map(|result| result.observed_order)
+         .filter(|p| p.is_finite() && *p > 0.)
+         .unwrap_or(2.);
+      let extrapolated = match resampled.as_slice() {
+         [.., coarse, fine] => {
+            let ratio = results[results.len() - 1].resolution as f64 / results[results.len() - 2].resolution as f64;
+            Some(richardson(coarse, fine, ratio, extrapolation_order))
+         }
+         _ => None
+      };
+      let estimated_error = extrapolated.as_ref().map(|extrapolated| error_norms(&resampled[resampled.len() - 1], extrapolated));
+      let extrapolated_error = match (&extrapolated, &exact_on_common) {
+         (Some(extrapolated), Some(exact)) => Some(error_norms(extrapolated, exact)),
+         _ => None
+      };
+
+      Ok(ConvergenceReport { levels: results, extrapolation_order, estimated_error, extrapolated_error })
+   }
+
+   // the kappa that makes a solver `resolution` wide see self.kappa between its walls
+   fn solver_kappa(&self, resolution: usize) -> f64 {
+      let length = (resolution - 2) as f64 / resolution as f64;
+      self.kappa as f64 * length * length
+   }
+
+   // one level from scratch on its own computer, run in bounded chunks up to t_end
+   async fn run_level(
+      &self,
+      device: &wgpu::Device,
+      queue: &wgpu::Queue,
+      level: &ConvergenceLevel,
+      initial: &impl Fn(f64, f64) -> f64,
+   ) -> anyhow::Result<(RectGrid, u64, f32)> {
+      let n = level.resolution as usize;
+      let data: Vec<f32> = (0..n * n).map(|k| {
+         let (x, y) = solver_position(k % n, k / n, n, n);
+         initial(x, y) as f32
+      }).collect();
+
+      // as few steps as keep to delta_t, then shrunk to land exactly on t_end
+      let steps = (self.t_end / level.delta_t as f64).ceil().max(1.) as u64;
+      let delta_t = (self.t_end / steps as f64) as f32;
+
+      let mut heateq = HeatComputer::new(&data, level.resolution, level.resolution, device);
+      heateq.set_integrator(self.integrator)?;
+      heateq.set_kappa(queue, self.solver_kappa(n) as f32);
+      heateq.set_delta_t(queue, delta_t);
+      for (edge, condition) in Edge::ALL.iter().zip(self.boundary_conditions) {
+         heateq.set_boundary_condition(queue, *edge, condition);
+      }
+
+      heateq.run_chunked(device, queue, steps, |_| true)?;
+
+      Ok((heateq.read_field(device, queue).await?, steps, delta_t))
+   }
+}
+
+/// the grid sampled at the cells of a width x height solver grid, both laid out like the
+///    solver's so the walls line up. Cubic, since bilinear's own error would swamp the
+///    differences we are after.
+pub fn resample_onto(grid: &RectGrid, width: usize, height: usize) -> RectGrid {
+   let (from_width, from_height) = (grid.width(), grid.height());
+   let data = (0..width * height).map(|k| {
+      let (x, y) = solver_position(k % width, k / width, width, height);
+      // back from between the walls to RectGrid's i/(width-1)
+      let u = (x * (from_width - 2) as f64 + 0.5) / (from_width - 1) as f64;
+      let v = (y * (from_height - 2) as f64 + 0.5) / (from_height - 1) as f64;
+      grid.samplebicubic(u as f32, v as f32)
+   }).collect();
+   RectGrid::newfromdata(width, height, data)
+}
+
+/// fine + (fine - coarse) / (ratio^order - 1), which cancels the leading error term
+pub fn richardson(coarse: &RectGrid, fine: &RectGrid, ratio: f64, order: f64) -> RectGrid {
+   let factor = 1. / (ratio.powf(order) - 1.);
+   fine.newbytemplate(fine.getarray().iter().zip(coarse.getarray().iter())
+      .map(|(&fine, &coarse)| (fine as f64 + (fine - coarse) as f64 * factor) as f32)
+      .collect())
+}
+
+// how fast an error shrank going `ratio` times finer
+fn order(coarse_error: f64, fine_error: f64, ratio: f64) -> f64 {
+   (coarse_error / fine_error).ln() / ratio.ln()
+}
+
+impl ConvergenceReport {
+   pub fn as_csv(&self) -> anyhow::Result<String> {
+      let mut thewriter = csv::WriterBuilder::new()
+         .delimiter(b',')
+         .from_writer(vec![]);
+
+      let blank_or = |value: Option<f64>| value.map_or(String::new(), |value| format!("{}", value));
+      thewriter.write_record([
+         "resolution", "delta_t", "steps", "l2_difference", "linf_difference", "observed_order",
+         "l2_error", "linf_error", "error_order"
+      ])?;
+      for level in self.levels.iter() {
+         thewriter.write_record([
+            format!("{}", level.resolution), format!("{}", level.delta_t), format!("{}", level.steps),
+            blank_or(level.difference.map(|d| d.l2)), blank_or(level.difference.map(|d| d.linf)),
+            blank_or(level.observed_order),
+            blank_or(level.error.map(|e| e.l2)), blank_or(level.error.map(|e| e.linf)),
+            blank_or(level.error_order),
+         ])?;
+      }
+
+      Ok(String::from_utf8(thewriter.into_inner()?)?)
+   }
+
+   /// the csv laid out for reading, with the extrapolation underneath
+   pub fn as_table(&self) -> String {
+      let cell = |value: Option<f64>| value.map_or("-".to_string(), |value| format!("{:.4e}", value));
+      let order = |value: Option<f64>| value.map_or("-".to_string(), |value| format!("{:.3}", value));
+      let mut table = format!(
+         "{:>6} {:>11} {:>8} {:>11} {:>11} {:>7} {:>11} {:>11} {:>7}\n",
+         "n", "delta_t", "steps", "L2 diff", "Linf diff", "order", "L2 error", "Linf error", "order"
+      );
+      for level in self.levels.iter() {
+         table.push_str(&format!(
+            "{:>6} {:>11.4e} {:>8} {:>11} {:>11} {:>7} {:>11} {:>11} {:>7}\n",
+            level.resolution, level.delta_t, level.steps,
+            cell(level.difference.map(|d| d.l2)), cell(level.difference.map(|d| d.linf)), order(level.observed_order),
+            cell(level.error.map(|e| e.l2)), cell(level.error.map(|e| e.linf)), order(level.error_order),
+         ));
+      }
+      if let Some(estimated) = self.estimated_error {
+         table.push_str(&format!(
+            "richardson at order {:.3}: finest level is about L2 {:.4e}, Linf {:.4e} off\n",
+            self.extrapolation_order, estimated.l2, estimated.linf
+         ));
+      }
+      if let Some(error) = self.extrapolated_error {
+         table.push_str(&format!("extrapolation against the exact solution: L2 {:.4e}, Linf {:.4e}\n", error.l2, error.linf));
+      }
+      table
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn richardson_cancels_second_order_error() {
+      // values carrying an error of 3 h^2 at h = 0.1 and h = 0.05
+      let exact = [1., -2., 0.5, 4.];
+      let with_error = |h: f32| RectGrid::newfromdata(2, 2, exact.iter().map(|value| value + 3. * h * h).collect());
+      let (coarse, fine) = (with_error(0.1), with_error(0.05));
+      let extrapolated = richardson(&coarse, &fine, 2., 2.);
+      for (value, exact) in extrapolated.getarray().iter().zip(exact) {
+         assert!((value - exact).abs() < 1e-5, "{} is not {}", value, exact);
+      }
+      assert!((order(3. * 0.01, 3. * 0.0025, 2.) - 2.).abs() < 1e-12);
+      assert!((order(0.3, 0.1, 3.) - 1.).abs() < 1e-12);
+   }
+
+   #[test]
+   fn resample_keeps_positions() {
+      // a field linear in the solver's x, which the cubic has to carry over exactly
+      let linear = |width: usize, height: usize| RectGrid::newfromdata(width, height,
+         (0..width * height).map(|k| solver_position(k % width, k / width, width, height).0 as f32).collect());
+      let resampled = resample_onto(&linear(34, 34), 18, 10);
+      // the cells outside the walls sit past the finer grid's, where it is clamped
+      for j in 1..9 {
+         for i in 1..17 {
+            let (x, _) = solver_position(i, j, 18, 10);
+            assert!((resampled.getelement(i, j) - x as f32).abs() < 1e-5, "({}, {}) is {}, not {}", i, j, resampled.getelement(i, j), x);
+         }
+      }
+      // and onto its own size nothing moves
+      let same = resample_onto(&linear(18, 10), 18, 10);
+      for (value, original) in same.getarray().iter().zip(linear(18, 10).getarray()) {
+         assert!((value - original).abs() < 1e-6);
+      }
+   }
+}
@@ -29,10 +29,21 @@ fn gen_print<T>(s: T) where T: Display {
    println!("{}",s)
 }
 
+/// runs one of the async gpu readbacks to completion from inside the event loop
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+   tokio::runtime::Builder::new_current_thread().build()
+      .expect("tokio runtime creation failed")
+      .block_on(future)
+}
+
 pub struct State {
    wgpuworkhorse: wgpuworkhorse::WgpuState,
    cli_state: std::string::String,
    compute_on_render: bool,
+   // the exact solution the field was last started from, if it was
+   analytic: Option<crate::analytic::AnalyticSolution>,
+   // the listener printing gpu events, while there is one
+   event_printer: Option<u64>,
    //end_cli_sender: oneshot::Sender<()>,
    //cli_receiver: mpsc::Receiver<Vec<char>>,
    window: Arc<Window>,
@@ -51,6 +62,8 @@ impl State {
          wgpuworkhorse: pony,
          cli_state: std::string::String::new(),
          compute_on_render: false,
+         analytic: None,
+         event_printer: None,
          window: valid_pre_surface
       })
    }
@@ -102,6 +115,20 @@ impl State {
          self.wgpuworkhorse.heateq.send_compute_job(&mut pending_queue,&self.wgpuworkhorse.device);
          self.wgpuworkhorse.heateq.send_color_job(&mut pending_queue,&self.wgpuworkhorse.device);
          self.wgpuworkhorse.heateq.color_to_texture(&mut pending_queue,&self.wgpuworkhorse.device,&self.wgpuworkhorse.texture_buffer);
+      } else if let Some(run) = self.wgpuworkhorse.heateq.send_run_chunk(&mut pending_queue,&self.wgpuworkhorse.device) {
+         self.wgpuworkhorse.heateq.send_color_job(&mut pending_queue,&self.wgpuworkhorse.device);
+         self.wgpuworkhorse.heateq.color_to_texture(&mut pending_queue,&self.wgpuworkhorse.device,&self.wgpuworkhorse.texture_buffer);
+         match (run.cancelled, run.is_finished()) {
+            (true, _) => println!("run cancelled after {} of {} steps, t = {}", run.done_steps, run.total_steps, self.wgpuworkhorse.heateq.sim_time),
+            (false, true) => println!("run finished, {} steps, t = {}", run.done_steps, self.wgpuworkhorse.heateq.sim_time),
+            (false, false) => {}
+         }
+      } else if let Some(status) = self.wgpuworkhorse.heateq.send_controlled_chunk(&mut pending_queue,&self.wgpuworkhorse.device) {
+         self.wgpuworkhorse.heateq.send_color_job(&mut pending_queue,&self.wgpuworkhorse.device);
+         self.wgpuworkhorse.heateq.color_to_texture(&mut pending_queue,&self.wgpuworkhorse.device,&self.wgpuworkhorse.texture_buffer);
+         if let crate::runcontrol::RunStatus::Finished(report) = status {
+            println!("{}", report);
+         }
       }
 
       let mut encoder = self.wgpuworkhorse.device.create_command_encoder(&Default::default());
@@ -123,7 +150,323 @@ impl State {
 
       let first_word = match instruction.next() {
          Some("start") => {self.compute_on_render = true; return}
-         Some("stop") => {self.compute_on_render = false; return}
+         Some("stop") => {
+            self.compute_on_render = false;
+            self.wgpuworkhorse.heateq.cancel_chunked_run();
+            let heateq = &self.wgpuworkhorse.heateq;
+            println!("stopped at t = {} after {} steps", heateq.sim_time, heateq.accepted_steps);
+            return
+         }
+         Some("clock") => {
+            let heateq = &mut self.wgpuworkhorse.heateq;
+            match instruction.next() {
+               None => println!("t = {}, {} steps ({} rejected)", heateq.sim_time, heateq.accepted_steps, heateq.rejected_steps),
+               Some("reset") => heateq.reset_clock(&self.wgpuworkhorse.queue),
+               Some(_) => println!("usage: clock | clock reset")
+            }
+            return
+         }
+         Some("run") => {
+            match instruction.next().map(|x| x.parse::<u64>()) {
+               Some(Ok(total_steps)) => {
+                  self.compute_on_render = false;
+                  self.wgpuworkhorse.heateq.begin_chunked_run(total_steps);
+               }
+               _ => println!("usage: run <total steps>")
+            }
+            return
+         }
+         Some("events") => {
+            let events = &self.wgpuworkhorse.heateq.events;
+            if let Some(id) = self.event_printer.take() {events.unsubscribe(id)}
+            match instruction.next() {
+               Some("on") => {
+                  // only these kinds if any are named, every frame is a few events otherwise
+                  let kinds: Vec<String> = instruction.map(|kind| kind.to_string()).collect();
+                  self.event_printer = Some(events.subscribe(Box::new(move |event| {
+                     if kinds.is_empty() || kinds.iter().any(|kind| kind == event.name()) {println!("{}", event)}
+                  })));
+               }
+               Some("off") => {}
+               _ => println!("usage: events on [submitted|compute_done|color_done|copy_done|readback_ready|error..] | events off")
+            }
+            return
+         }
+         Some("until") => {
+            let spec = instruction.collect::<Vec<&str>>().join(" ");
+            match spec.parse::<crate::runcontrol::RunUntil>() {
+               Ok(until) => {
+                  self.compute_on_render = false;
+                  let workhorse = &mut self.wgpuworkhorse;
+                  if let Err(e) = workhorse.heateq.begin_controlled_run(&workhorse.device, until) {println!("{}", e)}
+               }
+               Err(e) => println!("{}\nusage: until time=<t> steps=<n> steady=<max |dT/dt|> probe=<name>><T> probe=<name><<T> [every=<steps>]", e)
+            }
+            return
+         }
+         Some("range") => {
+            let mode = instruction.next().unwrap_or("");
+            let a = instruction.next().and_then(|x| x.parse::<f32>().ok());
+            let b = instruction.next().and_then(|x| x.parse::<f32>().ok());
+            match crate::colorrange::ColorRange::from_parts(mode, a, b) {
+               Ok(color_range) => {
+                  let workhorse = &mut self.wgpuworkhorse;
+                  let pending_queue = workhorse.pending_queue.replace(vec![]);
+                  workhorse.heateq.submit(&workhorse.queue, pending_queue);
+                  if let Err(e) = workhorse.heateq.set_color_range(&workhorse.device, &workhorse.queue, color_range) {
+                     println!("{}", e);
+                  }
+               }
+               Err(e) => println!("{}\nusage: range fixed|auto|hysteresis [pad]|percentile [low] [high]", e)
+            }
+            return
+         }
+         Some("view") => {
+            match instruction.next().map(|x| x.parse::<crate::flux::ViewField>()) {
+               Some(Ok(view)) => {
+                  let workhorse = &mut self.wgpuworkhorse;
+                  if let Err(e) = workhorse.heateq.set_view_field(&workhorse.device, view) {
+                     println!("{}", e);
+                     return
+                  }
+                  let mut pending_queue = workhorse.pending_queue.replace(vec![]);
+                  workhorse.heateq.send_color_job(&mut pending_queue, &workhorse.device);
+                  workhorse.heateq.color_to_texture(&mut pending_queue, &workhorse.device, &workhorse.texture_buffer);
+                  _ = workhorse.pending_queue.replace(pending_queue);
+               }
+               Some(Err(e)) => println!("{}", e),
+               None => println!("usage: view temperature|flux_x|flux_y|flux_mag|peak_T|time_above|first_arrival")
+            }
+            return
+         }
+         Some("export") => {
+            let view = instruction.next().unwrap_or("temperature");
+            let path = instruction.next().unwrap_or("field.csv").to_string();
+            match view.parse::<crate::flux::ViewField>() {
+               Ok(view) => {
+                  let workhorse = &mut self.wgpuworkhorse;
+                  let pending_queue = workhorse.pending_queue.replace(vec![]);
+                  workhorse.heateq.submit(&workhorse.queue, pending_queue);
+                  let grid = block_on(workhorse.heateq.read_view_field(&workhorse.device, &workhorse.queue, view));
+                  match grid.and_then(|grid| grid.writecsv()).map(|csv| std::fs::write(&path, csv)) {
+                     Ok(Ok(())) => println!("wrote {} at t = {}", path, workhorse.heateq.sim_time),
+                     Ok(Err(e)) => println!("could not write {}: {}", path, e),
+                     Err(e) => println!("export failed: {}", e)
+                  }
+               }
+               Err(e) => println!("{}", e)
+            }
+            return
+         }
+         Some("save") => {
+            let path = instruction.next().unwrap_or("run.ckpt");
+            let workhorse = &mut self.wgpuworkhorse;
+            let pending_queue = workhorse.pending_queue.replace(vec![]);
+            workhorse.heateq.submit(&workhorse.queue, pending_queue);
+            match block_on(workhorse.heateq.checkpoint(&workhorse.device, &workhorse.queue))
+               .map(|checkpoint| std::fs::write(path, checkpoint.to_bytes())) {
+               Ok(Ok(())) => println!("wrote {}", path),
+               Ok(Err(e)) => println!("could not write {}: {}", path, e),
+               Err(e) => println!("checkpoint failed: {}", e)
+            }
+            return
+         }
+         Some("load") => {
+            let path = instruction.next().unwrap_or("run.ckpt").to_string();
+            let checkpoint = std::fs::read(&path).map_err(anyhow::Error::from)
+               .and_then(|bytes| crate::checkpoint::Checkpoint::from_bytes(&bytes));
+            // a checkpoint of another size needs everything rebuilt for that size, texture and all
+            let checkpoint = checkpoint.and_then(|checkpoint| {
+               let heateq = &self.wgpuworkhorse.heateq;
+               if (heateq.width, heateq.height) != (checkpoint.width, checkpoint.height) {
+                  let events = heateq.events.clone();
+                  self.wgpuworkhorse = block_on(wgpuworkhorse::WgpuState::new_with(self.window.clone(), checkpoint.width, checkpoint.height))?;
+                  // keeps the event printer listening
+                  self.wgpuworkhorse.heateq.events = events;
+                  let size = self.window.inner_size();
+                  self.resize(size.width, size.height);
+               }
+               Ok(checkpoint)
+            });
+            let workhorse = &mut self.wgpuworkhorse;
+            match checkpoint.and_then(|checkpoint| workhorse.heateq.restore(&workhorse.device, &workhorse.queue, &checkpoint)) {
+               Ok(()) => {
+                  // the field loaded is whatever was saved, not the solution init started from
+                  self.analytic = None;
+                  println!("restored {} at t = {}", path, workhorse.heateq.sim_time)
+               }
+               Err(e) => println!("could not load {}: {}", path, e)
+            }
+            return
+         }
+         Some("numpy") => {
+            let words: Vec<String> = instruction.map(|x| x.to_string()).collect();
+            self.do_numpy_instruction(&words.iter().map(|x| x.as_str()).collect::<Vec<&str>>());
+            return
+         }
+         Some("vtk") => {
+            let words: Vec<String> = instruction.map(|x| x.to_string()).collect();
+            self.do_vtk_instruction(&words.iter().map(|x| x.as_str()).collect::<Vec<&str>>());
+            return
+         }
+         Some("profile") => {
+            let words: Vec<String> = instruction.map(|x| x.to_string()).collect();
+            self.do_profile_instruction(&words.iter().map(|x| x.as_str()).collect::<Vec<&str>>());
+            return
+         }
+         Some("init") => {
+            let name = instruction.next().unwrap_or("");
+            let params: Vec<f64> = instruction.filter_map(|x| x.parse::<f64>().ok()).collect();
+            match crate::analytic::AnalyticSolution::from_parts(name, &params) {
+               Ok(solution) => {
+                  self.compute_on_render = false;
+                  let workhorse = &mut self.wgpuworkhorse;
+                  let heateq = &mut workhorse.heateq;
+                  if let Err(e) = crate::analytic::check_grid_size(heateq.width as usize, heateq.height as usize) {
+                     println!("{}", e);
+                     return
+                  }
+                  let grid = solution.on_grid(heateq.width as usize, heateq.height as usize, 0., heateq.kappa as f64);
+                  if let Err(e) = heateq.write_field(&workhorse.queue, &grid) {
+                     println!("{}", e);
+                     return
+                  }
+                  heateq.reset_clock(&workhorse.queue);
+                  self.analytic = Some(solution);
+               }
+               Err(e) => println!("{}\nusage: init cosine [m n amplitude offset] | init gaussian [sigma amplitude x0 y0] | init erf_x|erf_y [at left right]", e)
+            }
+            return
+         }
+         Some("check") => {
+            let Some(solution) = self.analytic else {
+               println!("the field was not started from an analytic solution, see init");
+               return
+            };
+            let workhorse = &mut self.wgpuworkhorse;
+            if let Err(e) = crate::analytic::check_grid_size(workhorse.heateq.width as usize, workhorse.heateq.height as usize) {
+               println!("{}", e);
+               return
+            }
+            let pending_queue = workhorse.pending_queue.replace(vec![]);
+            workhorse.heateq.submit(&workhorse.queue, pending_queue);
+            let heateq = &workhorse.heateq;
+            match block_on(heateq.read_field(&workhorse.device, &workhorse.queue)) {
+               Ok(grid) => {
+                  let errors = solution.error(&grid, heateq.sim_time, heateq.kappa as f64);
+                  println!("t = {}: L2 error {}, Linf error {}", heateq.sim_time, errors.l2, errors.linf);
+               }
+               Err(e) => println!("readback failed: {}", e)
+            }
+            return
+         }
+         Some("ensemble") => {
+            let words: Vec<String> = instruction.map(|x| x.to_string()).collect();
+            self.do_ensemble_instruction(&words.iter().map(|x| x.as_str()).collect::<Vec<&str>>());
+            return
+         }
+         Some("montecarlo") => {
+            let words: Vec<String> = instruction.map(|x| x.to_string()).collect();
+            self.do_montecarlo_instruction(&words.iter().map(|x| x.as_str()).collect::<Vec<&str>>());
+            return
+         }
+         Some("fit") => {
+            let words: Vec<String> = instruction.map(|x| x.to_string()).collect();
+            self.do_fit_instruction(&words.iter().map(|x| x.as_str()).collect::<Vec<&str>>());
+            return
+         }
+         Some("converge") => {
+            let words: Vec<String> = instruction.map(|x| x.to_string()).collect();
+            self.do_converge_instruction(&words.iter().map(|x| x.as_str()).collect::<Vec<&str>>());
+            return
+         }
+         Some("accum") => {
+            let workhorse = &mut self.wgpuworkhorse;
+            match (instruction.next(), instruction.next().map(|x| x.parse::<f32>())) {
+               (Some("start"), Some(Ok(threshold))) => {
+                  let pending_queue = workhorse.pending_queue.replace(vec![]);
+                  workhorse.heateq.submit(&workhorse.queue, pending_queue);
+                  if let Err(e) = workhorse.heateq.start_accumulators(&workhorse.device, &workhorse.queue, threshold) {
+                     println!("{}", e);
+                  }
+               }
+               (Some("stop"), None) => workhorse.heateq.stop_accumulators(&workhorse.device),
+               _ => println!("usage: accum start <threshold> | accum stop")
+            }
+            return
+         }
+         Some("isotherm") => {
+            let words: Vec<String> = instruction.map(|x| x.to_string()).collect();
+            self.do_isotherm_instruction(&words.iter().map(|x| x.as_str()).collect::<Vec<&str>>());
+            return
+         }
+         Some("probe") => {
+            let words: Vec<String> = instruction.map(|x| x.to_string()).collect();
+            self.do_probe_instruction(&words.iter().map(|x| x.as_str()).collect::<Vec<&str>>());
+            return
+         }
+         Some("bc") => {
+            let edge = instruction.next().map(|x| x.parse::<crate::boundary::Edge>());
+            let kind = instruction.next().unwrap_or("");
+            let value = instruction.next().and_then(|x| x.parse::<f32>().ok());
+            match (edge, crate::boundary::BoundaryCondition::from_parts(kind, value)) {
+               (Some(Ok(edge)), Ok(condition)) => {
+                  let workhorse = &mut self.wgpuworkhorse;
+                  workhorse.heateq.set_boundary_condition(&workhorse.queue, edge, condition);
+               }
+               (Some(Err(e)), _) | (_, Err(e)) => println!("{}\nusage: bc y0|x0|y1|x1 insulated|fixed <T>", e),
+               (None, _) => println!("usage: bc y0|x0|y1|x1 insulated|fixed <T>")
+            }
+            return
+         }
+         Some("edgeflux") => {
+            let workhorse = &mut self.wgpuworkhorse;
+            let pending_queue = workhorse.pending_queue.replace(vec![]);
+            workhorse.heateq.submit(&workhorse.queue, pending_queue);
+            let heateq = &mut workhorse.heateq;
+            match instruction.next() {
+               Some("start") => {
+                  if let Err(e) = block_on(heateq.track_edge_flux(&workhorse.device, &workhorse.queue)) {
+                     println!("edge flux tracking failed: {}", e);
+                  }
+               }
+               Some("report") => match block_on(heateq.edge_flux_report(&workhorse.device, &workhorse.queue)) {
+                  Ok(report) => println!("{:?}", report),
+                  Err(e) => println!("edge flux report failed: {}", e)
+               },
+               Some("stop") => heateq.edge_flux = None,
+               _ => println!("usage: edgeflux start|report|stop")
+            }
+            return
+         }
+         Some("stats") => {
+            let workhorse = &mut self.wgpuworkhorse;
+            let pending_queue = workhorse.pending_queue.replace(vec![]);
+            workhorse.heateq.submit(&workhorse.queue, pending_queue);
+            match block_on(workhorse.heateq.field_stats(&workhorse.device, &workhorse.queue)) {
+               Ok(stats) => println!("{:?}", stats),
+               Err(e) => println!("stats failed: {}", e)
+            }
+            return
+         }
+         Some("adaptive") => {
+            // "until <time>" lands on that time, giving up after plenty of attempts,
+            //    anything else is a count of attempts
+            let run = match instruction.next() {
+               Some("until") => instruction.next().and_then(|x| x.parse::<f64>().ok()).map(|end_time| (100_000, Some(end_time))),
+               attempts => attempts.and_then(|x| x.parse::<u32>().ok()).map(|attempts| (attempts, None))
+            };
+            let atol = instruction.next().and_then(|x| x.parse::<f32>().ok()).unwrap_or(1e-3);
+            let rtol = instruction.next().and_then(|x| x.parse::<f32>().ok()).unwrap_or(1e-3);
+            match run {
+               Some((attempts, end_time)) => {
+                  self.compute_on_render = false;
+                  self.run_adaptive(attempts, end_time, atol, rtol);
+               }
+               None => println!("usage: adaptive <attempts> [atol] [rtol] | adaptive until <time> [atol] [rtol]")
+            }
+            return
+         }
          Some(x) => x,
          None => {println!("received empty command"); return}
       };
@@ -132,15 +475,14 @@ impl State {
          match (instruction.next(), instruction.next()) {
             (Some("max_T"), Some(x)) => {
                   if let Ok(max_T) = x.parse::<f32>() {
-                     self.wgpuworkhorse.queue.write_buffer(
-                        &self.wgpuworkhorse.heateq.vis_maxT_buffer, 0, bytemuck::cast_slice(&[max_T]));
+                     let heateq = &mut self.wgpuworkhorse.heateq;
+                     heateq.set_vis_range(&self.wgpuworkhorse.queue, heateq.vis_min_t, max_T);
                      self.wgpuworkhorse.queue.submit([]);
                   }
                }
             (Some("kappa"), Some(x)) => {
                if let Ok(kappa) = x.parse::<f32>() {
-                  self.wgpuworkhorse.queue.write_buffer(
-                     &self.wgpuworkhorse.heateq.kappa_buffer, 0, bytemuck::cast_slice(&[kappa]));
+                  self.wgpuworkhorse.heateq.set_kappa(&self.wgpuworkhorse.queue, kappa);
                   self.wgpuworkhorse.queue.submit([]);
                }
             }
@@ -149,6 +491,33 @@ impl State {
                   self.wgpuworkhorse.heateq.iteration_quantity = iter_quant;
                }
             }
+            (Some("integrator"), Some(x)) => {
+               match x.parse::<crate::webgpuheat::Integrator>() {
+                  Ok(integrator) => if let Err(e) = self.wgpuworkhorse.heateq.set_integrator(integrator) {
+                     println!("{}", e);
+                  },
+                  Err(e) => println!("{}", e)
+               }
+            }
+            (Some("delta_t"), Some(x)) => {
+               if let Ok(delta_t) = x.parse::<f32>() {
+                  self.wgpuworkhorse.heateq.set_delta_t(&self.wgpuworkhorse.queue, delta_t);
+                  self.wgpuworkhorse.queue.submit([]);
+               }
+            }
+            (Some("conductivity"), Some(x)) => {
+               if let Ok(conductivity) = x.parse::<f32>() {
+                  let workhorse = &mut self.wgpuworkhorse;
+                  if let Err(e) = workhorse.heateq.set_conductivity(&workhorse.device, &workhorse.queue, conductivity) {
+                     println!("{}", e);
+                  }
+               }
+            }
+            (Some("max_chunk"), Some(x)) => {
+               if let Ok(max_chunk) = x.parse::<u32>() {
+                  self.wgpuworkhorse.heateq.max_steps_per_submission = max_chunk.max(1);
+               }
+            }
             _ => {return;}
          }
       }
@@ -157,6 +526,494 @@ impl State {
 
 
 
+impl State {
+   /// numpy save <path.npy|path.npz> [f32|f64] | numpy load <path>
+   fn do_numpy_instruction(&mut self, words: &[&str]) {
+      let usage = "usage: numpy save <path.npy|path.npz> [f32|f64] | numpy load <path.npy|path.npz>";
+      let workhorse = &mut self.wgpuworkhorse;
+      let pending_queue = workhorse.pending_queue.replace(vec![]);
+      workhorse.heateq.submit(&workhorse.queue, pending_queue);
+      match words {
+         ["save", path, rest @ ..] => {
+            let dtype = match rest.first().map(|x| x.parse::<crate::npy::NpyDtype>()) {
+               None => crate::npy::NpyDtype::F32,
+               Some(Ok(dtype)) => dtype,
+               Some(Err(e)) => {println!("{}\n{}", e, usage); return}
+            };
+            let bytes = match path.ends_with(".npz") {
+               true => block_on(workhorse.heateq.fields_as_npz(&workhorse.device, &workhorse.queue, dtype)),
+               false => block_on(workhorse.heateq.field_as_npy(&workhorse.device, &workhorse.queue, dtype)),
+            };
+            match bytes.map(|bytes| std::fs::write(path, bytes)) {
+               Ok(Ok(())) => println!("wrote {}", path),
+               Ok(Err(e)) => println!("could not write {}: {}", path, e),
+               Err(e) => println!("{}", e)
+            }
+         }
+         ["load", path] => {
+            let loaded = std::fs::read(path).map_err(anyhow::Error::from)
+               .and_then(|bytes| block_on(workhorse.heateq.load_numpy(&workhorse.device, &workhorse.queue, &bytes)));
+            match loaded {
+               Ok(()) => {
+                  self.analytic = None;
+                  let mut pending_queue = workhorse.pending_queue.replace(vec![]);
+                  workhorse.heateq.send_color_job(&mut pending_queue, &workhorse.device);
+                  workhorse.heateq.color_to_texture(&mut pending_queue, &workhorse.device, &workhorse.texture_buffer);
+                  _ = workhorse.pending_queue.replace(pending_queue);
+                  println!("loaded {}", path)
+               }
+               Err(e) => println!("could not load {}: {}", path, e)
+            }
+         }
+         _ => println!("{}", usage)
+      }
+   }
+
+   /// vtk save [path.vti] [size=<x length>x<y length>] | vtk series <prefix> <times> [size=..],
+   ///    a series stepping the field on to each time and writing <prefix>_<n>.vti for it, then
+   ///    <prefix>.pvd listing them for ParaView. The domain is the unit square unless sized.
+   fn do_vtk_instruction(&mut self, words: &[&str]) {
+      let usage = "usage: vtk save [path.vti] [size=<x length>x<y length>] | vtk series <prefix> <times> [size=<x length>x<y length>]";
+      let (words, sizes): (Vec<&str>, Vec<&str>) = words.iter().partition(|word| !word.starts_with("size="));
+      let (x_length, y_length) = match sizes.last().map(|size| crate::vtk::parse_lengths(&size["size=".len()..])) {
+         None => (1., 1.),
+         Some(Ok(lengths)) => lengths,
+         Some(Err(e)) => {println!("{}\n{}", e, usage); return}
+      };
+      let workhorse = &mut self.wgpuworkhorse;
+      let pending_queue = workhorse.pending_queue.replace(vec![]);
+      workhorse.heateq.submit(&workhorse.queue, pending_queue);
+
+      match words.as_slice() {
+         ["save", rest @ ..] if rest.len() <= 1 => {
+            let path = rest.first().copied().unwrap_or("field.vti");
+            let bytes = block_on(workhorse.heateq.fields_as_vti(&workhorse.device, &workhorse.queue, x_length, y_length));
+            match bytes.map(|bytes| std::fs::write(path, bytes)) {
+               Ok(Ok(())) => println!("wrote {}", path),
+               Ok(Err(e)) => println!("could not write {}: {}", path, e),
+               Err(e) => println!("vtk failed: {}", e)
+            }
+         }
+         ["series", prefix, times] => {
+            let times = match crate::sweep::parse_values(times) {
+               Ok(times) => times,
+               Err(e) => {println!("{}\n{}", e, usage); return}
+            };
+            let heateq = &mut workhorse.heateq;
+            let mut series = crate::vtk::VtkSeries::new(prefix);
+            for time in times {
+               let steps = ((time - heateq.sim_time) / heateq.delta_t as f64).round().max(0.) as u64;
+               let written = heateq.run_chunked(&workhorse.device, &workhorse.queue, steps, |_| true)
+                  .and_then(|_| block_on(heateq.fields_as_vti(&workhorse.device, &workhorse.queue, x_length, y_length)))
+                  .and_then(|bytes| {
+                     let path = series.add(heateq.sim_time);
+                     std::fs::write(&path, bytes)?;
+                     Ok(path)
+                  });
+               match written {
+                  Ok(path) => println!("wrote {} at t = {}", path, heateq.sim_time),
+                  Err(e) => {println!("series stopped: {}", e); break}
+               }
+            }
+
+            let mut pending_queue = workhorse.pending_queue.replace(vec![]);
+            heateq.send_color_job(&mut pending_queue, &workhorse.device);
+            heateq.color_to_texture(&mut pending_queue, &workhorse.device, &workhorse.texture_buffer);
+            _ = workhorse.pending_queue.replace(pending_queue);
+            match std::fs::write(series.pvd_path(), series.as_pvd()) {
+               Ok(()) => println!("wrote {} with {} frames", series.pvd_path(), series.frames.len()),
+               Err(e) => println!("could not write {}: {}", series.pvd_path(), e)
+            }
+         }
+         _ => println!("{}", usage)
+      }
+   }
+
+   /// profile line <x0> <y0> <x1> <y1> [samples] [path] | profile row <j> [path] | profile col <i> [path]
+   fn do_profile_instruction(&mut self, words: &[&str]) {
+      let usage = "usage: profile line <x0> <y0> <x1> <y1> [samples] [path] | profile row <j> [path] | profile col <i> [path]";
+      let workhorse = &mut self.wgpuworkhorse;
+      let pending_queue = workhorse.pending_queue.replace(vec![]);
+      workhorse.heateq.submit(&workhorse.queue, pending_queue);
+      let grid = match block_on(workhorse.heateq.read_field(&workhorse.device, &workhorse.queue)) {
+         Ok(grid) => grid,
+         Err(e) => {println!("readback failed: {}", e); return}
+      };
+
+      let (profile, path) = match words {
+         ["line", x0, y0, x1, y1, rest @ ..] => {
+            let ends: Vec<f32> = [x0, y0, x1, y1].iter().filter_map(|x| x.parse::<f32>().ok()).collect();
+            if ends.len() != 4 {println!("{}", usage); return}
+            let samples = rest.first().and_then(|x| x.parse::<usize>().ok()).unwrap_or(grid.width().max(grid.height()));
+            (grid.lineprofile(ends[0], ends[1], ends[2], ends[3], samples.max(1)), rest.get(1))
+         }
+         ["row", j, rest @ ..] => match j.parse::<usize>() {
+            Ok(j) if j < grid.height() => (grid.rowprofile(j), rest.first()),
+            _ => {println!("row should be below {}", grid.height()); return}
+         },
+         ["col", i, rest @ ..] => match i.parse::<usize>() {
+            Ok(i) if i < grid.width() => (grid.columnprofile(i), rest.first()),
+            _ => {println!("column should be below {}", grid.width()); return}
+         },
+         _ => {println!("{}", usage); return}
+      };
+
+      let path = path.copied().unwrap_or("profile.csv");
+      match crate::rectgrid::profile_as_csv(&profile).map(|csv| std::fs::write(path, csv)) {
+         Ok(Ok(())) => println!("wrote {} points to {}", profile.len(), path),
+         Ok(Err(e)) => println!("could not write {}: {}", path, e),
+         Err(e) => println!("csv failed: {}", e)
+      }
+   }
+
+   /// isotherm csv <path> <levels..> | isotherm svg <path> <levels..> | isotherm overlay <levels..>|off
+   fn do_isotherm_instruction(&mut self, words: &[&str]) {
+      let usage = "usage: isotherm csv <path> <levels..> | isotherm svg <path> <levels..> | isotherm overlay <levels..>|off";
+      let workhorse = &mut self.wgpuworkhorse;
+
+      let (kind, path, levels) = match words {
+         ["overlay", "off"] => ("overlay", "", vec![]),
+         ["overlay", levels @ ..] => ("overlay", "", levels.to_vec()),
+         [kind @ ("csv" | "svg"), path, levels @ ..] => (*kind, *path, levels.to_vec()),
+         _ => {println!("{}", usage); return}
+      };
+      let levels: Vec<f32> = levels.iter().filter_map(|x| x.parse::<f32>().ok()).collect();
+
+      if kind == "overlay" {
+         if let Err(e) = workhorse.heateq.set_isotherm_overlay(&workhorse.device, levels) {
+            println!("{}", e);
+            return
+         }
+         let mut pending_queue = workhorse.pending_queue.replace(vec![]);
+         workhorse.heateq.send_color_job(&mut pending_queue, &workhorse.device);
+         workhorse.heateq.color_to_texture(&mut pending_queue, &workhorse.device, &workhorse.texture_buffer);
+         _ = workhorse.pending_queue.replace(pending_queue);
+         return
+      }
+      if levels.is_empty() {println!("{}", usage); return}
+
+      let pending_queue = workhorse.pending_queue.replace(vec![]);
+      workhorse.heateq.submit(&workhorse.queue, pending_queue);
+      let heateq = &workhorse.heateq;
+      let isotherms = match block_on(heateq.isotherms(&workhorse.device, &workhorse.queue, &levels)) {
+         Ok(isotherms) => isotherms,
+         Err(e) => {println!("readback failed: {}", e); return}
+      };
+      for isotherm in isotherms.iter() {
+         println!("T = {}: {} lines, length {}", isotherm.level, isotherm.lines.len(), isotherm.length());
+      }
+
+      let written = match kind {
+         "csv" => crate::isotherms::isotherms_as_csv(&isotherms, heateq.sim_time)
+            .map(|csv| std::fs::write(path, csv)),
+         _ => Ok(std::fs::write(path, crate::isotherms::isotherms_as_svg(
+            &isotherms, heateq.width as usize, heateq.height as usize))),
+      };
+      match written {
+         Ok(Ok(())) => println!("wrote {}", path),
+         Ok(Err(e)) => println!("could not write {}: {}", path, e),
+         Err(e) => println!("csv failed: {}", e)
+      }
+   }
+
+   /// converge <t_end> <diffusion number> <resolutions..> [path], rerunning the solution
+   ///    init last started from at each resolution with the current kappa, integrator and edges
+   fn do_converge_instruction(&mut self, words: &[&str]) {
+      let usage = "usage: converge <t_end> <diffusion number> <resolutions..> [path]";
+      let Some(solution) = self.analytic else {
+         println!("a study reruns the solution the field was started from, see init");
+         return
+      };
+
+      let (path, numbers) = match words.split_last() {
+         Some((last, rest)) if last.parse::<f64>().is_err() => (Some(*last), rest),
+         _ => (None, words)
+      };
+      let (t_end, diffusion_number, resolutions) = match numbers {
+         [t_end, diffusion_number, resolutions @ ..] if !resolutions.is_empty() => {
+            let resolutions = resolutions.iter().map(|x| x.parse::<u32>()).collect::<Result<Vec<u32>, _>>();
+            match (t_end.parse::<f64>(), diffusion_number.parse::<f32>(), resolutions) {
+               (Ok(t_end), Ok(diffusion_number), Ok(resolutions)) => (t_end, diffusion_number, resolutions),
+               _ => {println!("{}", usage); return}
+            }
+         }
+         _ => {println!("{}", usage); return}
+      };
+
+      let workhorse = &mut self.wgpuworkhorse;
+      let pending_queue = workhorse.pending_queue.replace(vec![]);
+      workhorse.heateq.submit(&workhorse.queue, pending_queue);
+      let heateq = &workhorse.heateq;
+      let study = crate::convergence::ConvergenceStudy::diffusive(
+         &resolutions, t_end, heateq.kappa, diffusion_number, heateq.integrator, heateq.boundary_conditions);
+      // the analytic solutions are only exact with every edge insulated
+      let insulated = heateq.boundary_conditions.iter().all(|x| *x == crate::boundary::BoundaryCondition::Insulated);
+
+      let initial = |x: f64, y: f64| solution.evaluate(x, y, 0., 0., 0.);
+      let report = match block_on(study.run(&workhorse.device, &workhorse.queue, initial, insulated.then_some(solution))) {
+         Ok(report) => report,
+         Err(e) => {println!("convergence study failed: {}", e); return}
+      };
+      print!("{}", report.as_table());
+
+      if let Some(path) = path {
+         match report.as_csv().map(|csv| std::fs::write(path, csv)) {
+            Ok(Ok(())) => println!("wrote {}", path),
+            Ok(Err(e)) => println!("could not write {}: {}", path, e),
+            Err(e) => println!("csv failed: {}", e)
+         }
+      }
+   }
+
+   /// fit <columns>x<rows> <time>:<field.csv>.. probes:<series.csv> [path], fitting kappa per
+   ///    region to fields written by export and probe series written by probe csv, starting
+   ///    from the field as it is now with the current kappa as the first guess. With a path
+   ///    the fitted kappa of every cell is written there as csv.
+   fn do_fit_instruction(&mut self, words: &[&str]) {
+      let usage = "usage: fit <columns>x<rows> <time>:<field.csv>.. probes:<series.csv> [path]";
+      let Some(((columns, rows), sources)) = words.split_first().and_then(|(regions, sources)| {
+         let (columns, rows) = regions.split_once('x')?;
+         Some(((columns.parse::<u32>().ok()?, rows.parse::<u32>().ok()?), sources))
+      }) else {println!("{}", usage); return};
+      let (path, sources) = match sources.split_last() {
+         Some((last, rest)) if !last.contains(':') => (Some(*last), rest),
+         _ => (None, sources)
+      };
+      if sources.is_empty() {println!("{}", usage); return}
+
+      let workhorse = &mut self.wgpuworkhorse;
+      let mut observations: Vec<crate::inverse::Observation> = Vec::new();
+      for source in sources {
+         let Some((time, path)) = source.split_once(':') else {println!("{}", usage); return};
+         let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {println!("could not read {}: {}", path, e); return}
+         };
+         let read = if time == "probes" {
+            let probes = workhorse.heateq.probes.as_ref().map_or(&[][..], |recorder| &recorder.probes[..]);
+            crate::inverse::probe_observations(&text, probes)
+         } else {
+            match time.parse::<f64>() {
+               Ok(time) => crate::rectgrid::RectGrid::readcsv(&text)
+                  .map(|field| vec![crate::inverse::Observation::Field { time, field }]),
+               Err(_) => {println!("{}", usage); return}
+            }
+         };
+         match read {
+            Ok(read) => observations.extend(read),
+            Err(e) => {println!("could not use {}: {}", path, e); return}
+         }
+      }
+
+      let pending_queue = workhorse.pending_queue.replace(vec![]);
+      workhorse.heateq.submit(&workhorse.queue, pending_queue);
+      let heateq = &workhorse.heateq;
+      let problem = crate::inverse::InverseProblem::new(observations, columns, rows, heateq.kappa);
+      let estimate = match block_on(problem.solve(heateq, &workhorse.device, &workhorse.queue)) {
+         Ok(estimate) => estimate,
+         Err(e) => {println!("fit failed: {}", e); return}
+      };
+      print!("{}", estimate.as_table());
+
+      if let Some(path) = path {
+         let map = estimate.kappa_map(heateq.width as usize, heateq.height as usize);
+         match map.writecsv().map(|csv| std::fs::write(path, csv)) {
+            Ok(Ok(())) => println!("wrote {}", path),
+            Ok(Err(e)) => println!("could not write {}: {}", path, e),
+            Err(e) => println!("csv failed: {}", e)
+         }
+      }
+   }
+
+   /// montecarlo <samples> <seed> <times> [kappa=<std>:<length>] [init=<std>:<length>] [batch=<n>] [prefix],
+   ///    sampling kappa and the field as it is now. With a prefix the mean, std and 95% band
+   ///    maps at each time are written to <prefix>_<n>_mean.csv and so on.
+   fn do_montecarlo_instruction(&mut self, words: &[&str]) {
+      let usage = "usage: montecarlo <samples> <seed> <times> [kappa=<std>:<length>] [init=<std>:<length>] [batch=<n>] [prefix]";
+      let [samples, seed, times, options @ ..] = words else {println!("{}", usage); return};
+      let (Ok(samples), Ok(seed), Ok(times)) = (samples.parse::<u32>(), seed.parse::<u64>(), crate::sweep::parse_values(times)) else {
+         println!("{}", usage);
+         return
+      };
+      let mut study = crate::montecarlo::MonteCarloStudy { samples, seed, kappa_field: None, initial_field: None, times, batch: 0 };
+      let mut prefix = None;
+      for option in options {
+         let parsed = match option.split_once('=') {
+            Some(("kappa", field)) => field.parse().map(|field| study.kappa_field = Some(field)),
+            Some(("init", field)) => field.parse().map(|field| study.initial_field = Some(field)),
+            Some(("batch", batch)) => batch.parse().map(|batch| study.batch = batch).map_err(|_| format!("{} is not a count", batch)),
+            Some(_) => Err(format!("{} is not an option", option)),
+            None => {prefix = Some(*option); Ok(())}
+         };
+         if let Err(e) = parsed {println!("{}\n{}", e, usage); return}
+      }
+
+      let workhorse = &mut self.wgpuworkhorse;
+      let pending_queue = workhorse.pending_queue.replace(vec![]);
+      workhorse.heateq.submit(&workhorse.queue, pending_queue);
+      let report = match block_on(study.run(&workhorse.heateq, &workhorse.device, &workhorse.queue)) {
+         Ok(report) => report,
+         Err(e) => {println!("monte carlo failed: {}", e); return}
+      };
+      print!("{}", report.as_table());
+
+      let Some(prefix) = prefix else {return};
+      for (n, maps) in report.maps.iter().enumerate() {
+         let (lower, upper) = maps.band(1.96);
+         for (name, grid) in [("mean", &maps.mean), ("std", &maps.std_dev), ("lower", &lower), ("upper", &upper)] {
+            let path = format!("{}_{}_{}.csv", prefix, n, name);
+            match grid.writecsv().map(|csv| std::fs::write(&path, csv)) {
+               Ok(Ok(())) => println!("wrote {}", path),
+               Ok(Err(e)) => println!("could not write {}: {}", path, e),
+               Err(e) => println!("csv failed: {}", e)
+            }
+         }
+      }
+   }
+
+   /// ensemble start <kappa>:<delta_t>.. | ensemble run <steps> | ensemble export <member> [path] | ensemble stop,
+   ///    every member starting from the field as it is now
+   fn do_ensemble_instruction(&mut self, words: &[&str]) {
+      let usage = "usage: ensemble start <kappa>:<delta_t>.. | ensemble run <steps> | ensemble export <member> [path] | ensemble stop";
+      let workhorse = &mut self.wgpuworkhorse;
+      let pending_queue = workhorse.pending_queue.replace(vec![]);
+      workhorse.heateq.submit(&workhorse.queue, pending_queue);
+      let heateq = &mut workhorse.heateq;
+
+      match words {
+         ["start", members @ ..] if !members.is_empty() => {
+            let members: Option<Vec<crate::ensemble::EnsembleMember>> = members.iter().map(|member| {
+               let (kappa, delta_t) = member.split_once(':')?;
+               Some(crate::ensemble::EnsembleMember {
+                  kappa: kappa.parse().ok()?, delta_t: delta_t.parse().ok()?, initial: None, kappa_map: None
+               })
+            }).collect();
+            let Some(members) = members else {println!("{}", usage); return};
+            match heateq.start_ensemble(&workhorse.device, &workhorse.queue, &members) {
+               Ok(()) => println!("started {} members", members.len()),
+               Err(e) => println!("{}", e)
+            }
+         }
+         ["run", steps] => {
+            let Ok(steps) = steps.parse::<u64>() else {println!("{}", usage); return};
+            let mut pending_queue = vec![];
+            if let Err(e) = heateq.send_ensemble_job(&mut pending_queue, &workhorse.device, steps) {
+               println!("{}", e);
+               return
+            }
+            heateq.submit(&workhorse.queue, pending_queue);
+            if let Some(ensemble) = &heateq.ensemble {
+               println!("{} steps, members at t = {:?}", ensemble.steps_taken, ensemble.member_times);
+            }
+         }
+         ["export", member, rest @ ..] => {
+            let Ok(member) = member.parse::<u32>() else {println!("{}", usage); return};
+            let path = rest.first().copied().unwrap_or("member.csv");
+            let grid = block_on(heateq.read_ensemble_member(&workhorse.device, &workhorse.queue, member));
+            match grid.and_then(|grid| grid.writecsv()).map(|csv| std::fs::write(path, csv)) {
+               Ok(Ok(())) => println!("wrote {}", path),
+               Ok(Err(e)) => println!("could not write {}: {}", path, e),
+               Err(e) => println!("export failed: {}", e)
+            }
+         }
+         ["stop"] => heateq.ensemble = None,
+         _ => println!("{}", usage)
+      }
+   }
+
+   /// probe add <name> <x> <y> [size=<x length>x<y length>] | probe every <steps> | probe clear | probe csv [path],
+   ///    x and y in domain units, the domain being the unit square between the walls unless sized
+   fn do_probe_instruction(&mut self, words: &[&str]) {
+      let usage = "usage: probe add <name> <x> <y> [size=<x length>x<y length>] | probe every <steps> | probe clear | probe csv [path]";
+      let workhorse = &mut self.wgpuworkhorse;
+      let (mut probes, mut every, capacity) = match &workhorse.heateq.probes {
+         Some(recorder) => (recorder.probes.clone(), recorder.every, recorder.capacity),
+         None => (vec![], 10, 4096)
+      };
+
+      match words {
+         ["add", name, x, y, sizes @ ..] if sizes.len() <= 1 => {
+            let (x_length, y_length) = match sizes.first().map(|size| size.strip_prefix("size=").ok_or(usage.to_string())
+               .and_then(crate::vtk::parse_lengths)) {
+               None => (1., 1.),
+               Some(Ok(lengths)) => lengths,
+               Some(Err(e)) => {println!("{}", e); return}
+            };
+            match (x.parse::<f64>(), y.parse::<f64>()) {
+               (Ok(x), Ok(y)) => {
+                  let heateq = &workhorse.heateq;
+                  let geometry = crate::vtk::VtkGeometry::solver_grid(heateq.width as usize, heateq.height as usize, x_length, y_length);
+                  probes.push(crate::probes::Probe::in_domain(name, x, y, &geometry))
+               }
+               _ => {println!("probe coordinates should be numbers in domain units"); return}
+            }
+         }
+         ["every", steps] => match steps.parse::<u32>() {
+            Ok(steps) => every = steps,
+            _ => {println!("usage: probe every <steps>"); return}
+         },
+         ["clear"] => {workhorse.heateq.probes = None; return}
+         ["csv", rest @ ..] => {
+            let path = rest.first().copied().unwrap_or("probes.csv");
+            let pending_queue = workhorse.pending_queue.replace(vec![]);
+            workhorse.heateq.submit(&workhorse.queue, pending_queue);
+            match block_on(workhorse.heateq.probes_as_csv(&workhorse.device, &workhorse.queue)) {
+               Ok(csv) => match std::fs::write(path, csv) {
+                  Ok(()) => println!("wrote {}", path),
+                  Err(e) => println!("could not write {}: {}", path, e)
+               },
+               Err(e) => println!("probe export failed: {}", e)
+            }
+            return
+         }
+         _ => {println!("{}", usage); return}
+      }
+
+      // changing the probes starts their recording over
+      if let Err(e) = workhorse.heateq.set_probes(&workhorse.device, probes, every, capacity) {
+         println!("{}", e);
+      }
+   }
+
+   /// `attempts` adaptive steps, or as many as it takes to land on `end_time` if there is one,
+   ///    blocking until they are done, then reports the clock
+   fn run_adaptive(&mut self, attempts: u32, end_time: Option<f64>, atol: f32, rtol: f32) {
+      let workhorse = &mut self.wgpuworkhorse;
+      let pending_queue = workhorse.pending_queue.replace(vec![]);
+      workhorse.heateq.submit(&workhorse.queue, pending_queue);
+      if let Err(e) = workhorse.heateq.enable_adaptive(&workhorse.device, atol, rtol) {
+         println!("{}", e);
+         return
+      }
+
+      match end_time {
+         Some(end_time) => {
+            let heateq = &mut workhorse.heateq;
+            if let Err(e) = block_on(heateq.adaptive_advance_to(&workhorse.device, &workhorse.queue, end_time, attempts)) {
+               println!("adaptive step failed: {}", e);
+            }
+         }
+         None => for _ in 0..attempts {
+            let heateq = &mut workhorse.heateq;
+            if let Err(e) = block_on(heateq.adaptive_step(&workhorse.device, &workhorse.queue, None)) {
+               println!("adaptive step failed: {}", e);
+               break
+            }
+         }
+      }
+
+      let heateq = &workhorse.heateq;
+      println!("t = {}, {} accepted, {} rejected, next delta_t {}",
+         heateq.sim_time, heateq.accepted_steps, heateq.rejected_steps,
+         heateq.adaptive.as_ref().map_or(0., |a| a.delta_t));
+
+      let mut pending_queue = workhorse.pending_queue.replace(vec![]);
+      workhorse.heateq.send_color_job(&mut pending_queue, &workhorse.device);
+      workhorse.heateq.color_to_texture(&mut pending_queue, &workhorse.device, &workhorse.texture_buffer);
+      _ = workhorse.pending_queue.replace(pending_queue);
+   }
+}
+
 pub struct App {
     #[cfg(target_arch = "wasm32")]
     proxy: Option<winit::event_loop::EventLoopProxy<State>>,
@@ -0,0 +1,85 @@
+struct EdgeFluxParams {
+   width: u32,
+   // same spacing as laplacian.wgsl, delta_x = 1/width and delta_y = 1/height
+   height: u32,
+   _pad0: u32,
+   _pad: u32,
+   kappa: f32,
+   // the step that just finished, 0 when we are only seeding the rates
+   delta_t: f32,
+   _pad1: f32,
+   _pad2: f32,
+}
+
+@group(0) @binding(0) var<storage, read> data: array<f32>;
+// [0..4] latest outward rate through each edge, in the order y=0, x=0, y=1, x=1
+// [4..8] those rates integrated over time, [8..12] the compensation for that sum,
+// [12] the sum of all edge cells, [13] 1 once the rates have been seeded
+@group(0) @binding(1) var<storage, read_write> tally: array<f32, 16>;
+@group(0) @binding(2) var<uniform> params: EdgeFluxParams;
+
+var<workgroup> partial_rates: array<vec4<f32>, 64>;
+var<workgroup> partial_edge_sums: array<f32, 64>;
+
+// the heat leaving through each edge per unit time is kappa times the same differences the
+//    laplacian takes across the edge, summed along it. this is exactly what the interior cells
+//    lose per unit time, so energy plus what has left stays constant
+@compute
+@workgroup_size(64,1,1)
+fn main(
+   @builtin(local_invocation_id) lid: vec3<u32>
+) {
+   let width = params.width;
+   let height = params.height;
+
+   var rates = vec4<f32>(0.0);
+   var edge_sum = 0.0f;
+   for (var i = 1u + lid.x; i < width - 1u; i += 64u) {
+      rates.x += data[i + width] - data[i];
+      rates.z += data[i + (height - 2u) * width] - data[i + (height - 1u) * width];
+   }
+   for (var j = 1u + lid.x; j < height - 1u; j += 64u) {
+      rates.y += data[j * width + 1u] - data[j * width];
+      rates.w += data[j * width + width - 2u] - data[j * width + width - 1u];
+      edge_sum += data[j * width] + data[j * width + width - 1u];
+   }
+   for (var i = lid.x; i < width; i += 64u) {
+      edge_sum += data[i] + data[i + (height - 1u) * width];
+   }
+   partial_rates[lid.x] = rates;
+   partial_edge_sums[lid.x] = edge_sum;
+   workgroupBarrier();
+
+   for (var stride = 32u; stride > 0u; stride = stride >> 1u) {
+      if (lid.x < stride) {
+         partial_rates[lid.x] += partial_rates[lid.x + stride];
+         partial_edge_sums[lid.x] += partial_edge_sums[lid.x + stride];
+      }
+      workgroupBarrier();
+   }
+
+   if (lid.x == 0u) {
+      let delta_x = 1.0f / f32(width);
+      let delta_y = 1.0f / f32(params.height);
+      let along_y_edges = params.kappa * delta_x / delta_y;
+      let along_x_edges = params.kappa * delta_y / delta_x;
+      let rate = partial_rates[0] * vec4<f32>(along_y_edges, along_x_edges, along_y_edges, along_x_edges);
+
+      if (tally[13] == 1.0f) {
+         // trapezoid rule between the end of the last step and the end of this one,
+         //    kahan summed so long runs dont lose the small increments
+         for (var edge = 0u; edge < 4u; edge++) {
+            let increment = params.delta_t * 0.5f * (tally[edge] + rate[edge]);
+            let y = increment - tally[8u + edge];
+            let t = tally[4u + edge] + y;
+            tally[8u + edge] = (t - tally[4u + edge]) - y;
+            tally[4u + edge] = t;
+         }
+      }
+      for (var edge = 0u; edge < 4u; edge++) {
+         tally[edge] = rate[edge];
+      }
+      tally[12] = partial_edge_sums[0];
+      tally[13] = 1.0f;
+   }
+}
@@ -0,0 +1,273 @@
+use bytemuck::cast_slice;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::rectgrid::RectGrid;
+use crate::webgpuheat::{HeatComputer, helper_basic_compute_shader, helper_compute_bind_group, helper_compute_interim_data_buffer};
+
+/// One simulation of an ensemble. Every member is the size of the computer it runs on and
+///    shares its edge conditions, but steps with its own kappa and delta_t.
+pub struct EnsembleMember {
+   pub kappa: f32,
+   pub delta_t: f32,
+   /// None starts from the computer's field as it is now
+   pub initial: Option<RectGrid>,
+   /// what each cell multiplies kappa by, for materials that vary over the grid. None is 1
+   ///    everywhere.
+   pub kappa_map: Option<RectGrid>,
+}
+
+/// Many independent fields stepped together with RK2, one after the other in the same
+///    buffers, so a sweep over hundreds of parameters takes one dispatch per stage rather
+///    than a computer each. The laplacian is taken inside the stage kernel, so on top of
+///    the fields there is only a midpoint per member. If any member has a kappa map
+///    they all step with the variable kappa kernel, which costs a map per member more.
+pub struct Ensemble {
+   pub members: u32,
+   pub params: Vec<(f32, f32)>,
+   /// simulated time of each member, which differ as their delta_t do
+   pub member_times: Vec<f64>,
+   pub steps_taken: u64,
+   pub stage_shader: wgpu::ShaderModule,
+   pub stage_pipeline: wgpu::ComputePipeline,
+   // the fields ping pong between these two, `current` says which has the latest
+   pub field_buffers: [wgpu::Buffer; 2],
+   pub current: usize,
+   pub midpoint_buffer: wgpu::Buffer,
+   pub member_buffer: wgpu::Buffer,
+   pub kappa_map_buffer: Option<wgpu::Buffer>,
+   pub half_buffer: wgpu::Buffer,
+   pub full_buffer: wgpu::Buffer,
+   // indexed by current
+   pub boundary_bind_groups: [wgpu::BindGroup; 2],
+   pub midpoint_boundary_bind_group: wgpu::BindGroup,
+   pub half_bind_groups: [wgpu::BindGroup; 2],
+   pub full_bind_groups: [wgpu::BindGroup; 2],
+}
+
+impl Ensemble {
+   /// Lays out and fills the members. Takes the queue to copy in the ones starting from the
+   ///    computer's field, so anything still in a pending queue should be submitted first.
+   pub fn new(heateq: &HeatComputer, device: &wgpu::Device, queue: &wgpu::Queue, members: &[EnsembleMember]) -> anyhow::Result<Self> {
+      let cells = (heateq.width * heateq.height) as u64;
+      let count = members.len() as u64;
+      let limits = device.limits();
+      if members.is_empty() {anyhow::bail!("an ensemble needs at least one member")}
+      if count * cells > HeatComputer::max_cells(&limits) {
+         anyhow::bail!("{} members of {} cells do not fit in one buffer on this device", count, cells)
+      }
+      if count > limits.max_compute_workgroups_per_dimension as u64 {
+         anyhow::bail!("at most {} members can be dispatched at once", limits.max_compute_workgroups_per_dimension)
+      }
+      for (n, member) in members.iter().enumerate() {
+         if let Some(initial) = &member.initial
+            && (initial.width(), initial.height()) != (heateq.width as usize, heateq.height as usize) {
+            anyhow::bail!("member {} starts from a {}x{} grid, the computer is {}x{}",
+               n, initial.width(), initial.height(), heateq.width, heateq.height)
+         }
+         if let Some(kappa_map) = &member.kappa_map
+            && (kappa_map.width(), kappa_map.height()) != (heateq.width as usize, heateq.height as usize) {
+            anyhow::bail!("member {} has a {}x{} kappa map, the computer is {}x{}",
+               n, kappa_map.width(), kappa_map.height(), heateq.width, heateq.height)
+         }
+      }
+
+      let variable = members.iter().any(|member| member.kappa_map.is_some());
+      let stage_shader = if variable {
+         device.create_shader_module(wgpu::include_wgsl!("ensemble_variable_stage.wgsl"))
+      } else {
+         device.create_shader_module(wgpu::include_wgsl!("ensemble_stage.wgsl"))
+      };
+      let stage_pipeline = helper_basic_compute_shader(device, Some("Ensemble Stage Pipeline"), &stage_shader);
+
+      let size = count * cells * 4;
+      let field_buffers = [
+         helper_compute_interim_data_buffer(device, Some("ensemble fields"), size),
+         helper_compute_interim_data_buffer(device, Some("ensemble fields"), size),
+      ];
+      let midpoint_buffer = helper_compute_interim_data_buffer(device, Some("ensemble midpoint"), size);
+      let params: Vec<(f32, f32)> = members.iter().map(|member| (member.kappa, member.delta_t)).collect();
+      let member_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("ensemble members"),
+         contents: cast_slice(&params.iter().flat_map(|&(kappa, delta_t)| [kappa, delta_t]).collect::<Vec<f32>>()),
+         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+      });
+      let kappa_map_buffer = variable.then(|| {
+         let ones = vec![1f32; cells as usize];
+         let maps: Vec<f32> = members.iter().flat_map(|member| match &member.kappa_map {
+            Some(kappa_map) => kappa_map.getarray().clone(),
+            None => ones.clone(),
+         }).collect();
+         device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("ensemble kappa maps"),
+            contents: cast_slice(&maps),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+         })
+      });
+      let half_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("half step"),
+         contents: cast_slice(&[0.5f32]),
+         usage: wgpu::BufferUsages::UNIFORM,
+      });
+      let full_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("full step"),
+         contents: cast_slice(&[1f32]),
+         usage: wgpu::BufferUsages::UNIFORM,
+      });
+
+      let boundary_bind_group = |buffer: &wgpu::Buffer| helper_compute_bind_group(
+         device, None, &heateq.fix_boundary_conditions_ppln,
+         &[buffer, &heateq.width_buffer, &heateq.height_buffer, &heateq.boundary_buffer]
+      );
+      let stage_bind_group = |base: &wgpu::Buffer, slope_of: &wgpu::Buffer, output: &wgpu::Buffer, fraction: &wgpu::Buffer| {
+         let mut buffers = vec![base, slope_of, output, &member_buffer, &heateq.width_buffer, &heateq.height_buffer, fraction];
+         buffers.extend(kappa_map_buffer.as_ref());
+         helper_compute_bind_group(device, None, &stage_pipeline, &buffers)
+      };
+      let boundary_bind_groups = [boundary_bind_group(&field_buffers[0]), boundary_bind_group(&field_buffers[1])];
+      let midpoint_boundary_bind_group = boundary_bind_group(&midpoint_buffer);
+      let half_bind_groups = [0, 1].map(|n|
+         stage_bind_group(&field_buffers[n], &field_buffers[n], &midpoint_buffer, &half_buffer));
+      let full_bind_groups = [0, 1].map(|n|
+         stage_bind_group(&field_buffers[n], &midpoint_buffer, &field_buffers[1 - n], &full_buffer));
+
+      let mut encoder = device.create_command_encoder(&Default::default());
+      for (n, member) in members.iter().enumerate() {
+         let offset = n as u64 * cells * 4;
+         match &member.initial {
+            Some(initial) => queue.write_buffer(&field_buffers[0], offset, cast_slice(initial.getarray())),
+            None => encoder.copy_buffer_to_buffer(&heateq.data_buffer, 0, &field_buffers[0], offset, cells * 4),
+         }
+      }
+      queue.submit([encoder.finish()]);
+
+      Ok(Self {
+         members: count as u32,
+         member_times: vec![heateq.sim_time; members.len()],
+         params,
+         steps_taken: 0,
+         stage_shader,
+         stage_pipeline,
+         field_buffers,
+         current: 0,
+         midpoint_buffer,
+         member_buffer,
+         kappa_map_buffer,
+         half_buffer,
+         full_buffer,
+         boundary_bind_groups,
+         midpoint_boundary_bind_group,
+         half_bind_groups,
+         full_bind_groups,
+      })
+   }
+
+   fn record_boundary_conditions(&self, heateq: &HeatComputer, gputodo: &mut wgpu::ComputePass, bind_group: &wgpu::BindGroup) {
+      let boundary_conds_wg_quant = (heateq.width*2 + heateq.height*2).div_ceil(heateq.workgroup_size);
+
+      gputodo.set_pipeline(&heateq.fix_boundary_conditions_ppln);
+      gputodo.set_bind_group(0, bind_group, &[]);
+      gputodo.dispatch_workgroups(boundary_conds_wg_quant, 1, self.members);
+   }
+
+   /// one RK2 step of every member, the same as the computer's own but all at once
+   fn record_step(&self, heateq: &HeatComputer, gputodo: &mut wgpu::ComputePass, current: usize) {
+      let x_workgroup_quantity = heateq.width.div_ceil(8);
+      let y_workgroup_quantity = heateq.height.div_ceil(8);
+
+      self.record_boundary_conditions(heateq, gputodo, &self.boundary_bind_groups[current]);
+
+      gputodo.set_pipeline(&self.stage_pipeline);
+      gputodo.set_bind_group(0, &self.half_bind_groups[current], &[]);
+      gputodo.dispatch_workgroups(x_workgroup_quantity, y_workgroup_quantity, self.members);
+
+      self.record_boundary_conditions(heateq, gputodo, &self.midpoint_boundary_bind_group);
+
+      gputodo.set_pipeline(&self.stage_pipeline);
+      gputodo.set_bind_group(0, &self.full_bind_groups[current], &[]);
+      gputodo.dispatch_workgroups(x_workgroup_quantity, y_workgroup_quantity, self.members);
+   }
+
+   /// queues `steps` steps of every member, with the edges put back after the last so the
+   ///    fields read back whole
+   pub fn send_steps(
+      &mut self,
+      heateq: &HeatComputer,
+      pending_queue: &mut Vec<wgpu::CommandBuffer>,
+      device: &wgpu::Device,
+      steps: u32,
+   ) {
+      let mut encoder = device.create_command_encoder(&Default::default());
+      {
+         let mut gputodo = encoder.begin_compute_pass(&Default::default());
+         for _ in 0..steps {
+            self.record_step(heateq, &mut gputodo, self.current);
+            self.current = 1 - self.current;
+         }
+         self.record_boundary_conditions(heateq, &mut gputodo, &self.boundary_bind_groups[self.current]);
+      }
+      pending_queue.push(encoder.finish());
+
+      self.steps_taken += steps as u64;
+      for (time, (_, delta_t)) in self.member_times.iter_mut().zip(self.params.iter()) {
+         *time += steps as f64 * *delta_t as f64;
+      }
+   }
+
+   /// reads back one member's field. Anything still in a pending queue should be submitted
+   ///    first.
+   pub async fn read_member(&self, heateq: &HeatComputer, device: &wgpu::Device, queue: &wgpu::Queue, member: u32) -> anyhow::Result<RectGrid> {
+      if member >= self.members {anyhow::bail!("there is no member {}, only {}", member, self.members)}
+      let size = (heateq.width * heateq.height) as u64 * 4;
+      let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("ensemble member readback"),
+         size,
+         usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+         mapped_at_creation: false,
+      });
+
+      let mut encoder = device.create_command_encoder(&Default::default());
+      encoder.copy_buffer_to_buffer(&self.field_buffers[self.current], member as u64 * size, &staging_buffer, 0, size);
+      let (sender, receiver) = tokio::sync::oneshot::channel();
+      encoder.map_buffer_on_submit(&staging_buffer, wgpu::MapMode::Read, ..,
+         move |result| {_ = sender.send(result);});
+      queue.submit([encoder.finish()]);
+
+      device.poll(wgpu::PollType::wait_indefinitely())?;
+      receiver.await??;
+
+      let field: Vec<f32> = cast_slice(&staging_buffer.get_mapped_range(..)).to_vec();
+      staging_buffer.unmap();
+
+      Ok(RectGrid::newfromdata(heateq.width as usize, heateq.height as usize, field))
+   }
+
+   /// every member's field in one readback, in member order
+   pub async fn read_all(&self, heateq: &HeatComputer, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<Vec<RectGrid>> {
+      let cells = (heateq.width * heateq.height) as usize;
+      let field_buffer = &self.field_buffers[self.current];
+      let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("ensemble readback"),
+         size: field_buffer.size(),
+         usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+         mapped_at_creation: false,
+      });
+
+      let mut encoder = device.create_command_encoder(&Default::default());
+      encoder.copy_buffer_to_buffer(field_buffer, 0, &staging_buffer, 0, field_buffer.size());
+      let (sender, receiver) = tokio::sync::oneshot::channel();
+      encoder.map_buffer_on_submit(&staging_buffer, wgpu::MapMode::Read, ..,
+         move |result| {_ = sender.send(result);});
+      queue.submit([encoder.finish()]);
+
+      device.poll(wgpu::PollType::wait_indefinitely())?;
+      receiver.await??;
+
+      let fields: Vec<RectGrid> = cast_slice::<u8, f32>(&staging_buffer.get_mapped_range(..))
+         .chunks(cells)
+         .map(|field| RectGrid::newfromdata(heateq.width as usize, heateq.height as usize, field.to_vec()))
+         .collect();
+      staging_buffer.unmap();
+
+      Ok(fields)
+   }
+}
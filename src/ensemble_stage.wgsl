@@ -0,0 +1,47 @@
+@group(0) @binding(0) var<storage, read> base: array<f32>;
+@group(0) @binding(1) var<storage, read> slope_of: array<f32>;
+@group(0) @binding(2) var<storage, read_write> output: array<f32>;
+@group(0) @binding(3) var<storage, read> members: array<MemberParams>;
+@group(0) @binding(4) var<uniform> width: u32;
+@group(0) @binding(5) var<uniform> height: u32;
+// how much of delta_t this stage takes, 0.5 for the midpoint and 1 for the full step
+@group(0) @binding(6) var<uniform> fraction: f32;
+
+struct MemberParams {
+   kappa: f32,
+   delta_t: f32,
+}
+
+// one RK2 stage for every member at once, gid.z being the member:
+//    output = base + fraction delta_t kappa lap(slope_of). the laplacian is taken on the
+//    spot the same way laplacian.wgsl does, so there is no laplacian buffer per member.
+//    edge cells are left for boundary_cond.wgsl, run at each member's offset.
+@compute// Entrypoint
+@workgroup_size(8,8,1)
+fn main(
+   @builtin(global_invocation_id) gid: vec3<u32>
+) {
+   if ((gid.x == 0) | (gid.x >= width - 1)) {return;}
+   if ((gid.y == 0) | (gid.y >= height - 1)) {return;}
+   if (gid.z >= arrayLength(&members)) {return;}
+
+   let delta_x_sq = 1.0f / f32(width) / f32(width);
+   let delta_y_sq = 1.0f / f32(height) / f32(height);
+
+   let params = members[gid.z];
+   let indexwecareabout = gid.z * width * height + gid.x + gid.y * width;
+
+   let laplacian =
+      (
+         slope_of[indexwecareabout + 1]
+         -2.0f * slope_of[indexwecareabout]
+         + slope_of[indexwecareabout - 1]
+      ) / delta_x_sq
+      + (
+         slope_of[indexwecareabout + width]
+         -2.0f * slope_of[indexwecareabout]
+         + slope_of[indexwecareabout - width]
+      ) / delta_y_sq;
+
+   output[indexwecareabout] = base[indexwecareabout] + fraction * params.delta_t * params.kappa * laplacian;
+}
@@ -0,0 +1,53 @@
+@group(0) @binding(0) var<storage, read> base: array<f32>;
+@group(0) @binding(1) var<storage, read> slope_of: array<f32>;
+@group(0) @binding(2) var<storage, read_write> output: array<f32>;
+@group(0) @binding(3) var<storage, read> members: array<MemberParams>;
+@group(0) @binding(4) var<uniform> width: u32;
+@group(0) @binding(5) var<uniform> height: u32;
+// how much of delta_t this stage takes, 0.5 for the midpoint and 1 for the full step
+@group(0) @binding(6) var<uniform> fraction: f32;
+// what each cell multiplies its member's kappa by, laid out like the fields
+@group(0) @binding(7) var<storage, read> kappa_map: array<f32>;
+
+struct MemberParams {
+   kappa: f32,
+   delta_t: f32,
+}
+
+// across a face between two cells the conductivities act in series
+fn face(a: f32, b: f32) -> f32 {
+   return 2.0f * a * b / max(a + b, 1e-30f);
+}
+
+// ensemble_stage.wgsl with kappa varying over the grid, so in place of kappa lap(T) it
+//    takes div(kappa grad T), with each face's kappa the harmonic mean of the cells it
+//    separates.
+@compute// Entrypoint
+@workgroup_size(8,8,1)
+fn main(
+   @builtin(global_invocation_id) gid: vec3<u32>
+) {
+   if ((gid.x == 0) | (gid.x >= width - 1)) {return;}
+   if ((gid.y == 0) | (gid.y >= height - 1)) {return;}
+   if (gid.z >= arrayLength(&members)) {return;}
+
+   let delta_x_sq = 1.0f / f32(width) / f32(width);
+   let delta_y_sq = 1.0f / f32(height) / f32(height);
+
+   let params = members[gid.z];
+   let n = gid.z * width * height + gid.x + gid.y * width;
+
+   let c = kappa_map[n];
+   let t = slope_of[n];
+   let divergence =
+      (
+         face(c, kappa_map[n + 1]) * (slope_of[n + 1] - t)
+         - face(c, kappa_map[n - 1]) * (t - slope_of[n - 1])
+      ) / delta_x_sq
+      + (
+         face(c, kappa_map[n + width]) * (slope_of[n + width] - t)
+         - face(c, kappa_map[n - width]) * (t - slope_of[n - width])
+      ) / delta_y_sq;
+
+   output[n] = base[n] + fraction * params.delta_t * params.kappa * divergence;
+}
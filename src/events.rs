@@ -0,0 +1,98 @@
+use std::sync::{Arc, Mutex};
+
+use crate::webgpuheat::ComputeRelevantEvent;
+
+/// heard on whichever thread polls the device, and wgpu wants Send even on the web
+pub type EventListener = Box<dyn FnMut(&ComputeRelevantEvent) + Send>;
+
+// what got recorded into a pending queue since the last submission
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecordedWork {
+   pub compute: bool,
+   pub color: bool,
+   pub copy: bool,
+}
+
+#[derive(Default)]
+struct Subscribers {
+   next_id: u64,
+   listeners: Vec<(u64, EventListener)>,
+   // how many emits have the listeners out, so an unsubscribe from inside one still sticks
+   emitting: u32,
+   dropped: Vec<u64>,
+}
+
+/// Everyone who wants to hear what the gpu is up to. Cheap to clone, every clone shares the
+///    same listeners, which is how the callbacks the queue and buffers hold on to reach them.
+///    Listeners may subscribe and unsubscribe from inside a listener, and one added then
+///    hears from the next event on.
+#[derive(Clone, Default)]
+pub struct EventStream {
+   subscribers: Arc<Mutex<Subscribers>>,
+   submissions: Arc<Mutex<u64>>,
+}
+
+impl EventStream {
+   /// gives back an id for unsubscribe
+   pub fn subscribe(&self, listener: EventListener) -> u64 {
+      let mut subscribers = self.subscribers.lock().unwrap();
+      subscribers.next_id += 1;
+      let id = subscribers.next_id;
+      subscribers.listeners.push((id, listener));
+      id
+   }
+
+   pub fn unsubscribe(&self, id: u64) {
+      let mut subscribers = self.subscribers.lock().unwrap();
+      subscribers.listeners.retain(|(listener_id, _)| *listener_id != id);
+      if subscribers.emitting > 0 {subscribers.dropped.push(id)}
+   }
+
+   pub fn emit(&self, event: ComputeRelevantEvent) {
+      let mut listeners = {
+         let mut subscribers = self.subscribers.lock().unwrap();
+         subscribers.emitting += 1;
+         std::mem::take(&mut subscribers.listeners)
+      };
+      for (_, listener) in listeners.iter_mut() {
+         listener(&event);
+      }
+      let mut subscribers = self.subscribers.lock().unwrap();
+      subscribers.emitting -= 1;
+      let dropped = match subscribers.emitting {
+         0 => std::mem::take(&mut subscribers.dropped),
+         _ => subscribers.dropped.clone()
+      };
+      listeners.retain(|(id, _)| !dropped.contains(id));
+      listeners.append(&mut subscribers.listeners);
+      subscribers.listeners = listeners;
+   }
+
+   /// Says `work` has gone to the queue as the next submission, and has the queue say when
+   ///    each part of it is done. Work done is reported in the order it happens on the gpu,
+   ///    steps, then colour, then the copy to the texture, all at once as the queue only
+   ///    tells us about the whole submission.
+   pub fn submitted(&self, queue: &wgpu::Queue, work: RecordedWork) {
+      let submission = {
+         let mut submissions = self.submissions.lock().unwrap();
+         *submissions += 1;
+         *submissions
+      };
+      self.emit(ComputeRelevantEvent::Submitted(submission));
+
+      let events = self.clone();
+      queue.on_submitted_work_done(move || {
+         if work.compute {events.emit(ComputeRelevantEvent::ComputeDoneNowColor(submission))}
+         if work.color {events.emit(ComputeRelevantEvent::ColorIsDone(submission))}
+         if work.copy {events.emit(ComputeRelevantEvent::ColorCopyDone(submission))}
+      });
+   }
+
+   /// a readback either mapped or failed to
+   pub fn readback(&self, result: &Result<(), wgpu::BufferAsyncError>) {
+      self.emit(match result {
+         Ok(()) => ComputeRelevantEvent::ReadbackReady,
+         Err(e) => ComputeRelevantEvent::Error(format!("readback failed: {}", e))
+      });
+   }
+}
@@ -0,0 +1,131 @@
+use bytemuck::cast_slice;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::webgpuheat::{HeatComputer, helper_basic_compute_shader, helper_compute_bind_group, helper_compute_interim_data_buffer};
+
+/// Which field the heatmap shows, and which one a view export reads back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViewField {
+   Temperature,
+   FluxX,
+   FluxY,
+   FluxMagnitude,
+   /// the accumulated maps, only there once accumulators are started
+   PeakTemperature,
+   TimeAbove,
+   FirstArrival,
+}
+
+impl ViewField {
+   pub fn is_flux(&self) -> bool {
+      matches!(self, ViewField::FluxX | ViewField::FluxY | ViewField::FluxMagnitude)
+   }
+
+   pub fn is_accumulated(&self) -> bool {
+      matches!(self, ViewField::PeakTemperature | ViewField::TimeAbove | ViewField::FirstArrival)
+   }
+}
+
+impl std::str::FromStr for ViewField {
+   type Err = String;
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      match s {
+         "temperature" => Ok(ViewField::Temperature),
+         "flux_x" => Ok(ViewField::FluxX),
+         "flux_y" => Ok(ViewField::FluxY),
+         "flux_mag" => Ok(ViewField::FluxMagnitude),
+         "peak_T" => Ok(ViewField::PeakTemperature),
+         "time_above" => Ok(ViewField::TimeAbove),
+         "first_arrival" => Ok(ViewField::FirstArrival),
+         other => Err(format!(
+            "unknown field {}, expected temperature, flux_x, flux_y, flux_mag, peak_T, time_above or first_arrival", other))
+      }
+   }
+}
+
+/// The heat flux -k grad T of the current field, as x and y components and magnitude, using
+///    the same spacing as the laplacian. grad T itself is just -flux/k, so with k = 1 these
+///    are the gradient with its sign flipped.
+pub struct FluxComputer {
+   pub conductivity: f32,
+   pub flux_pipeline: wgpu::ComputePipeline,
+   pub conductivity_buffer: wgpu::Buffer,
+   pub flux_x_buffer: wgpu::Buffer,
+   pub flux_y_buffer: wgpu::Buffer,
+   pub magnitude_buffer: wgpu::Buffer,
+   pub flux_bind_group: wgpu::BindGroup,
+   // the heat hue pipeline reading each of the three instead of data
+   pub heat_hue_bind_groups: [wgpu::BindGroup; 3],
+}
+
+impl FluxComputer {
+   pub fn new(heateq: &HeatComputer, device: &wgpu::Device, conductivity: f32) -> Self {
+      let size = heateq.data_buffer.size();
+
+      let flux_shader = device.create_shader_module(wgpu::include_wgsl!("flux.wgsl"));
+      let flux_pipeline = helper_basic_compute_shader(device, Some("Flux Pipeline"), &flux_shader);
+
+      let conductivity_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("conductivity"),
+         contents: cast_slice(&[conductivity]),
+         usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      });
+      let flux_x_buffer = helper_compute_interim_data_buffer(device, Some("flux x"), size);
+      let flux_y_buffer = helper_compute_interim_data_buffer(device, Some("flux y"), size);
+      let magnitude_buffer = helper_compute_interim_data_buffer(device, Some("flux magnitude"), size);
+
+      let flux_bind_group = helper_compute_bind_group(
+         device, None, &flux_pipeline,
+         &[&heateq.data_buffer, &flux_x_buffer, &flux_y_buffer, &magnitude_buffer, &heateq.width_buffer,
+            &heateq.height_buffer, &conductivity_buffer]
+      );
+      let heat_hue_bind_groups = [&flux_x_buffer, &flux_y_buffer, &magnitude_buffer].map(|buffer|
+         helper_compute_bind_group(
+            device, None, &heateq.heat_hue_pipeline,
+            &[buffer, &heateq.heat_map_buffer, &heateq.vis_minT_buffer, &heateq.vis_maxT_buffer,
+               &heateq.width_buffer, &heateq.height_buffer, &heateq.pad_buffer]
+         )
+      );
+
+      Self {
+         conductivity,
+         flux_pipeline,
+         conductivity_buffer,
+         flux_x_buffer,
+         flux_y_buffer,
+         magnitude_buffer,
+         flux_bind_group,
+         heat_hue_bind_groups,
+      }
+   }
+
+   pub fn set_conductivity(&mut self, queue: &wgpu::Queue, conductivity: f32) {
+      self.conductivity = conductivity;
+      queue.write_buffer(&self.conductivity_buffer, 0, cast_slice(&[conductivity]));
+   }
+
+   pub fn record(&self, heateq: &HeatComputer, gputodo: &mut wgpu::ComputePass) {
+      gputodo.set_pipeline(&self.flux_pipeline);
+      gputodo.set_bind_group(0, &self.flux_bind_group, &[]);
+      gputodo.dispatch_workgroups(heateq.width.div_ceil(8), heateq.height.div_ceil(8), 1);
+   }
+
+   /// where a flux view lives, None for the others
+   pub fn buffer(&self, view: ViewField) -> Option<&wgpu::Buffer> {
+      match view {
+         ViewField::FluxX => Some(&self.flux_x_buffer),
+         ViewField::FluxY => Some(&self.flux_y_buffer),
+         ViewField::FluxMagnitude => Some(&self.magnitude_buffer),
+         _ => None,
+      }
+   }
+
+   pub fn heat_hue_bind_group(&self, view: ViewField) -> Option<&wgpu::BindGroup> {
+      match view {
+         ViewField::FluxX => Some(&self.heat_hue_bind_groups[0]),
+         ViewField::FluxY => Some(&self.heat_hue_bind_groups[1]),
+         ViewField::FluxMagnitude => Some(&self.heat_hue_bind_groups[2]),
+         _ => None,
+      }
+   }
+}
@@ -0,0 +1,35 @@
+@group(0) @binding(0) var<storage, read> data: array<f32>;
+@group(0) @binding(1) var<storage, read_write> flux_x: array<f32>;
+@group(0) @binding(2) var<storage, read_write> flux_y: array<f32>;
+@group(0) @binding(3) var<storage, read_write> magnitude: array<f32>;
+@group(0) @binding(4) var<uniform> width: u32;
+@group(0) @binding(5) var<uniform> height: u32;
+// same spacing as laplacian.wgsl, delta_x = 1/width and delta_y = 1/height
+@group(0) @binding(6) var<uniform> conductivity: f32;
+
+// flux = -k grad T. central differences inside, one sided differences on the edges,
+//    which come out as 0 across insulated edges since those cells copy their neighbour
+@compute
+@workgroup_size(8,8,1)
+fn main(
+   @builtin(global_invocation_id) gid: vec3<u32>
+) {
+   if ((gid.x >= width) | (gid.y >= height)) {return;}
+
+   let index = gid.x + gid.y * width;
+
+   let left = select(gid.x - 1u, gid.x, gid.x == 0u);
+   let right = select(gid.x + 1u, gid.x, gid.x == width - 1u);
+   let below = select(gid.y - 1u, gid.y, gid.y == 0u);
+   let above = select(gid.y + 1u, gid.y, gid.y == height - 1u);
+
+   let delta_x = 1.0f / f32(width);
+   let delta_y = 1.0f / f32(height);
+
+   let grad_x = (data[right + gid.y * width] - data[left + gid.y * width]) / (f32(right - left) * delta_x);
+   let grad_y = (data[gid.x + above * width] - data[gid.x + below * width]) / (f32(above - below) * delta_y);
+
+   flux_x[index] = -conductivity * grad_x;
+   flux_y[index] = -conductivity * grad_y;
+   magnitude[index] = conductivity * sqrt(grad_x * grad_x + grad_y * grad_y);
+}
@@ -0,0 +1,26 @@
+// swaps halo rows between two neighbouring tiles of a grid: the last row the upper tile owns
+//    becomes the lower tile's top halo, and the first row the lower tile owns becomes the
+//    upper tile's bottom halo
+
+struct HaloParams {
+   width: u32,
+   // rows in the upper tile, its halos included
+   upper_rows: u32,
+   _pad0: u32,
+   _pad1: u32,
+}
+
+@group(0) @binding(0) var<storage, read_write> upper: array<f32>;
+@group(0) @binding(1) var<storage, read_write> lower: array<f32>;
+@group(0) @binding(2) var<uniform> params: HaloParams;
+
+@compute
+@workgroup_size(64,1,1)
+fn main(
+   @builtin(global_invocation_id) gid: vec3<u32>
+) {
+   if (gid.x >= params.width) {return;}
+
+   lower[gid.x] = upper[(params.upper_rows - 2u) * params.width + gid.x];
+   upper[(params.upper_rows - 1u) * params.width + gid.x] = lower[params.width + gid.x];
+}
@@ -0,0 +1,55 @@
+struct HeunParams {
+   // kappa * delta_t / 2
+   kappa_half_dt: f32,
+   atol: f32,
+   rtol: f32,
+   _pad: f32,
+}
+
+@group(0) @binding(0) var<storage, read> data: array<f32>;
+@group(0) @binding(1) var<storage, read> laplacian: array<f32>;
+@group(0) @binding(2) var<storage, read> predictor_laplacian: array<f32>;
+@group(0) @binding(3) var<storage, read_write> out: array<f32>;
+// the largest scaled error anywhere, as the bits of a non negative f32 (which order the same as the floats do)
+@group(0) @binding(4) var<storage, read_write> error_bits: atomic<u32>;
+@group(0) @binding(5) var<uniform> width: u32;
+@group(0) @binding(6) var<uniform> height: u32;
+@group(0) @binding(7) var<uniform> params: HeunParams;
+
+// invocations in a row of workgroups, see linear_workgroups in webgpuheat.rs
+const ROW_LENGTH: u32 = 4194240u;
+
+var<workgroup> worst: array<f32, 64>;
+
+// heun's step from the euler predictor whose laplacian is predictor_laplacian, and the
+//    difference between the two as our error estimate
+@compute
+@workgroup_size(64,1,1)
+fn main(
+   @builtin(global_invocation_id) gid: vec3<u32>,
+   @builtin(local_invocation_id) lid: vec3<u32>
+) {
+   // no early return here, every invocation has to reach the barriers
+   var scaled_error = 0.0f;
+   let cell = gid.x + gid.y * ROW_LENGTH;
+   if (cell < width * height) {
+      let here = data[cell];
+      let heun = here + params.kappa_half_dt * (laplacian[cell] + predictor_laplacian[cell]);
+      out[cell] = heun;
+      let error = abs(params.kappa_half_dt * (predictor_laplacian[cell] - laplacian[cell]));
+      scaled_error = error / (params.atol + params.rtol * max(abs(here), abs(heun)));
+   }
+
+   worst[lid.x] = scaled_error;
+   workgroupBarrier();
+   for (var stride = 32u; stride > 0u; stride = stride >> 1u) {
+      if (lid.x < stride) {
+         worst[lid.x] = max(worst[lid.x], worst[lid.x + stride]);
+      }
+      workgroupBarrier();
+   }
+
+   if (lid.x == 0) {
+      atomicMax(&error_bits, bitcast<u32>(worst[0]));
+   }
+}
@@ -0,0 +1,350 @@
+use crate::analytic::solver_position;
+use crate::ensemble::{Ensemble, EnsembleMember};
+use crate::probes::Probe;
+use crate::rectgrid::RectGrid;
+use crate::webgpuheat::HeatComputer;
+
+/// something measured that the fitted kappa should reproduce. Times are simulated times on
+///    the computer's clock, and get rounded to the nearest step of its delta_t.
+pub enum Observation {
+   /// the whole field at `time`, the size of the computer
+   Field { time: f64, field: RectGrid },
+   /// readings at one point, as (time, T)
+   Probe { probe: Probe, readings: Vec<(f64, f32)> },
+}
+
+impl Observation {
+   // how many numbers this compares against a model
+   fn len(&self) -> usize {
+      match self {
+         Observation::Field { field, .. } => (field.width() - 2) * (field.height() - 2),
+         Observation::Probe { readings, .. } => readings.len(),
+      }
+   }
+}
+
+/// Reads a probe series in the layout probes_as_csv writes, a time column and a column per
+///    probe. Columns are matched to `probes` by name, for where each one sits.
+pub fn probe_observations(text: &str, probes: &[Probe]) -> anyhow::Result<Vec<Observation>> {
+   let mut csvrdr = csv::ReaderBuilder::new()
+      .delimiter(b',')
+      .from_reader(text.as_bytes());
+
+   let headers = csvrdr.headers()?.clone();
+   if headers.get(0).map(str::trim) != Some("time") {anyhow::bail!("a probe series starts with a time column")}
+   let mut observations: Vec<Observation> = Vec::new();
+   for name in headers.iter().skip(1) {
+      let Some(probe) = probes.iter().find(|probe| probe.name == name.trim()) else {
+         anyhow::bail!("there is no probe named {} to say where it was", name)
+      };
+      observations.push(Observation::Probe { probe: probe.clone(), readings: Vec::new() });
+   }
+
+   for (row, record) in csvrdr.records().enumerate() {
+      let record = record?;
+      let values: Option<Vec<f64>> = record.iter().map(|entry| entry.trim().parse().ok()).collect();
+      let Some(values) = values else {anyhow::bail!("could not read row {} of the probe series", row)};
+      for (observation, value) in observations.iter_mut().zip(values.iter().skip(1)) {
+         if let Observation::Probe { readings, .. } = observation {readings.push((values[0], *value as f32))}
+      }
+   }
+
+   Ok(observations)
+}
+
+/// Fits kappa to observations by Levenberg-Marquardt on log kappa, so kappa stays positive
+///    and a step means the same relative change at any size. The grid is split into
+///    columns x rows rectangles between the walls, each with its own kappa, 1x1 being a
+///    single kappa for everything. Sensitivities are forward differences, and the base run
+///    and one perturbed run per region go through an ensemble together, so each iteration
+///    is one batched forward run however many regions there are.
+///
+///    The runs start from the computer's field and clock as they are now and step with its
+///    delta_t and edges, with the ensemble's RK2 whatever the computer's integrator.
+pub struct InverseProblem {
+   pub observations: Vec<Observation>,
+   pub columns: u32,
+   pub rows: u32,
+   /// where every region starts from
+   pub initial_kappa: f32,
+   pub max_iterations: u32,
+   /// stop once a step improves the misfit by less than this fraction
+   pub tolerance: f64,
+   /// the forward difference step, in log kappa
+   pub perturbation: f64,
+}
+
+/// the fitted kappa per region, row by row like the cells of a grid
+pub struct KappaEstimate {
+   pub columns: u32,
+   pub rows: u32,
+   pub kappas: Vec<f32>,
+   /// root mean square of model minus observation, over every number compared
+   pub rms_misfit: f64,
+   /// the misfit before the first step and after every accepted one
+   pub history: Vec<f64>,
+   pub iterations: u32,
+   pub forward_runs: u32,
+}
+
+// what the forward runs gave for one set of parameters, the residuals of the base run and
+//    their derivative with respect to each parameter
+struct Linearisation {
+   residuals: Vec<f64>,
+   jacobian: Vec<Vec<f64>>,
+}
+
+impl InverseProblem {
+   pub fn new(observations: Vec<Observation>, columns: u32, rows: u32, initial_kappa: f32) -> Self {
+      Self {
+         observations, columns, rows, initial_kappa,
+         max_iterations: 30,
+         tolerance: 1e-6,
+         perturbation: 1e-2,
+      }
+   }
+
+   pub async fn solve(&self, heateq: &HeatComputer, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<KappaEstimate> {
+      if self.columns == 0 || self.rows == 0 {anyhow::bail!("kappa needs at least one region")}
+      if self.initial_kappa <= 0. {anyhow::bail!("kappa has to start out positive")}
+      if self.observations.is_empty() {anyhow::bail!("there is nothing to fit kappa to")}
+      for observation in self.observations.iter() {
+         let times: Vec<f64> = match observation {
+            Observation::Field { time, field } => {
+               if (field.width(), field.height()) != (heateq.width as usize, heateq.height as usize) {
+                  anyhow::bail!("an observed field is {}x{}, the computer is {}x{}",
+                     field.width(), field.height(), heateq.width, heateq.height)
+               }
+               vec![*time]
+            }
+            Observation::Probe { readings, .. } => readings.iter().map(|(time, _)| *time).collect(),
+         };
+         if let Some(time) = times.iter().find(|&&time| time < heateq.sim_time) {
+            anyhow::bail!("an observation at {} is before the runs start at {}", time, heateq.sim_time)
+         }
+      }
+
+      let regions = (self.columns * self.rows) as usize;
+      let mut parameters = vec![(self.initial_kappa as f64).ln(); regions];
+      let mut current = self.linearise(heateq, device, queue, &parameters).await?;
+      let mut forward_runs = 1;
+      let mut cost = sum_of_squares(&current.residuals);
+      if !cost.is_finite() {anyhow::bail!("the starting kappa does not give a finite misfit, is delta_t stable for it?")}
+      let mut history = vec![self.rms(cost)];
+      let mut lambda = 1e-3;
+      let mut iterations = 0;
+
+      while iterations < self.max_iterations {
+         iterations += 1;
+         let (normal, gradient) = normal_equations(&current);
+
+         // damp harder until a step lowers the misfit, or give up when none will
+         let mut accepted = None;
+         while lambda < 1e10 {
+            let mut damped = normal.clone();
+            let largest = (0..regions).map(|k| normal[k][k]).fold(0., f64::max);
+            for (k, row) in damped.iter_mut().enumerate() {
+               // a region nothing observed depends on has no curvature, keep it where it is
+               row[k] += lambda * (normal[k][k] + 1e-12 * largest);
+            }
+            let Some(step) = solve_dense(damped, gradient.iter().map(|g| -g).collect()) else {
+               lambda *= 10.;
+               continue
+            };
+            // no region moves by more than a factor of e at once
+            let trial: Vec<f64> = parameters.iter().zip(step.iter()).map(|(p, s)| p + s.clamp(-1., 1.)).collect();
+            let linearisation = self.linearise(heateq, device, queue, &trial).await?;
+            forward_runs += 1;
+            let trial_cost = sum_of_squares(&linearisation.residuals);
+            // a kappa too big for delta_t blows up into NaN, which fails this too
+            if trial_cost < cost {
+               accepted = Some((trial, linearisation, trial_cost));
+               lambda = (lambda / 3.).max(1e-12);
+               break
+            }
+            lambda *= 10.;
+         }
+
+         let Some((trial, linearisation, trial_cost)) = accepted else {break};
+         let improvement = (cost - trial_cost) / cost;
+         parameters = trial;
+         current = linearisation;
+         cost = trial_cost;
+         history.push(self.rms(cost));
+         if improvement < self.tolerance || cost == 0. {break}
+      }
+
+      Ok(KappaEstimate {
+         columns: self.columns,
+         rows: self.rows,
+         kappas: parameters.iter().map(|p| p.exp() as f32).collect(),
+         rms_misfit: self.rms(cost),
+         history,
+         iterations,
+         forward_runs,
+      })
+   }
+
+   fn rms(&self, cost: f64) -> f64 {
+      let count: usize = self.observations.iter().map(Observation::len).sum();
+      (cost / count.max(1) as f64).sqrt()
+   }
+
+   // the members for log kappas `parameters`: them as they are, then each nudged in turn
+   fn members(&self, heateq: &HeatComputer, parameters: &[f64]) -> Vec<EnsembleMember> {
+      let (width, height) = (heateq.width as usize, heateq.height as usize);
+      let nudged = (0..parameters.len()).map(|k| {
+         let mut nudged = parameters.to_vec();
+         nudged[k] += self.perturbation;
+         nudged
+      });
+      std::iter::once(parameters.to_vec()).chain(nudged).map(|parameters| {
+         // one region is just kappa, so it keeps to the constant kappa kernel
+         if parameters.len() == 1 {
+            return EnsembleMember { kappa: parameters[0].exp() as f32, delta_t: heateq.delta_t, initial: None, kappa_map: None }
+         }
+         let map = (0..width * height)
+            .map(|n| parameters[region_of(n % width, n / width, width, height, self.columns, self.rows)].exp() as f32)
+            .collect();
+         EnsembleMember {
+            kappa: 1.,
+            delta_t: heateq.delta_t,
+            initial: None,
+            kappa_map: Some(RectGrid::newfromdata(width, height, map)),
+         }
+      }).collect()
+   }
+
+   // runs the base and every nudged member through each observation time
+   async fn linearise(&self, heateq: &HeatComputer, device: &wgpu::Device, queue: &wgpu::Queue, parameters: &[f64]) -> anyhow::Result<Linearisation> {
+      let step_of = |time: f64| ((time - heateq.sim_time) / heateq.delta_t as f64).round() as u64;
+      let mut steps: Vec<u64> = self.observations.iter().flat_map(|observation| match observation {
+         Observation::Field { time, .. } => vec![step_of(*time)],
+         Observation::Probe { readings, .. } => readings.iter().map(|(time, _)| step_of(*time)).collect(),
+      }).collect();
+      steps.sort_unstable();
+      steps.dedup();
+
+      let members = self.members(heateq, parameters);
+      let mut ensemble = Ensemble::new(heateq, device, queue, &members)?;
+      // residuals[member][observation] with one entry per number compared, filled in as
+      //    the run passes each time
+      let mut residuals: Vec<Vec<Vec<f64>>> = vec![
+         self.observations.iter().map(|observation| vec![0.; observation.len()]).collect();
+         members.len()
+      ];
+      let mut taken = 0;
+      for step in steps {
+         while taken < step {
+            let chunk = (step - taken).min(heateq.max_steps_per_submission.max(1) as u64);
+            let mut pending_queue = Vec::new();
+            ensemble.send_steps(heateq, &mut pending_queue, device, chunk as u32);
+            queue.submit(pending_queue);
+            taken += chunk;
+         }
+         let fields = ensemble.read_all(heateq, device, queue).await?;
+         for (member, field) in fields.iter().enumerate() {
+            for (observation, residuals) in self.observations.iter().zip(residuals[member].iter_mut()) {
+               match observation {
+                  Observation::Field { time, field: observed } if step_of(*time) == step => {
+                     let (width, height) = (field.width(), field.height());
+                     for j in 1..height - 1 {
+                        for i in 1..width - 1 {
+                           residuals[(i - 1) + (j - 1) * (width - 2)] =
+                              field.getelement(i, j) as f64 - observed.getelement(i, j) as f64;
+                        }
+                     }
+                  }
+                  Observation::Probe { probe, readings } => {
+                     for (n, (time, value)) in readings.iter().enumerate() {
+                        if step_of(*time) == step {
+                           residuals[n] = field.samplebilinear(probe.x, probe.y) as f64 - *value as f64;
+                        }
+                     }
+                  }
+                  _ => {}
+               }
+            }
+         }
+      }
+
+      let mut flattened = residuals.into_iter().map(|member| member.concat());
+      let residuals = flattened.next().unwrap_or_default();
+      let jacobian = flattened
+         .map(|nudged| nudged.iter().zip(residuals.iter()).map(|(a, b)| (a - b) / self.perturbation).collect())
+         .collect();
+      Ok(Linearisation { residuals, jacobian })
+   }
+}
+
+/// which of columns x rows regions cell (i,j) of a width x height computer falls in, the
+///    edge cells going with the region beside them
+pub fn region_of(i: usize, j: usize, width: usize, height: usize, columns: u32, rows: u32) -> usize {
+   let (x, y) = solver_position(i, j, width, height);
+   let column = ((x * columns as f64).floor().max(0.) as usize).min(columns as usize - 1);
+   let row = ((y * rows as f64).floor().max(0.) as usize).min(rows as usize - 1);
+   column + row * columns as usize
+}
+
+fn sum_of_squares(residuals: &[f64]) -> f64 {
+   residuals.iter().map(|r| r * r).sum()
+}
+
+// J^T J and J^T r
+fn normal_equations(linearisation: &Linearisation) -> (Vec<Vec<f64>>, Vec<f64>) {
+   let columns = &linearisation.jacobian;
+   let normal = columns.iter()
+      .map(|a| columns.iter().map(|b| a.iter().zip(b.iter()).map(|(a, b)| a * b).sum()).collect())
+      .collect();
+   let gradient = columns.iter()
+      .map(|a| a.iter().zip(linearisation.residuals.iter()).map(|(a, r)| a * r).sum())
+      .collect();
+   (normal, gradient)
+}
+
+// gaussian elimination with partial pivoting, None if the matrix is singular
+fn solve_dense(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+   let n = b.len();
+   for k in 0..n {
+      let pivot = (k..n).max_by(|&p, &q| a[p][k].abs().total_cmp(&a[q][k].abs()))?;
+      if a[pivot][k] == 0. || !a[pivot][k].is_finite() {return None}
+      a.swap(k, pivot);
+      b.swap(k, pivot);
+      let (above, below) = a.split_at_mut(k + 1);
+      let pivot_row = &above[k];
+      for (offset, row) in below.iter_mut().enumerate() {
+         let factor = row[k] / pivot_row[k];
+         for (entry, pivot) in row[k..].iter_mut().zip(pivot_row[k..].iter()) {
+            *entry -= factor * pivot;
+         }
+         b[k + 1 + offset] -= factor * b[k];
+      }
+   }
+   let mut x = vec![0.; n];
+   for k in (0..n).rev() {
+      let tail: f64 = (k + 1..n).map(|column| a[k][column] * x[column]).sum();
+      x[k] = (b[k] - tail) / a[k][k];
+   }
+   Some(x)
+}
+
+impl KappaEstimate {
+   /// kappa at every cell of a width x height computer
+   pub fn kappa_map(&self, width: usize, height: usize) -> RectGrid {
+      RectGrid::newfromdata(width, height, (0..width * height)
+         .map(|n| self.kappas[region_of(n % width, n / width, width, height, self.columns, self.rows)])
+         .collect())
+   }
+
+   pub fn as_table(&self) -> String {
+      let mut table = format!(
+         "{} iterations, {} forward runs, rms misfit {:.4e} (from {:.4e})\n",
+         self.iterations, self.forward_runs, self.rms_misfit, self.history.first().copied().unwrap_or(self.rms_misfit)
+      );
+      for row in self.kappas.chunks(self.columns as usize) {
+         table.push_str(&row.iter().map(|kappa| format!("{:>12.5e}", kappa)).collect::<Vec<String>>().join(" "));
+         table.push('\n');
+      }
+      table
+   }
+}
@@ -0,0 +1,41 @@
+@group(0) @binding(0) var<storage, read> data: array<f32>;
+@group(0) @binding(1) var<storage, read_write> rgba_out: array<u32>;
+@group(0) @binding(2) var<uniform> width: u32;
+@group(0) @binding(3) var<uniform> height: u32;
+@group(0) @binding(4) var<uniform> pad_per_line: u32;
+@group(0) @binding(5) var<uniform> isotherms: IsothermLevels;
+
+struct IsothermLevels {
+   count: u32,
+   levels: array<vec4<f32>, 2>,
+}
+
+fn crosses(a: f32, b: f32) -> bool {
+   for (var n = 0u; n < isotherms.count; n++) {
+      let level = isotherms.levels[n / 4u][n % 4u];
+      if ((a >= level) != (b >= level)) {return true;}
+   }
+   return false;
+}
+
+// goes after heatcolor.wgsl, darkening the pixels an isotherm passes between. only the
+//    pixel on the near side of each crossing is marked, so the lines stay one pixel wide
+@compute
+@workgroup_size(8,8,1)
+fn main(
+   @builtin(global_invocation_id) gid: vec3<u32>
+) {
+   if ((gid.x >= width) | (gid.y >= height)) {return;}
+
+   let index = gid.x + gid.y * width;
+   let here = data[index];
+
+   var on_line = false;
+   if (gid.x + 1u < width) {on_line = on_line | crosses(here, data[index + 1u]);}
+   if (gid.y + 1u < height) {on_line = on_line | crosses(here, data[index + width]);}
+
+   if (on_line) {
+      // opaque black, in the same ABGR the heatmap uses
+      rgba_out[gid.x + gid.y * (pad_per_line + width)] = 0xFF000000u;
+   }
+}
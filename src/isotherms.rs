@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use bytemuck::cast_slice;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::rectgrid::RectGrid;
+use crate::webgpuheat::{HeatComputer, helper_basic_compute_shader, helper_compute_bind_group};
+
+/// the overlay shader has room for this many levels
+pub const MAX_OVERLAY_LEVELS: usize = 8;
+
+/// Every polyline where the field crosses `level`, in the same [0,1]x[0,1] coordinates as
+///    RectGrid. A line that closes on itself repeats its first point at the end, one that
+///    does not runs from edge to edge of the grid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Isotherm {
+   pub level: f32,
+   pub lines: Vec<Vec<(f32, f32)>>,
+}
+
+impl Isotherm {
+   /// total length of all the lines, handy for following a front over time
+   pub fn length(&self) -> f32 {
+      self.lines.iter().map(|line| line.windows(2).map(|pair| {
+         ((pair[1].0 - pair[0].0).powi(2) + (pair[1].1 - pair[0].1).powi(2)).sqrt()
+      }).sum::<f32>()).sum()
+   }
+}
+
+// a crossing lives on a cell side, either the one from (i,j) to (i+1,j) or to (i,j+1).
+//    neighbouring cells share sides, which is how segments get joined into lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Side {
+   Along(usize, usize),
+   Up(usize, usize),
+}
+
+// which sides of a cell the segments join, by which corners are at or above the level.
+//    corners are bit 0 (i,j), bit 1 (i+1,j), bit 2 (i+1,j+1), bit 3 (i,j+1), and sides
+//    are 0 bottom, 1 right, 2 top, 3 left. the two saddles are settled by the centre.
+fn cell_segments(case: usize, centre_above: bool) -> &'static [(usize, usize)] {
+   match case {
+      0 | 15 => &[],
+      1 | 14 => &[(3, 0)],
+      2 | 13 => &[(0, 1)],
+      3 | 12 => &[(3, 1)],
+      4 | 11 => &[(1, 2)],
+      6 | 9 => &[(0, 2)],
+      7 | 8 => &[(3, 2)],
+      5 => if centre_above {&[(3, 2), (0, 1)]} else {&[(3, 0), (1, 2)]},
+      10 => if centre_above {&[(3, 0), (1, 2)]} else {&[(3, 2), (0, 1)]},
+      _ => unreachable!("four corners make 16 cases")
+   }
+}
+
+/// marching squares over the grid for each level, with the segments joined up into
+///    polylines
+pub fn extract_isotherms(grid: &RectGrid, levels: &[f32]) -> Vec<Isotherm> {
+   levels.iter().map(|&level| Isotherm { level, lines: isotherm_lines(grid, level) }).collect()
+}
+
+fn isotherm_lines(grid: &RectGrid, level: f32) -> Vec<Vec<(f32, f32)>> {
+   let (width, height) = (grid.width(), grid.height());
+   if width < 2 || height < 2 {return vec![]}
+   let x_step = 1. / (width - 1) as f32;
+   let y_step = 1. / (height - 1) as f32;
+
+   // where along a side the field reaches the level
+   let crossing = |side: Side| -> (f32, f32) {
+      let ((i0, j0), (i1, j1)) = match side {
+         Side::Along(i, j) => ((i, j), (i + 1, j)),
+         Side::Up(i, j) => ((i, j), (i, j + 1)),
+      };
+      let (a, b) = (grid.getelement(i0, j0), grid.getelement(i1, j1));
+      let t = (level - a) / (b - a);
+      ((i0 as f32 + t * (i1 - i0) as f32) * x_step, (j0 as f32 + t * (j1 - j0) as f32) * y_step)
+   };
+
+   let mut segments: Vec<(Side, Side)> = Vec::new();
+   for j in 0..height - 1 {
+      for i in 0..width - 1 {
+         let corners = [
+            grid.getelement(i, j), grid.getelement(i + 1, j),
+            grid.getelement(i + 1, j + 1), grid.getelement(i, j + 1),
+         ];
+         let case = corners.iter().enumerate()
+            .fold(0, |case, (bit, &value)| if value >= level {case | 1 << bit} else {case});
+         let centre_above = corners.iter().sum::<f32>() / 4. >= level;
+         let sides = [Side::Along(i, j), Side::Up(i + 1, j), Side::Along(i, j + 1), Side::Up(i, j)];
+         for &(from, to) in cell_segments(case, centre_above) {
+            segments.push((sides[from], sides[to]));
+         }
+      }
+   }
+
+   // every side is on at most two segments, one from each cell it borders
+   let mut touching: HashMap<Side, Vec<usize>> = HashMap::new();
+   for (n, (from, to)) in segments.iter().enumerate() {
+      touching.entry(*from).or_default().push(n);
+      touching.entry(*to).or_default().push(n);
+   }
+
+   let mut used = vec![false; segments.len()];
+   let mut lines = Vec::new();
+   // lines ending on the grid edge first, so they are walked from one end, then the loops
+   let open_ends: Vec<usize> = touching.values().filter(|on| on.len() == 1).map(|on| on[0]).collect();
+   for start in open_ends.into_iter().chain(0..segments.len()) {
+      if used[start] {continue}
+      let (from, to) = segments[start];
+      let first = if touching[&from].len() == 1 {from} else if touching[&to].len() == 1 {to} else {from};
+
+      let mut sides = vec![first];
+      let mut current = start;
+      let mut at = first;
+      loop {
+         used[current] = true;
+         let (from, to) = segments[current];
+         at = if from == at {to} else {from};
+         sides.push(at);
+         match touching[&at].iter().find(|&&n| !used[n]) {
+            Some(&next) => current = next,
+            None => break
+         }
+      }
+      lines.push(sides.into_iter().map(crossing).collect());
+   }
+
+   lines
+}
+
+/// one row per point, with the time the field was at so exports from several times can be
+///    put together to follow a front
+pub fn isotherms_as_csv(isotherms: &[Isotherm], sim_time: f64) -> anyhow::Result<String> {
+   let mut thewriter = csv::WriterBuilder::new()
+      .delimiter(b',')
+      .from_writer(vec![]);
+
+   thewriter.write_record(["time", "level", "line", "x", "y"])?;
+   for isotherm in isotherms.iter() {
+      for (n, line) in isotherm.lines.iter().enumerate() {
+         for (x, y) in line.iter() {
+            thewriter.write_record([
+               format!("{}", sim_time), format!("{}", isotherm.level), format!("{}", n),
+               format!("{}", x), format!("{}", y)
+            ])?;
+         }
+      }
+   }
+
+   Ok(String::from_utf8(thewriter.into_inner()?)?)
+}
+
+/// an svg the size of the grid in pixels, one path per level, so it lies exactly over a
+///    heatmap png of the same field (row 0 at the top, like the heatmap)
+pub fn isotherms_as_svg(isotherms: &[Isotherm], width: usize, height: usize) -> String {
+   let x_scale = (width - 1) as f32;
+   let y_scale = (height - 1) as f32;
+   let mut svg = format!(
+      "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+      w = width, h = height
+   );
+   for isotherm in isotherms.iter() {
+      let path: Vec<String> = isotherm.lines.iter().map(|line| {
+         line.iter().enumerate().map(|(n, (x, y))| {
+            // + 0.5 since pixel i covers i to i+1 and the cell value sits in its middle
+            format!("{}{:.3},{:.3}", if n == 0 {"M"} else {"L"}, x * x_scale + 0.5, y * y_scale + 0.5)
+         }).collect::<Vec<String>>().join(" ")
+      }).collect();
+      svg.push_str(&format!(
+         "<path data-level=\"{}\" d=\"{}\" fill=\"none\" stroke=\"black\" stroke-width=\"0.5\"/>\n",
+         isotherm.level, path.join(" ")
+      ));
+   }
+   svg.push_str("</svg>\n");
+   svg
+}
+
+/// Draws the isotherms of the temperature over the heatmap, whichever field it shows, by
+///    darkening every pixel where the temperature crosses a level on its way to the next
+///    pixel right or up. Runs after heat hue so nothing leaves the gpu.
+pub struct IsothermOverlay {
+   pub levels: Vec<f32>,
+   pub overlay_pipeline: wgpu::ComputePipeline,
+   pub level_buffer: wgpu::Buffer,
+   pub overlay_bind_group: wgpu::BindGroup,
+}
+
+impl IsothermOverlay {
+   pub fn new(heateq: &HeatComputer, device: &wgpu::Device, levels: Vec<f32>) -> anyhow::Result<Self> {
+      if levels.is_empty() || levels.len() > MAX_OVERLAY_LEVELS {
+         anyhow::bail!("the overlay takes between 1 and {} levels, got {}", MAX_OVERLAY_LEVELS, levels.len())
+      }
+
+      let overlay_shader = device.create_shader_module(wgpu::include_wgsl!("isotherm_overlay.wgsl"));
+      let overlay_pipeline = helper_basic_compute_shader(device, Some("Isotherm Overlay Pipeline"), &overlay_shader);
+
+      let level_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("isotherm levels"),
+         contents: &Self::level_uniform(&levels),
+         usage: wgpu::BufferUsages::UNIFORM,
+      });
+      let overlay_bind_group = helper_compute_bind_group(
+         device, None, &overlay_pipeline,
+         &[&heateq.data_buffer, &heateq.heat_map_buffer, &heateq.width_buffer, &heateq.height_buffer,
+            &heateq.pad_buffer, &level_buffer]
+      );
+
+      Ok(Self {
+         levels,
+         overlay_pipeline,
+         level_buffer,
+         overlay_bind_group,
+      })
+   }
+
+   // the count padded out to 16 bytes, then the levels
+   fn level_uniform(levels: &[f32]) -> Vec<u8> {
+      let mut padded = [0f32; MAX_OVERLAY_LEVELS];
+      padded[..levels.len()].copy_from_slice(levels);
+      let mut bytes: Vec<u8> = Vec::new();
+      bytes.extend_from_slice(cast_slice(&[levels.len() as u32, 0, 0, 0]));
+      bytes.extend_from_slice(cast_slice(&padded));
+      bytes
+   }
+
+   pub fn record(&self, heateq: &HeatComputer, gputodo: &mut wgpu::ComputePass) {
+      gputodo.set_pipeline(&self.overlay_pipeline);
+      gputodo.set_bind_group(0, &self.overlay_bind_group, &[]);
+      gputodo.dispatch_workgroups(heateq.width.div_ceil(8), heateq.height.div_ceil(8), 1);
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   // lines as sets of rounded points, so neither their order nor direction matters
+   fn endpoints(lines: &[Vec<(f32, f32)>]) -> Vec<[(i32, i32); 2]> {
+      let round = |(x, y): (f32, f32)| ((x * 1000.).round() as i32, (y * 1000.).round() as i32);
+      let mut ends: Vec<[(i32, i32); 2]> = lines.iter().map(|line| {
+         let mut ends = [round(line[0]), round(*line.last().unwrap())];
+         ends.sort();
+         ends
+      }).collect();
+      ends.sort();
+      ends
+   }
+
+   #[test]
+   fn single_contour() {
+      // a hot centre cell, so one closed diamond through the middles of its four sides
+      let grid = RectGrid::newfromdata(3, 3, vec![0., 0., 0., 0., 1., 0., 0., 0., 0.]);
+      let lines = isotherm_lines(&grid, 0.5);
+      assert_eq!(lines.len(), 1);
+      let line = &lines[0];
+      assert_eq!(line.len(), 5);
+      assert_eq!(line.first(), line.last());
+      for &(x, y) in line.iter() {
+         assert!(((x - 0.5).abs() + (y - 0.5).abs() - 0.25).abs() < 1e-6, "({}, {}) is off the diamond", x, y);
+      }
+      let isotherms = extract_isotherms(&grid, &[0.5, 2.]);
+      assert!((isotherms[0].length() - 2f32.sqrt()).abs() < 1e-6);
+      assert!(isotherms[1].lines.is_empty());
+   }
+
+   #[test]
+   fn single_saddle() {
+      // hot (0,0) and (1,1), cold (1,0) and (0,1), with a centre of exactly 0.5
+      let grid = RectGrid::newfromdata(2, 2, vec![1., 0., 0., 1.]);
+      // at or below the centre the hot corners join up and the lines cut off the cold ones
+      assert_eq!(endpoints(&isotherm_lines(&grid, 0.5)), vec![[(0, 500), (500, 1000)], [(500, 0), (1000, 500)]]);
+      // above it the cold corners join up and the lines cut off the hot ones
+      let lines = isotherm_lines(&grid, 0.6);
+      assert_eq!(lines.len(), 2);
+      assert_eq!(endpoints(&lines), vec![[(0, 400), (400, 0)], [(600, 1000), (1000, 600)]]);
+   }
+}
@@ -6,6 +6,9 @@
 @group(0) @binding(5) var<uniform> kappa: f32;
 @group(0) @binding(6) var<uniform> delta_t: f32;
 
+// invocations in a row of workgroups, see linear_workgroups in webgpuheat.rs
+const ROW_LENGTH: u32 = 4194240u;
+
 @compute// Entrypoint
 @workgroup_size(64,1,1)
 fn main(
@@ -20,7 +23,8 @@ fn main(
    // valuable reference: https://www.w3.org/TR/WGSL/#arithmetic-expr
 
    // // exit if on boundary. might be inefficient but easier to call too many workers
-   if (gid.x > (width * height)) {return;}
+   let cell = gid.x + gid.y * ROW_LENGTH;
+   if (cell >= (width * height)) {return;}
 
-   output[gid.x] = data[gid.x] + delta_t * kappa * laplacian[gid.x];
+   output[cell] = data[cell] + delta_t * kappa * laplacian[cell];
 }
@@ -2,6 +2,8 @@
 @group(0) @binding(1) var<storage, read_write> laplacian: array<f32>;
 @group(0) @binding(2) var<uniform> width: u32;
 @group(0) @binding(3) var<uniform> height: u32;
+// height of the whole grid, which is only different from height when we are one tile of many
+@group(0) @binding(4) var<uniform> grid_height: u32;
 
 @compute// Entrypoint
 @workgroup_size(8,8,1)
@@ -30,7 +32,7 @@ fn main(
    // }
 
    let widthfloatmin1 = f32(width);
-   let heightfloatmin1 = f32(height);
+   let heightfloatmin1 = f32(grid_height);
    let delta_x_sq = 1.0f / widthfloatmin1 / widthfloatmin1;
    let delta_y_sq = 1.0f / heightfloatmin1 / heightfloatmin1;
 
@@ -3,6 +3,30 @@ mod rectgrid;
 mod aspng;
 mod webgpuheat;
 mod wgpuworkhorse;
+mod supertimestep;
+mod tridiagonal;
+mod adi;
+mod adaptive;
+mod reduction;
+mod colorrange;
+mod probes;
+mod flux;
+mod boundary;
+mod isotherms;
+mod accumulators;
+mod analytic;
+mod convergence;
+mod ensemble;
+mod inverse;
+mod montecarlo;
+mod checkpoint;
+mod runcontrol;
+mod events;
+mod npy;
+mod vtk;
+mod tiledheat;
+#[cfg(not(target_arch = "wasm32"))]
+mod sweep;
 
 #[cfg(target_arch = "wasm32")]
 mod web_app;
@@ -42,8 +66,6 @@ use wasm_bindgen::prelude::*;
 #[cfg(target_arch = "wasm32")]
 use console_log::*;
 #[cfg(target_arch = "wasm32")]
-use wasm_bindgen::JsCast;
-#[cfg(target_arch = "wasm32")]
 use winit::platform::web::WindowAttributesExtWebSys;
 
 
@@ -80,6 +102,34 @@ pub fn run_desktop() -> Option<()> {
     Some(())
 }
 
+/// runs a parameter sweep with no window, for build servers. Takes the key=value words of
+///    SweepSpec::from_args plus out=<path> for the csv, which otherwise goes to stdout.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run_sweep(args: &[String]) -> anyhow::Result<()> {
+    let (outs, rest): (Vec<&str>, Vec<&str>) = args.iter().map(|x| x.as_str()).partition(|x| x.starts_with("out="));
+    let spec = crate::sweep::SweepSpec::from_args(&rest).map_err(anyhow::Error::msg)?;
+    let points = spec.points().len();
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    let (device, queue) = runtime.block_on(wgpuworkhorse::headless_device())?;
+    let on_point = |done: usize, total: usize, error: Option<&anyhow::Error>| match error {
+        Some(e) => eprintln!("sweep: run {} of {} failed: {}", done, total, e),
+        None => eprintln!("sweep: {} of {} runs done", done, total),
+    };
+
+    eprintln!("sweep: {} runs", points);
+    match outs.last() {
+        Some(out) => {
+            let path = &out["out=".len()..];
+            let file = std::fs::File::create(path)?;
+            runtime.block_on(spec.run(&device, &queue, file, on_point))?;
+            eprintln!("sweep: wrote {}", path);
+        }
+        None => runtime.block_on(spec.run(&device, &queue, std::io::stdout(), on_point))?,
+    }
+    Ok(())
+}
+
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen(start)]
 pub async fn run_web() -> Result<(), wasm_bindgen::JsValue> {
@@ -90,20 +140,7 @@ pub async fn run_web() -> Result<(), wasm_bindgen::JsValue> {
     let (sender, receiver) = tokio::sync::oneshot::channel::<Result<(), wgpu::BufferAsyncError>>();
     _ = web_app::INTERNAL_MESSAGE.replace(Some(receiver));
 
-    let window = wgpu::web_sys::window().unwrap_throw();
-    let document = window.document().unwrap_throw();
-    //let canvas = body.get_element_by_id(CANVAS_ID).unwrap_throw();
-    let canvas: web_sys::Element = document
-       .query_selector("canvas")
-       .expect("could not find canvas")
-       .expect("canvas query returned empty");
-    let html_canvas_element: HtmlCanvasElement = canvas
-       .dyn_into()
-       .expect("man your canvas is bonked or somethin");
-
-    let mut webstate = wgpuworkhorse::WgpuState::new(html_canvas_element)
-        .await
-        .map_err(|e| JsValue::from_str(&format!("error {}",e)))?;
+    let mut webstate = web_app::state_on_canvas(256, 256).await?;
 
     webstate.render().map_err(|e| JsValue::from_str(&format!("error {}",e)));
 
@@ -1,5 +1,20 @@
 use pet_webgpusolver::run_desktop;
+#[cfg(not(target_arch = "wasm32"))]
+use pet_webgpusolver::run_sweep;
 
 fn main() {
+   // `sweep key=value..` runs headless and exits, anything else opens the window
+   #[cfg(not(target_arch = "wasm32"))]
+   {
+      let args: Vec<String> = std::env::args().collect();
+      if args.get(1).map(|x| x.as_str()) == Some("sweep") {
+         if let Err(e) = run_sweep(&args[2..]) {
+            eprintln!("sweep failed: {}", e);
+            std::process::exit(1);
+         }
+         return
+      }
+   }
+
    run_desktop().unwrap();
 }
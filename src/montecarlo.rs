@@ -0,0 +1,274 @@
+use crate::analytic::solver_position;
+use crate::ensemble::{Ensemble, EnsembleMember};
+use crate::rectgrid::RectGrid;
+use crate::supertimestep::explicit_stable_delta_t;
+use crate::webgpuheat::HeatComputer;
+
+/// xoshiro256** seeded through splitmix64, small and the same on every platform, so a seed
+///    gives the same samples on the desktop and in the browser
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+   state: [u64; 4],
+}
+
+impl SeededRng {
+   pub fn new(seed: u64) -> Self {
+      let mut mix = seed;
+      let mut next = || {
+         mix = mix.wrapping_add(0x9e3779b97f4a7c15);
+         let mut z = mix;
+         z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+         z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+         z ^ (z >> 31)
+      };
+      Self { state: [next(), next(), next(), next()] }
+   }
+
+   pub fn next_u64(&mut self) -> u64 {
+      let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+      let shifted = self.state[1] << 17;
+      self.state[2] ^= self.state[0];
+      self.state[3] ^= self.state[1];
+      self.state[1] ^= self.state[2];
+      self.state[0] ^= self.state[3];
+      self.state[2] ^= shifted;
+      self.state[3] = self.state[3].rotate_left(45);
+      result
+   }
+
+   /// uniform on [0,1)
+   pub fn uniform(&mut self) -> f64 {
+      (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+   }
+
+   /// standard normal, by Box-Muller
+   pub fn normal(&mut self) -> f64 {
+      let u = 1. - self.uniform();
+      let v = self.uniform();
+      (-2. * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+   }
+}
+
+/// A stationary gaussian random field, zero mean, `std_dev` at every cell and correlated as
+///    exp(-r^2 / 2 length^2), with the length in the same units as positions between the
+///    walls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RandomField {
+   pub std_dev: f64,
+   pub correlation_length: f64,
+}
+
+impl std::str::FromStr for RandomField {
+   type Err = String;
+
+   /// <std_dev>:<correlation length>
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      let (std_dev, correlation_length) = s.split_once(':').ok_or(format!("{} should be <std_dev>:<correlation length>", s))?;
+      let std_dev: f64 = std_dev.parse().map_err(|_| format!("{} is not a number", std_dev))?;
+      let correlation_length: f64 = correlation_length.parse().map_err(|_| format!("{} is not a number", correlation_length))?;
+      if std_dev < 0. || correlation_length < 0. {return Err("a random field needs a std_dev and length of at least 0".to_string())}
+      Ok(Self { std_dev, correlation_length })
+   }
+}
+
+impl RandomField {
+   /// One sample on a width x height solver grid. White noise is blurred by a gaussian
+   ///    length/sqrt(2) wide, which correlates it as wanted, then scaled by the blur's own
+   ///    variance. The noise runs past the grid by the blur's reach so the edges are as
+   ///    random as the middle.
+   pub fn sample(&self, width: usize, height: usize, rng: &mut SeededRng) -> RectGrid {
+      // the blur in cells, which differ across x and y as the walls sit at different spacings
+      let kernel = |cells: usize| -> Vec<f64> {
+         let sigma = self.correlation_length / std::f64::consts::SQRT_2 * (cells - 2) as f64;
+         if sigma < 1e-3 {return vec![1.]}
+         let reach = (3. * sigma).ceil() as i64;
+         (-reach..=reach).map(|n| (-(n * n) as f64 / (2. * sigma * sigma)).exp()).collect()
+      };
+      let (kernel_x, kernel_y) = (kernel(width), kernel(height));
+      let (reach_x, reach_y) = (kernel_x.len() / 2, kernel_y.len() / 2);
+      let (noise_width, noise_height) = (width + 2 * reach_x, height + 2 * reach_y);
+      let noise: Vec<f64> = (0..noise_width * noise_height).map(|_| rng.normal()).collect();
+
+      // separable, rows then columns
+      let mut along_x = vec![0.; width * noise_height];
+      for j in 0..noise_height {
+         for i in 0..width {
+            along_x[i + j * width] = kernel_x.iter().enumerate()
+               .map(|(k, weight)| weight * noise[i + k + j * noise_width]).sum();
+         }
+      }
+      let norm = (kernel_x.iter().map(|k| k * k).sum::<f64>() * kernel_y.iter().map(|k| k * k).sum::<f64>()).sqrt();
+      let data = (0..width * height).map(|n| {
+         let (i, j) = (n % width, n / width);
+         let blurred: f64 = kernel_y.iter().enumerate().map(|(k, weight)| weight * along_x[i + (j + k) * width]).sum();
+         (self.std_dev * blurred / norm) as f32
+      }).collect();
+      RectGrid::newfromdata(width, height, data)
+   }
+}
+
+/// Propagates uncertain material and starting fields through the solver by sampling them.
+///    Each sample multiplies kappa by exp(g - std_dev^2/2) for a random field g, which keeps
+///    kappa positive with the computer's kappa as its mean, and adds another random field to
+///    the starting temperatures. Samples go through ensembles `batch` at a time from the
+///    computer's field, clock, delta_t and edges, and sample n always draws from the seed
+///    mixed with n, so the results do not depend on how they were batched. A sample whose
+///    largest kappa is too stiff for RK2 at the computer's delta_t stops the study.
+pub struct MonteCarloStudy {
+   pub samples: u32,
+   pub seed: u64,
+   pub kappa_field: Option<RandomField>,
+   pub initial_field: Option<RandomField>,
+   /// when to take the statistics, rounded to the nearest step
+   pub times: Vec<f64>,
+   /// at most this many samples per ensemble, 0 for as many as fit
+   pub batch: u32,
+}
+
+/// the statistics of every sample at one time
+pub struct MonteCarloMaps {
+   pub time: f64,
+   pub mean: RectGrid,
+   /// the sample standard deviation, n-1 in the denominator
+   pub std_dev: RectGrid,
+}
+
+pub struct MonteCarloReport {
+   pub samples: u32,
+   pub maps: Vec<MonteCarloMaps>,
+}
+
+impl MonteCarloStudy {
+   pub async fn run(&self, heateq: &HeatComputer, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<MonteCarloReport> {
+      if self.samples < 2 {anyhow::bail!("a standard deviation needs at least two samples")}
+      if self.times.is_empty() {anyhow::bail!("there are no times to take statistics at")}
+      if let Some(time) = self.times.iter().find(|&&time| time < heateq.sim_time) {
+         anyhow::bail!("{} is before the samples start at {}", time, heateq.sim_time)
+      }
+
+      let (width, height) = (heateq.width as usize, heateq.height as usize);
+      let cells = width * height;
+      let limits = device.limits();
+      let fit = (HeatComputer::max_cells(&limits) / cells as u64)
+         .min(limits.max_compute_workgroups_per_dimension as u64)
+         .min(self.samples as u64) as u32;
+      let batch = if self.batch == 0 {fit} else {self.batch.min(fit)};
+      if batch == 0 {anyhow::bail!("not even one sample fits on this device")}
+
+      let step_of = |time: f64| ((time - heateq.sim_time) / heateq.delta_t as f64).round() as u64;
+      let mut order: Vec<usize> = (0..self.times.len()).collect();
+      order.sort_by_key(|&n| step_of(self.times[n]));
+      let start = match self.initial_field {
+         Some(_) => Some(heateq.read_field(device, queue).await?),
+         None => None
+      };
+
+      // welford's running mean and sum of squared deviations, per time and cell
+      let mut means = vec![vec![0f64; cells]; self.times.len()];
+      let mut deviations = vec![vec![0f64; cells]; self.times.len()];
+      let mut counted = 0f64;
+
+      let mut first = 0;
+      while first < self.samples {
+         let last = (first + batch).min(self.samples);
+         let members = (first..last).map(|n| {
+            let mut rng = SeededRng::new(self.seed ^ (n as u64).wrapping_mul(0xd1b54a32d192ed03));
+            let kappa_map = self.kappa_field.map(|field| {
+               let g = field.sample(width, height, &mut rng);
+               let bias = field.std_dev * field.std_dev / 2.;
+               g.newbytemplate(g.getarray().iter().map(|&g| (g as f64 - bias).exp() as f32).collect())
+            });
+            // the ensemble steps with RK2, which goes to NaN past the explicit limit of its stiffest cell
+            let largest = kappa_map.as_ref().map_or(1., |kappa_map| kappa_map.getarray().iter().copied().fold(0f32, f32::max));
+            let limit = explicit_stable_delta_t(heateq.kappa * largest, heateq.width, heateq.height);
+            if heateq.delta_t as f64 > limit {
+               anyhow::bail!("sample {} has kappa up to {:.2} times the mean, which needs delta_t at most {:.3e} to stay stable, it is {:.3e}",
+                  n, largest, limit, heateq.delta_t)
+            }
+            let initial = match (self.initial_field, &start) {
+               (Some(field), Some(start)) => {
+                  let g = field.sample(width, height, &mut rng);
+                  Some(start.newbytemplate(start.getarray().iter().zip(g.getarray()).map(|(t, g)| t + g).collect()))
+               }
+               _ => None
+            };
+            Ok(EnsembleMember { kappa: heateq.kappa, delta_t: heateq.delta_t, initial, kappa_map })
+         }).collect::<anyhow::Result<Vec<EnsembleMember>>>()?;
+
+         let mut ensemble = Ensemble::new(heateq, device, queue, &members)?;
+         let mut taken = 0;
+         for &n in order.iter() {
+            let step = step_of(self.times[n]);
+            while taken < step {
+               let chunk = (step - taken).min(heateq.max_steps_per_submission.max(1) as u64);
+               let mut pending_queue = Vec::new();
+               ensemble.send_steps(heateq, &mut pending_queue, device, chunk as u32);
+               queue.submit(pending_queue);
+               taken += chunk;
+            }
+            let fields = ensemble.read_all(heateq, device, queue).await?;
+            for (k, field) in fields.iter().enumerate() {
+               let count = counted + k as f64 + 1.;
+               for ((mean, deviation), &value) in means[n].iter_mut().zip(deviations[n].iter_mut()).zip(field.getarray()) {
+                  let delta = value as f64 - *mean;
+                  *mean += delta / count;
+                  *deviation += delta * (value as f64 - *mean);
+               }
+            }
+         }
+         counted += (last - first) as f64;
+         first = last;
+      }
+
+      let maps = self.times.iter().zip(means.iter().zip(deviations.iter())).map(|(&time, (mean, deviation))| MonteCarloMaps {
+         time,
+         mean: RectGrid::newfromdata(width, height, mean.iter().map(|&m| m as f32).collect()),
+         std_dev: RectGrid::newfromdata(width, height, deviation.iter().map(|&d| (d / (counted - 1.)).sqrt() as f32).collect()),
+      }).collect();
+      Ok(MonteCarloReport { samples: self.samples, maps })
+   }
+}
+
+impl MonteCarloMaps {
+   /// mean - z std_dev and mean + z std_dev, so z = 1.96 brackets 95% of samples if they
+   ///    come out roughly normal
+   pub fn band(&self, z: f32) -> (RectGrid, RectGrid) {
+      let bound = |sign: f32| self.mean.newbytemplate(self.mean.getarray().iter().zip(self.std_dev.getarray())
+         .map(|(mean, std_dev)| mean + sign * z * std_dev)
+         .collect());
+      (bound(-1.), bound(1.))
+   }
+
+   /// the largest standard deviation between the walls and where it is
+   pub fn widest(&self) -> (f32, f64, f64) {
+      let (width, height) = (self.std_dev.width(), self.std_dev.height());
+      let mut widest = (0f32, 0., 0.);
+      for j in 1..height - 1 {
+         for i in 1..width - 1 {
+            let std_dev = self.std_dev.getelement(i, j);
+            if std_dev > widest.0 {
+               let (x, y) = solver_position(i, j, width, height);
+               widest = (std_dev, x, y);
+            }
+         }
+      }
+      widest
+   }
+}
+
+impl MonteCarloReport {
+   pub fn as_table(&self) -> String {
+      let mut table = format!("{} samples\n{:>12} {:>12} {:>12} {:>16}\n", self.samples, "time", "mean T", "widest std", "at");
+      for maps in self.maps.iter() {
+         let (width, height) = (maps.mean.width(), maps.mean.height());
+         let interior: Vec<f32> = (width..width * (height - 1))
+            .filter(|n| n % width != 0 && n % width != width - 1)
+            .map(|n| maps.mean.getarray()[n])
+            .collect();
+         let mean = interior.iter().map(|&t| t as f64).sum::<f64>() / interior.len().max(1) as f64;
+         let (widest, x, y) = maps.widest();
+         table.push_str(&format!("{:>12.5e} {:>12.5e} {:>12.5e} {:>16}\n", maps.time, mean, widest, format!("({:.3}, {:.3})", x, y)));
+      }
+      table
+   }
+}
@@ -0,0 +1,346 @@
+use crate::rectgrid::RectGrid;
+
+const MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+/// the two float types we write, both little endian
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NpyDtype {
+   F32,
+   F64,
+}
+
+impl std::str::FromStr for NpyDtype {
+   type Err = String;
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      match s {
+         "f32" | "float32" => Ok(NpyDtype::F32),
+         "f64" | "float64" => Ok(NpyDtype::F64),
+         other => Err(format!("unknown dtype {}, expected f32 or f64", other))
+      }
+   }
+}
+
+/// A grid as a .npy file, shape (height, width) in C order so that array[j, i] is
+///    getelement(i, j), the same rows writecsv writes.
+pub fn write_npy(grid: &RectGrid, dtype: NpyDtype) -> Vec<u8> {
+   let descr = match dtype {
+      NpyDtype::F32 => "<f4",
+      NpyDtype::F64 => "<f8",
+   };
+   let mut header = format!("{{'descr': '{}', 'fortran_order': False, 'shape': ({}, {}), }}", descr, grid.height(), grid.width());
+   // numpy pads the header with spaces so the data starts on a multiple of 64
+   let (version, prefix): (u8, usize) = if header.len() + 11 < 65536 {(1, 10)} else {(2, 12)};
+   header.push_str(&" ".repeat(63 - (prefix + header.len()) % 64));
+   header.push('\n');
+
+   let mut bytes = Vec::with_capacity(prefix + header.len() + grid.getarray().len() * 8);
+   bytes.extend_from_slice(MAGIC);
+   bytes.extend_from_slice(&[version, 0]);
+   match version {
+      1 => bytes.extend_from_slice(&(header.len() as u16).to_le_bytes()),
+      _ => bytes.extend_from_slice(&(header.len() as u32).to_le_bytes()),
+   }
+   bytes.extend_from_slice(header.as_bytes());
+   for value in grid.getarray() {
+      match dtype {
+         NpyDtype::F32 => bytes.extend_from_slice(&value.to_le_bytes()),
+         NpyDtype::F64 => bytes.extend_from_slice(&(*value as f64).to_le_bytes()),
+      }
+   }
+   bytes
+}
+
+/// Reads a two dimensional float32 or float64 .npy of either byte order and either memory
+///    order back into a grid, as write_npy lays it out.
+pub fn read_npy(bytes: &[u8]) -> anyhow::Result<RectGrid> {
+   if bytes.len() < 10 || bytes[..6] != *MAGIC {anyhow::bail!("this is not a .npy file")}
+   let (header_length, data_start) = match bytes[6] {
+      1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+      2 | 3 if bytes.len() >= 12 => (u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize, 12),
+      version => anyhow::bail!(".npy version {} is not one we read", version)
+   };
+   let Some(header) = bytes.get(data_start..data_start + header_length) else {anyhow::bail!("the .npy header runs past the end")};
+   let header = std::str::from_utf8(header)?;
+   let data = &bytes[data_start + header_length..];
+
+   let descr = header_value(header, "descr")?.trim_matches(|c| c == '\'' || c == '"');
+   let (little_endian, size) = match descr {
+      "<f4" | "|f4" => (true, 4),
+      "<f8" | "|f8" => (true, 8),
+      ">f4" => (false, 4),
+      ">f8" => (false, 8),
+      other => anyhow::bail!("dtype {} is not float32 or float64", other)
+   };
+   let fortran_order = match header_value(header, "fortran_order")? {
+      "True" => true,
+      "False" => false,
+      other => anyhow::bail!("fortran_order {} is neither True nor False", other)
+   };
+   let shape = header_value(header, "shape")?
+      .trim_matches(|c| c == '(' || c == ')')
+      .split(',')
+      .map(str::trim)
+      .filter(|dimension| !dimension.is_empty())
+      .map(|dimension| dimension.parse::<usize>().map_err(|_| anyhow::anyhow!("{} is not a dimension", dimension)))
+      .collect::<anyhow::Result<Vec<usize>>>()?;
+   let [height, width] = shape[..] else {anyhow::bail!("a field has two dimensions, this has {}", shape.len())};
+
+   let Some(needed) = width.checked_mul(height).and_then(|cells| cells.checked_mul(size)) else {
+      anyhow::bail!("a {}x{} array is too big to read", height, width)
+   };
+   if data.len() != needed {
+      anyhow::bail!("a {}x{} array needs {} bytes of data, there are {}", height, width, needed, data.len())
+   }
+   let values: Vec<f32> = data.chunks_exact(size).map(|word| match (size, little_endian) {
+      (4, true) => f32::from_le_bytes(word.try_into().unwrap()),
+      (4, false) => f32::from_be_bytes(word.try_into().unwrap()),
+      (_, true) => f64::from_le_bytes(word.try_into().unwrap()) as f32,
+      (_, false) => f64::from_be_bytes(word.try_into().unwrap()) as f32,
+   }).collect();
+   let values = match fortran_order {
+      false => values,
+      // stored column by column, so element (j, i) sits at j + i * height
+      true => (0..width * height).map(|n| values[n / width + (n % width) * height]).collect()
+   };
+   Ok(RectGrid::newfromdata(width, height, values))
+}
+
+/// the (width, height) of the field in a .npy, or of the one HeatComputer::load_numpy would
+///    take from a .npz
+#[cfg(target_arch = "wasm32")]
+pub fn field_shape(bytes: &[u8]) -> anyhow::Result<(usize, usize)> {
+   let grid = match bytes.starts_with(b"PK") {
+      false => read_npy(bytes)?,
+      true => {
+         let mut fields = read_npz(bytes)?;
+         match fields.iter().position(|(name, _)| name == "temperature") {
+            Some(n) => fields.remove(n).1,
+            None => match fields.iter().position(|(name, _)| name != "kappa" && name != "mask") {
+               Some(n) => fields.remove(n).1,
+               None => anyhow::bail!("the .npz has no temperature")
+            }
+         }
+      }
+   };
+   Ok((grid.width(), grid.height()))
+}
+
+// the text after `'key':` in a .npy header dict, up to the next top level comma
+fn header_value<'a>(header: &'a str, key: &str) -> anyhow::Result<&'a str> {
+   let Some(at) = header.find(&format!("'{}':", key)) else {anyhow::bail!("the .npy header has no {}", key)};
+   let rest = header[at + key.len() + 3..].trim_start();
+   let mut depth = 0;
+   for (n, c) in rest.char_indices() {
+      match c {
+         '(' => depth += 1,
+         ')' => depth -= 1,
+         ',' | '}' if depth == 0 => return Ok(rest[..n].trim()),
+         _ => {}
+      }
+   }
+   anyhow::bail!("the .npy header's {} never ends", key)
+}
+
+/// Several named grids as a .npz, which is a zip of one .npy per name, stored without
+///    compression like np.savez.
+pub fn write_npz(fields: &[(&str, &RectGrid)], dtype: NpyDtype) -> anyhow::Result<Vec<u8>> {
+   let mut bytes = Vec::new();
+   let mut directory = Vec::new();
+   for (name, grid) in fields.iter() {
+      let data = write_npy(grid, dtype);
+      let file_name = format!("{}.npy", name);
+      let (Ok(size), Ok(offset)) = (u32::try_from(data.len()), u32::try_from(bytes.len())) else {
+         anyhow::bail!("{} does not fit in a zip without zip64", name)
+      };
+      let crc = crc32fast::hash(&data);
+
+      // local header: version 2.0, no flags, stored, 1980-01-01 00:00
+      bytes.extend_from_slice(&0x04034b50u32.to_le_bytes());
+      for half in [20u16, 0, 0, 0, 0x21] {bytes.extend_from_slice(&half.to_le_bytes())}
+      for word in [crc, size, size] {bytes.extend_from_slice(&word.to_le_bytes())}
+      for half in [file_name.len() as u16, 0] {bytes.extend_from_slice(&half.to_le_bytes())}
+      bytes.extend_from_slice(file_name.as_bytes());
+      bytes.extend_from_slice(&data);
+
+      directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+      for half in [20u16, 20, 0, 0, 0, 0x21] {directory.extend_from_slice(&half.to_le_bytes())}
+      for word in [crc, size, size] {directory.extend_from_slice(&word.to_le_bytes())}
+      for half in [file_name.len() as u16, 0, 0, 0, 0] {directory.extend_from_slice(&half.to_le_bytes())}
+      for word in [0u32, offset] {directory.extend_from_slice(&word.to_le_bytes())}
+      directory.extend_from_slice(file_name.as_bytes());
+   }
+
+   let Ok(directory_offset) = u32::try_from(bytes.len()) else {anyhow::bail!("the fields do not fit in a zip without zip64")};
+   bytes.extend_from_slice(&directory);
+   bytes.extend_from_slice(&0x06054b50u32.to_le_bytes());
+   for half in [0u16, 0, fields.len() as u16, fields.len() as u16] {bytes.extend_from_slice(&half.to_le_bytes())}
+   for word in [directory.len() as u32, directory_offset] {bytes.extend_from_slice(&word.to_le_bytes())}
+   bytes.extend_from_slice(&0u16.to_le_bytes());
+   Ok(bytes)
+}
+
+/// Every .npy in a .npz, named without the .npy, in the order they are stored. Takes stored
+///    and deflated entries, so np.savez_compressed output reads too.
+pub fn read_npz(bytes: &[u8]) -> anyhow::Result<Vec<(String, RectGrid)>> {
+   let u16_at = |at: usize| -> anyhow::Result<u16> {
+      Ok(u16::from_le_bytes(bytes.get(at..at + 2).ok_or(anyhow::anyhow!("the .npz ends early"))?.try_into()?))
+   };
+   let u32_at = |at: usize| -> anyhow::Result<u32> {
+      Ok(u32::from_le_bytes(bytes.get(at..at + 4).ok_or(anyhow::anyhow!("the .npz ends early"))?.try_into()?))
+   };
+   let u64_at = |at: usize| -> anyhow::Result<u64> {
+      Ok(u64::from_le_bytes(bytes.get(at..at + 8).ok_or(anyhow::anyhow!("the .npz ends early"))?.try_into()?))
+   };
+
+   // the end record sits last, before a comment of at most 65535 bytes
+   let Some(end) = (0..bytes.len().saturating_sub(21)).rev().take(65536)
+      .find(|&at| bytes[at..at + 4] == 0x06054b50u32.to_le_bytes()) else {anyhow::bail!("this is not a .npz, there is no zip directory")};
+   let entries = u16_at(end + 10)? as usize;
+   let mut at = u32_at(end + 16)? as usize;
+
+   let mut fields = Vec::with_capacity(entries);
+   for _ in 0..entries {
+      if u32_at(at)? != 0x02014b50 {anyhow::bail!("the .npz directory is broken")}
+      let method = u16_at(at + 10)?;
+      let crc = u32_at(at + 16)?;
+      let mut compressed_size = u32_at(at + 20)? as u64;
+      let mut size = u32_at(at + 24)? as u64;
+      let (name_length, extra_length, comment_length) = (u16_at(at + 28)? as usize, u16_at(at + 30)? as usize, u16_at(at + 32)? as usize);
+      let mut local = u32_at(at + 42)? as u64;
+      let Some(name) = bytes.get(at + 46..at + 46 + name_length) else {anyhow::bail!("the .npz ends early")};
+      let name = String::from_utf8_lossy(name).to_string();
+
+      // zip64 keeps whichever of these overflowed in an extra field, in this order
+      let mut extra = at + 46 + name_length;
+      while extra + 4 <= at + 46 + name_length + extra_length {
+         let (id, length) = (u16_at(extra)?, u16_at(extra + 2)? as usize);
+         if id == 0x0001 {
+            let mut field = extra + 4;
+            for value in [&mut size, &mut compressed_size, &mut local] {
+               if *value == 0xffffffff {*value = u64_at(field)?; field += 8}
+            }
+         }
+         extra += 4 + length;
+      }
+      at += 46 + name_length + extra_length + comment_length;
+
+      let local = local as usize;
+      if u32_at(local)? != 0x04034b50 {anyhow::bail!("{} in the .npz is broken", name)}
+      let start = local + 30 + u16_at(local + 26)? as usize + u16_at(local + 28)? as usize;
+      let Some(raw) = bytes.get(start..start + compressed_size as usize) else {anyhow::bail!("{} runs past the end of the .npz", name)};
+      let data = match method {
+         0 => raw.to_vec(),
+         8 => miniz_oxide::inflate::decompress_to_vec_with_limit(raw, size as usize)
+            .map_err(|e| anyhow::anyhow!("could not inflate {}: {:?}", name, e.status))?,
+         other => anyhow::bail!("{} is compressed with method {}, only stored and deflate are read", name, other)
+      };
+      if data.len() as u64 != size || crc32fast::hash(&data) != crc {anyhow::bail!("{} in the .npz is corrupt", name)}
+
+      let grid = read_npy(&data).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+      fields.push((name.strip_suffix(".npy").unwrap_or(&name).to_string(), grid));
+   }
+   Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn grid() -> RectGrid {
+      RectGrid::newfromdata(3, 2, vec![0., 1.5, -2., 3.25, 400., 1e-3])
+   }
+
+   // a .npy as numpy would write it, with a header and data of our choosing
+   fn npy(descr: &str, fortran_order: bool, shape: (usize, usize), data: &[u8]) -> Vec<u8> {
+      let order = if fortran_order {"True"} else {"False"};
+      let mut header = format!("{{'descr': '{}', 'fortran_order': {}, 'shape': ({}, {}), }}", descr, order, shape.0, shape.1);
+      header.push_str(&" ".repeat(63 - (10 + header.len()) % 64));
+      header.push('\n');
+      let mut bytes = MAGIC.to_vec();
+      bytes.extend_from_slice(&[1, 0]);
+      bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+      bytes.extend_from_slice(header.as_bytes());
+      bytes.extend_from_slice(data);
+      bytes
+   }
+
+   #[test]
+   fn round_trip() {
+      for dtype in [NpyDtype::F32, NpyDtype::F64] {
+         let bytes = write_npy(&grid(), dtype);
+         // the data starts on a multiple of 64, as numpy lays it out
+         assert_eq!((bytes.len() - grid().getarray().len() * if dtype == NpyDtype::F32 {4} else {8}) % 64, 0);
+         let read = read_npy(&bytes).unwrap();
+         assert_eq!((read.width(), read.height()), (3, 2));
+         assert_eq!(read.getarray(), grid().getarray());
+      }
+   }
+
+   #[test]
+   fn header() {
+      let bytes = write_npy(&grid(), NpyDtype::F64);
+      let header = std::str::from_utf8(&bytes[10..128]).unwrap();
+      assert!(header.ends_with(" \n"));
+      assert!(header.starts_with("{'descr': '<f8', 'fortran_order': False, 'shape': (2, 3), }"));
+   }
+
+   #[test]
+   fn big_endian() {
+      let data: Vec<u8> = grid().getarray().iter().flat_map(|x| x.to_be_bytes()).collect();
+      assert_eq!(read_npy(&npy(">f4", false, (2, 3), &data)).unwrap().getarray(), grid().getarray());
+      let data: Vec<u8> = grid().getarray().iter().flat_map(|x| (*x as f64).to_be_bytes()).collect();
+      assert_eq!(read_npy(&npy(">f8", false, (2, 3), &data)).unwrap().getarray(), grid().getarray());
+   }
+
+   #[test]
+   fn fortran_order() {
+      // column by column, so (0,0) (1,0) (0,1) (1,1) (0,2) (1,2) as (row, column)
+      let values = grid();
+      let data: Vec<u8> = (0..3).flat_map(|i| (0..2).map(move |j| (i, j)))
+         .flat_map(|(i, j)| values.getelement(i, j).to_le_bytes()).collect();
+      let read = read_npy(&npy("<f4", true, (2, 3), &data)).unwrap();
+      assert_eq!((read.width(), read.height()), (3, 2));
+      assert_eq!(read.getarray(), grid().getarray());
+   }
+
+   #[test]
+   fn rejects() {
+      let data = [0u8; 24];
+      assert!(read_npy(b"not a numpy file").is_err());
+      assert!(read_npy(&npy("<i4", false, (2, 3), &data)).is_err());
+      assert!(read_npy(&npy("<f4", false, (2, 2), &data)).is_err());
+      let bytes = write_npy(&grid(), NpyDtype::F32);
+      assert!(read_npy(&bytes[..bytes.len() - 1]).is_err());
+   }
+
+   #[test]
+   fn rejects_oversize_shape() {
+      // the byte count of this shape overflows a usize
+      let huge = 1usize << (usize::BITS / 2);
+      assert!(read_npy(&npy("<f4", false, (huge, huge), &[0u8; 24])).is_err());
+      assert!(read_npy(&npy("<f8", false, (usize::MAX, 1), &[0u8; 24])).is_err());
+   }
+
+   #[test]
+   fn npz_round_trip() {
+      let other = RectGrid::newfromdata(2, 2, vec![1., 0., 0., 1.]);
+      let bytes = write_npz(&[("temperature", &grid()), ("mask", &other)], NpyDtype::F64).unwrap();
+      let fields = read_npz(&bytes).unwrap();
+      assert_eq!(fields.len(), 2);
+      assert_eq!(fields[0].0, "temperature");
+      assert_eq!(fields[0].1.getarray(), grid().getarray());
+      assert_eq!(fields[1].0, "mask");
+      assert_eq!((fields[1].1.width(), fields[1].1.height()), (2, 2));
+      assert_eq!(fields[1].1.getarray(), other.getarray());
+   }
+
+   #[test]
+   fn npz_corrupt() {
+      let mut bytes = write_npz(&[("temperature", &grid())], NpyDtype::F32).unwrap();
+      assert!(read_npz(&bytes[..bytes.len() - 30]).is_err());
+      // a flipped byte in the stored .npy fails the crc
+      let npy_at = 30 + "temperature.npy".len() + 100;
+      bytes[npy_at] ^= 0xff;
+      assert!(read_npz(&bytes).is_err());
+   }
+}
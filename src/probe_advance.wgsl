@@ -0,0 +1,8 @@
+@group(0) @binding(0) var<storage, read_write> head: array<u32>;
+
+// kept apart from probe_sample.wgsl so every probe sees the same head
+@compute
+@workgroup_size(1,1,1)
+fn main() {
+   head[0] = head[0] + 1u;
+}
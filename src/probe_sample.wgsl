@@ -0,0 +1,43 @@
+struct ProbeDims {
+   width: u32,
+   height: u32,
+   probe_count: u32,
+   // how many samples of every probe the ring holds
+   capacity: u32,
+}
+
+@group(0) @binding(0) var<storage, read> data: array<f32>;
+// in the same [0,1]x[0,1] coordinates as RectGrid
+@group(0) @binding(1) var<storage, read> positions: array<vec2<f32>>;
+@group(0) @binding(2) var<storage, read_write> ring: array<f32>;
+// samples taken so far, only probe_advance.wgsl moves it on
+@group(0) @binding(3) var<storage, read> head: array<u32>;
+@group(0) @binding(4) var<uniform> dims: ProbeDims;
+
+fn cell(i: u32, j: u32) -> f32 {
+   return data[i + j * dims.width];
+}
+
+// bilinear interpolation of the four cells around each probe
+@compute
+@workgroup_size(64,1,1)
+fn main(
+   @builtin(global_invocation_id) gid: vec3<u32>
+) {
+   if (gid.x >= dims.probe_count) {return;}
+
+   let position = clamp(positions[gid.x], vec2<f32>(0.0), vec2<f32>(1.0));
+   let fx = position.x * f32(dims.width - 1u);
+   let fy = position.y * f32(dims.height - 1u);
+   let i0 = min(u32(floor(fx)), dims.width - 1u);
+   let j0 = min(u32(floor(fy)), dims.height - 1u);
+   let i1 = min(i0 + 1u, dims.width - 1u);
+   let j1 = min(j0 + 1u, dims.height - 1u);
+   let tx = fx - f32(i0);
+   let ty = fy - f32(j0);
+
+   let below = mix(cell(i0, j0), cell(i1, j0), tx);
+   let above = mix(cell(i0, j1), cell(i1, j1), tx);
+
+   ring[(head[0] % dims.capacity) * dims.probe_count + gid.x] = mix(below, above, ty);
+}
@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+
+use bytemuck::cast_slice;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::vtk::VtkGeometry;
+use crate::webgpuheat::{HeatComputer, helper_basic_compute_shader, helper_compute_bind_group};
+
+/// A named sensor point, in the same [0,1]x[0,1] coordinates as RectGrid. Positions measured
+///    on the domain go through in_domain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Probe {
+   pub name: String,
+   pub x: f32,
+   pub y: f32,
+}
+
+impl Probe {
+   /// a probe at (x, y) in domain units, on the domain `geometry` lays over the grid, so a
+   ///    thermocouple's position from an experiment goes in as measured
+   pub fn in_domain(name: &str, x: f64, y: f64, geometry: &VtkGeometry) -> Self {
+      let (x, y) = geometry.grid_coordinates(x, y);
+      Self { name: name.to_string(), x, y }
+   }
+}
+
+/// Records the bilinearly interpolated temperature at every probe, every `every` steps,
+///    into a ring buffer on the gpu holding the last `capacity` samples. The ring and its
+///    head counter never leave the gpu until read_series, while the simulated time of each
+///    sample is kept here, since the cpu already knows it when the steps are recorded.
+pub struct ProbeRecorder {
+   pub probes: Vec<Probe>,
+   pub every: u32,
+   pub capacity: u32,
+   /// oldest first, one per sample still in the ring
+   pub sample_times: VecDeque<f64>,
+   pub sample_pipeline: wgpu::ComputePipeline,
+   pub advance_pipeline: wgpu::ComputePipeline,
+   pub position_buffer: wgpu::Buffer,
+   pub ring_buffer: wgpu::Buffer,
+   pub head_buffer: wgpu::Buffer,
+   pub dims_buffer: wgpu::Buffer,
+   pub readback_buffer: wgpu::Buffer,
+   pub sample_bind_group: wgpu::BindGroup,
+   pub advance_bind_group: wgpu::BindGroup,
+}
+
+impl ProbeRecorder {
+   pub fn new(
+      heateq: &HeatComputer,
+      device: &wgpu::Device,
+      probes: Vec<Probe>,
+      every: u32,
+      capacity: u32,
+   ) -> anyhow::Result<Self> {
+      if probes.is_empty() {anyhow::bail!("no probes given")}
+      if every == 0 || capacity == 0 {anyhow::bail!("probes need every and capacity of at least 1")}
+      for probe in probes.iter() {
+         if !(0. ..=1.).contains(&probe.x) || !(0. ..=1.).contains(&probe.y) {
+            anyhow::bail!("probe {} at ({}, {}) of the grid is off it", probe.name, probe.x, probe.y)
+         }
+      }
+
+      let probe_count = probes.len() as u32;
+      let ring_size = capacity as u64 * probe_count as u64 * 4;
+
+      let sample_shader = device.create_shader_module(wgpu::include_wgsl!("probe_sample.wgsl"));
+      let advance_shader = device.create_shader_module(wgpu::include_wgsl!("probe_advance.wgsl"));
+      let sample_pipeline = helper_basic_compute_shader(device, Some("Probe Sample Pipeline"), &sample_shader);
+      let advance_pipeline = helper_basic_compute_shader(device, Some("Probe Advance Pipeline"), &advance_shader);
+
+      let positions: Vec<f32> = probes.iter().flat_map(|probe| [probe.x, probe.y]).collect();
+      let position_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("probe positions"),
+         contents: cast_slice(&positions),
+         usage: wgpu::BufferUsages::STORAGE,
+      });
+      let ring_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("probe ring"),
+         size: ring_size,
+         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+         mapped_at_creation: false,
+      });
+      let head_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("probe head"),
+         contents: cast_slice(&[0u32]),
+         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+      });
+      let dims_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("probe dims"),
+         contents: cast_slice(&[heateq.width, heateq.height, probe_count, capacity]),
+         usage: wgpu::BufferUsages::UNIFORM,
+      });
+      // the head goes in front of the ring
+      let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("probe readback"),
+         size: 4 + ring_size,
+         usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+         mapped_at_creation: false,
+      });
+
+      let sample_bind_group = helper_compute_bind_group(
+         device, None, &sample_pipeline,
+         &[&heateq.data_buffer, &position_buffer, &ring_buffer, &head_buffer, &dims_buffer]
+      );
+      let advance_bind_group = helper_compute_bind_group(
+         device, None, &advance_pipeline, &[&head_buffer]
+      );
+
+      Ok(Self {
+         probes,
+         every,
+         capacity,
+         sample_times: VecDeque::new(),
+         sample_pipeline,
+         advance_pipeline,
+         position_buffer,
+         ring_buffer,
+         head_buffer,
+         dims_buffer,
+         readback_buffer,
+         sample_bind_group,
+         advance_bind_group,
+      })
+   }
+
+   /// samples the data buffer as it is at this point in the pass
+   pub fn record_sample(&self, gputodo: &mut wgpu::ComputePass) {
+      gputodo.set_pipeline(&self.sample_pipeline);
+      gputodo.set_bind_group(0, &self.sample_bind_group, &[]);
+      gputodo.dispatch_workgroups((self.probes.len() as u32).div_ceil(64), 1, 1);
+
+      gputodo.set_pipeline(&self.advance_pipeline);
+      gputodo.set_bind_group(0, &self.advance_bind_group, &[]);
+      gputodo.dispatch_workgroups(1, 1, 1);
+   }
+
+   /// the cpu half of a sample, to be called once for every record_sample
+   pub fn note_sample(&mut self, sim_time: f64) {
+      if self.sample_times.len() == self.capacity as usize {
+         self.sample_times.pop_front();
+      }
+      self.sample_times.push_back(sim_time);
+   }
+
+   /// every sample still in the ring, oldest first, as (time, one value per probe)
+   pub async fn read_series(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<Vec<(f64, Vec<f32>)>> {
+      let ring_size = self.ring_buffer.size();
+
+      let mut encoder = device.create_command_encoder(&Default::default());
+      encoder.copy_buffer_to_buffer(&self.head_buffer, 0, &self.readback_buffer, 0, 4);
+      encoder.copy_buffer_to_buffer(&self.ring_buffer, 0, &self.readback_buffer, 4, ring_size);
+      let (sender, receiver) = tokio::sync::oneshot::channel();
+      encoder.map_buffer_on_submit(&self.readback_buffer, wgpu::MapMode::Read, ..,
+         move |result| {_ = sender.send(result);});
+      queue.submit([encoder.finish()]);
+
+      device.poll(wgpu::PollType::wait_indefinitely())?;
+      receiver.await??;
+
+      let (head, ring): (u32, Vec<f32>) = {
+         let mapped = self.readback_buffer.get_mapped_range(..);
+         (cast_slice::<u8, u32>(&mapped[0..4])[0], cast_slice(&mapped[4..]).to_vec())
+      };
+      self.readback_buffer.unmap();
+
+      let held = head.min(self.capacity) as usize;
+      if held != self.sample_times.len() {
+         anyhow::bail!("gpu holds {} probe samples but {} sample times were noted", held, self.sample_times.len())
+      }
+
+      let probe_count = self.probes.len();
+      let oldest = if head > self.capacity {head % self.capacity} else {0} as usize;
+      Ok(self.sample_times.iter().enumerate().map(|(n, time)| {
+         let slot = (oldest + n) % self.capacity as usize;
+         (*time, ring[slot * probe_count..(slot + 1) * probe_count].to_vec())
+      }).collect())
+   }
+
+   /// a time column and then one column per probe, headed by the probe names
+   pub fn series_as_csv(&self, series: &[(f64, Vec<f32>)]) -> anyhow::Result<String> {
+      let mut thewriter = csv::WriterBuilder::new()
+         .delimiter(b',')
+         .from_writer(vec![]);
+
+      thewriter.write_record(
+         std::iter::once("time".to_string()).chain(self.probes.iter().map(|probe| probe.name.clone()))
+      )?;
+      for (time, values) in series.iter() {
+         thewriter.write_record(
+            std::iter::once(format!("{}", time)).chain(values.iter().map(|f| format!("{}", f)))
+         )?;
+      }
+
+      Ok(String::from_utf8(thewriter.into_inner()?)?)
+   }
+}
@@ -94,4 +94,132 @@ impl RectGrid {
 
       RectGrid { array: newdata, imax: self.imax, jmax: self.jmax }
    }
+
+   /// for wrapping a field read back from the gpu, row by row as it is there
+   pub fn newfromdata(width: usize, height: usize, newdata: Vec<f32>) -> RectGrid {
+      assert_eq!(width * height, newdata.len());
+
+      RectGrid { array: newdata, imax: width, jmax: height }
+   }
+
+   pub fn getelement(&self, i: usize, j: usize) -> f32 {
+      self.array[i + j * self.imax]
+   }
+
+   /// bilinear interpolation between the four cells around (x,y), which is clamped
+   ///   onto [0,1]x[0,1]
+   pub fn samplebilinear(&self, x: f32, y: f32) -> f32 {
+      let fx = x.clamp(0., 1.) * (self.imax - 1) as f32;
+      let fy = y.clamp(0., 1.) * (self.jmax - 1) as f32;
+      let i0 = (fx.floor() as usize).min(self.imax - 1);
+      let j0 = (fy.floor() as usize).min(self.jmax - 1);
+      let i1 = (i0 + 1).min(self.imax - 1);
+      let j1 = (j0 + 1).min(self.jmax - 1);
+      let tx = fx - i0 as f32;
+      let ty = fy - j0 as f32;
+
+      let below = self.getelement(i0, j0) * (1. - tx) + self.getelement(i1, j0) * tx;
+      let above = self.getelement(i0, j1) * (1. - tx) + self.getelement(i1, j1) * tx;
+      below * (1. - ty) + above * ty
+   }
+
+   /// cubic lagrange interpolation through the 4x4 cells around (x,y), clamped like
+   ///   samplebilinear. Exact for cubics, so its error is fourth order where bilinear's is
+   ///   second, small enough to compare solutions whose own error is second order.
+   pub fn samplebicubic(&self, x: f32, y: f32) -> f32 {
+      let fx = x.clamp(0., 1.) * (self.imax - 1) as f32;
+      let fy = y.clamp(0., 1.) * (self.jmax - 1) as f32;
+      let i0 = (fx.floor() as usize).min(self.imax - 1);
+      let j0 = (fy.floor() as usize).min(self.jmax - 1);
+      let weights = |t: f32| [
+         -t * (t - 1.) * (t - 2.) / 6.,
+         (t + 1.) * (t - 1.) * (t - 2.) / 2.,
+         -(t + 1.) * t * (t - 2.) / 2.,
+         (t + 1.) * t * (t - 1.) / 6.,
+      ];
+      let (wx, wy) = (weights(fx - i0 as f32), weights(fy - j0 as f32));
+      // neighbours past the edge repeat the edge cell
+      let index = |n: usize, offset: usize, max: usize| (n + offset).saturating_sub(1).min(max - 1);
+
+      (0..4).map(|b| {
+         let j = index(j0, b, self.jmax);
+         wy[b] * (0..4).map(|a| wx[a] * self.getelement(index(i0, a, self.imax), j)).sum::<f32>()
+      }).sum()
+   }
+
+   /// `samples` evenly spaced (distance from start, T) pairs along the segment from
+   ///   (x0,y0) to (x1,y1), ends included
+   pub fn lineprofile(&self, x0: f32, y0: f32, x1: f32, y1: f32, samples: usize) -> Vec<(f32, f32)> {
+      let length = ((x1 - x0).powi(2) + (y1 - y0).powi(2)).sqrt();
+      (0..samples).map(|n| {
+         let t = if samples > 1 {n as f32 / (samples - 1) as f32} else {0.};
+         (t * length, self.samplebilinear(x0 + t * (x1 - x0), y0 + t * (y1 - y0)))
+      }).collect()
+   }
+
+   /// the cells of row j as (x, T), no interpolation needed
+   pub fn rowprofile(&self, j: usize) -> Vec<(f32, f32)> {
+      (0..self.imax).map(|i| (i as f32 / (self.imax - 1) as f32, self.getelement(i, j))).collect()
+   }
+
+   /// the cells of column i as (y, T)
+   pub fn columnprofile(&self, i: usize) -> Vec<(f32, f32)> {
+      (0..self.jmax).map(|j| (j as f32 / (self.jmax - 1) as f32, self.getelement(i, j))).collect()
+   }
+}
+
+impl RectGrid {
+   /// one line per row of the grid and no header, the same layout parse_csv reads back in
+   pub fn writecsv(&self) -> anyhow::Result<String> {
+      let mut thewriter = csv::WriterBuilder::new()
+         .delimiter(b',')
+         .has_headers(false)
+         .from_writer(vec![]);
+      for row in self.array.chunks(self.imax) {
+         thewriter.write_record(row.iter().map(|f| format!("{}",f)))?;
+      }
+
+      Ok(String::from_utf8(thewriter.into_inner()?)?)
+   }
+
+   /// reads back what writecsv writes, every row as long as the first
+   pub fn readcsv(text: &str) -> anyhow::Result<RectGrid> {
+      let mut csvrdr = csv::ReaderBuilder::new()
+         .delimiter(b',')
+         .has_headers(false)
+         .from_reader(text.as_bytes());
+
+      let mut array: Vec<f32> = Vec::new();
+      let mut width = 0;
+      let mut height = 0;
+      for record in csvrdr.records() {
+         let record = record?;
+         if height == 0 {width = record.len()}
+         if record.len() != width {anyhow::bail!("row {} has {} entries, the first has {}", height, record.len(), width)}
+         for (i, entry) in record.iter().enumerate() {
+            match entry.trim().parse::<f32>() {
+               Ok(value) => array.push(value),
+               Err(_) => anyhow::bail!("could not read csv entry ({},{}) as float32", height, i)
+            }
+         }
+         height += 1;
+      }
+      if width < 3 || height < 3 {anyhow::bail!("a {}x{} grid is too small to hold a field", width, height)}
+
+      Ok(RectGrid { array, imax: width, jmax: height })
+   }
+}
+
+/// a profile as two columns, distance and T, with a header
+pub fn profile_as_csv(profile: &[(f32, f32)]) -> anyhow::Result<String> {
+   let mut thewriter = csv::WriterBuilder::new()
+      .delimiter(b',')
+      .from_writer(vec![]);
+
+   thewriter.write_record(["distance", "T"])?;
+   for (distance, value) in profile.iter() {
+      thewriter.write_record([format!("{}", distance), format!("{}", value)])?;
+   }
+
+   Ok(String::from_utf8(thewriter.into_inner()?)?)
 }
@@ -0,0 +1,149 @@
+use bytemuck::cast_slice;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::webgpuheat::{helper_basic_compute_shader, helper_compute_bind_group, linear_workgroups};
+
+/// Summary numbers for a whole field, with the centroid in the same [0,1]x[0,1]
+///    coordinates as RectGrid and weighted by T, so it is NaN for a field summing to 0.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldStats {
+   pub sum: f32,
+   pub min: f32,
+   pub max: f32,
+   pub mean: f32,
+   /// sqrt of the sum of squares over every cell, no cell area involved
+   pub l2: f32,
+   pub linf: f32,
+   pub centroid_x: f32,
+   pub centroid_y: f32,
+}
+
+impl FieldStats {
+   /// from the 8 floats stats_final.wgsl leaves behind
+   pub fn from_raw(raw: &[f32], cells: u32) -> Self {
+      Self {
+         sum: raw[0],
+         min: raw[1],
+         max: raw[2],
+         mean: raw[0] / cells as f32,
+         l2: raw[3].sqrt(),
+         linf: raw[4],
+         centroid_x: raw[5] / raw[0],
+         centroid_y: raw[6] / raw[0],
+      }
+   }
+}
+
+/// GPU reduction of one width x height field down to a FieldStats, in two dispatches:
+///    one partial per 64 cells, then a single workgroup folding the partials. Only 32
+///    bytes ever come back to the cpu, so this is cheap enough to do every frame.
+pub struct FieldReducer {
+   pub width: u32,
+   pub height: u32,
+   pub partial_pipeline: wgpu::ComputePipeline,
+   pub final_pipeline: wgpu::ComputePipeline,
+   pub dims_buffer: wgpu::Buffer,
+   pub partials_buffer: wgpu::Buffer,
+   /// the raw 8 floats, left on the gpu so other passes can use them without a readback
+   pub result_buffer: wgpu::Buffer,
+   pub readback_buffer: wgpu::Buffer,
+   pub partial_bind_group: wgpu::BindGroup,
+   pub final_bind_group: wgpu::BindGroup,
+}
+
+impl FieldReducer {
+   pub fn new(device: &wgpu::Device, width: u32, height: u32, field: &wgpu::Buffer) -> Self {
+      let partial_count = (width * height).div_ceil(64);
+
+      let partial_shader = device.create_shader_module(wgpu::include_wgsl!("stats_partial.wgsl"));
+      let final_shader = device.create_shader_module(wgpu::include_wgsl!("stats_final.wgsl"));
+      let partial_pipeline = helper_basic_compute_shader(device, Some("Stats Partial Pipeline"), &partial_shader);
+      let final_pipeline = helper_basic_compute_shader(device, Some("Stats Final Pipeline"), &final_shader);
+
+      let dims_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("stats dims"),
+         contents: cast_slice(&[width, height, partial_count, 0]),
+         usage: wgpu::BufferUsages::UNIFORM,
+      });
+      let partials_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("stats partials"),
+         size: partial_count as u64 * 32,
+         usage: wgpu::BufferUsages::STORAGE,
+         mapped_at_creation: false,
+      });
+      let result_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("stats result"),
+         size: 32,
+         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+         mapped_at_creation: false,
+      });
+      let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("stats readback"),
+         size: 32,
+         usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+         mapped_at_creation: false,
+      });
+
+      let partial_bind_group = helper_compute_bind_group(
+         device, None, &partial_pipeline, &[field, &partials_buffer, &dims_buffer]
+      );
+      let final_bind_group = helper_compute_bind_group(
+         device, None, &final_pipeline, &[&partials_buffer, &result_buffer, &dims_buffer]
+      );
+
+      Self {
+         width,
+         height,
+         partial_pipeline,
+         final_pipeline,
+         dims_buffer,
+         partials_buffer,
+         result_buffer,
+         readback_buffer,
+         partial_bind_group,
+         final_bind_group,
+      }
+   }
+
+   /// leaves the raw stats of the field as it is at this point in result_buffer
+   pub fn record(&self, gputodo: &mut wgpu::ComputePass) {
+      gputodo.set_pipeline(&self.partial_pipeline);
+      gputodo.set_bind_group(0, &self.partial_bind_group, &[]);
+      let (row_workgroups, rows) = linear_workgroups(self.width * self.height);
+      gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+
+      gputodo.set_pipeline(&self.final_pipeline);
+      gputodo.set_bind_group(0, &self.final_bind_group, &[]);
+      gputodo.dispatch_workgroups(1, 1, 1);
+   }
+
+   /// reduces the field and waits for the 32 bytes of answer. Anything that should happen
+   ///    to the field first has to be submitted before this.
+   pub async fn read_stats(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<FieldStats> {
+      let mut encoder = device.create_command_encoder(&Default::default());
+      {
+         let mut gputodo = encoder.begin_compute_pass(&Default::default());
+         self.record(&mut gputodo);
+      }
+      encoder.copy_buffer_to_buffer(&self.result_buffer, 0, &self.readback_buffer, 0, 32);
+      let (sender, receiver) = tokio::sync::oneshot::channel();
+      encoder.map_buffer_on_submit(&self.readback_buffer, wgpu::MapMode::Read, ..,
+         move |result| {_ = sender.send(result);});
+      queue.submit([encoder.finish()]);
+
+      device.poll(wgpu::PollType::wait_indefinitely())?;
+      receiver.await??;
+
+      let stats = {
+         let mapped = self.readback_buffer.get_mapped_range(..);
+         FieldStats::from_raw(cast_slice(&mapped), self.width * self.height)
+      };
+      self.readback_buffer.unmap();
+
+      Ok(stats)
+   }
+}
@@ -0,0 +1,290 @@
+use std::sync::{Arc, Mutex};
+
+use bytemuck::cast_slice;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::rectgrid::RectGrid;
+use crate::webgpuheat::HeatComputer;
+
+/// One reason to stop a controlled run. Times are on the computer's own clock, so `time=1`
+///    stops at t = 1 however long the field had already run, while steps count from the
+///    start of the run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopCondition {
+   Time(f64),
+   Steps(u64),
+   /// the largest |dT/dt| between the walls, taken across two checks, falls below this
+   Steady(f32),
+   /// the named probe reads at least this much
+   ProbeAbove(String, f32),
+   /// the named probe reads at most this much
+   ProbeBelow(String, f32),
+}
+
+impl std::str::FromStr for StopCondition {
+   type Err = String;
+
+   /// time=<t>, steps=<n>, steady=<max |dT/dt|>, probe=<name>><T> or probe=<name><<T>
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      let (kind, value) = s.split_once('=').ok_or(format!("{} should be <kind>=<value>", s))?;
+      let number = |text: &str| text.parse::<f64>().map_err(|_| format!("{} is not a number", text));
+      match kind {
+         "time" => Ok(Self::Time(number(value)?)),
+         "steps" => value.parse().map(Self::Steps).map_err(|_| format!("{} is not a step count", value)),
+         "steady" => match number(value)? {
+            tolerance if tolerance > 0. => Ok(Self::Steady(tolerance as f32)),
+            _ => Err("steady needs a tolerance above 0".to_string())
+         },
+         "probe" => {
+            let at = value.rfind(['<', '>']).ok_or(format!("{} should be <name>><T> or <name><<T>", value))?;
+            let (name, threshold) = (value[..at].to_string(), number(&value[at + 1..])? as f32);
+            if name.is_empty() {return Err("the probe needs a name".to_string())}
+            match &value[at..at + 1] {
+               ">" => Ok(Self::ProbeAbove(name, threshold)),
+               _ => Ok(Self::ProbeBelow(name, threshold)),
+            }
+         }
+         other => Err(format!("{} is not a stop condition, try time, steps, steady or probe", other))
+      }
+   }
+}
+
+impl std::fmt::Display for StopCondition {
+   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      match self {
+         Self::Time(time) => write!(f, "t reached {}", time),
+         Self::Steps(steps) => write!(f, "{} steps taken", steps),
+         Self::Steady(tolerance) => write!(f, "max |dT/dt| below {}", tolerance),
+         Self::ProbeAbove(name, threshold) => write!(f, "probe {} reached {}", name, threshold),
+         Self::ProbeBelow(name, threshold) => write!(f, "probe {} fell to {}", name, threshold),
+      }
+   }
+}
+
+impl StopCondition {
+   /// whether this needs the field read back to be decided
+   pub fn needs_field(&self) -> bool {
+      matches!(self, Self::Steady(_) | Self::ProbeAbove(..) | Self::ProbeBelow(..))
+   }
+}
+
+/// What a controlled run is asked to do: stop as soon as any one condition holds, reading
+///    the field back every `check_every` steps for the conditions that need it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunUntil {
+   pub conditions: Vec<StopCondition>,
+   /// 0 for every iteration_quantity steps
+   pub check_every: u32,
+}
+
+impl std::str::FromStr for RunUntil {
+   type Err = String;
+
+   /// conditions separated by spaces, plus every=<n> for how often to check
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      let mut until = Self { conditions: Vec::new(), check_every: 0 };
+      for word in s.split_whitespace() {
+         match word.strip_prefix("every=") {
+            Some(every) => until.check_every = every.parse().map_err(|_| format!("{} is not a step count", every))?,
+            None => until.conditions.push(word.parse()?)
+         }
+      }
+      if until.conditions.is_empty() {return Err("a run needs at least one condition to stop on".to_string())}
+      Ok(until)
+   }
+}
+
+/// how a controlled run ended
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter_with_clone))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunReport {
+   /// the condition that held, None if the run was cancelled
+   pub reason: Option<String>,
+   pub cancelled: bool,
+   /// steps taken by this run
+   pub steps: u64,
+   pub sim_time: f64,
+   /// the max |dT/dt| between the walls at the last check, if there were two checks
+   pub max_rate: Option<f32>,
+}
+
+impl std::fmt::Display for RunReport {
+   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      match &self.reason {
+         Some(reason) => write!(f, "run stopped, {}, after {} steps, t = {}", reason, self.steps, self.sim_time)?,
+         None => write!(f, "run cancelled after {} steps, t = {}", self.steps, self.sim_time)?,
+      }
+      if let Some(rate) = self.max_rate {write!(f, ", max |dT/dt| = {:e}", rate)?}
+      Ok(())
+   }
+}
+
+pub enum RunStatus {
+   Running,
+   Finished(RunReport),
+}
+
+// a copy of the field on its way back, taken at this time
+struct InFlight {
+   time: f64,
+   mapped: Arc<Mutex<Option<bool>>>,
+}
+
+/// Drives a run chunk by chunk until one of its conditions holds. Time and step conditions
+///    are known on the cpu and the chunks are cut so they land on the exact step. The others
+///    copy the field into a staging buffer every check_every steps and look at it once the
+///    map has come through, a chunk or so later, so the run never waits on a readback and
+///    may overshoot them by that much.
+pub struct RunController {
+   pub until: RunUntil,
+   pub start_steps: u64,
+   pub check_every: u64,
+   pub max_rate: Option<f32>,
+   // where each condition's probe is, None for the conditions without one
+   probe_positions: Vec<Option<(f32, f32)>>,
+   staging_buffer: Option<wgpu::Buffer>,
+   in_flight: Option<InFlight>,
+   last_check: Option<(f64, RectGrid)>,
+   next_check: u64,
+}
+
+impl RunController {
+   pub fn new(heateq: &HeatComputer, device: &wgpu::Device, until: RunUntil) -> anyhow::Result<Self> {
+      if until.conditions.is_empty() {anyhow::bail!("a run needs at least one condition to stop on")}
+      let probe_positions = until.conditions.iter().map(|condition| match condition {
+         StopCondition::ProbeAbove(name, _) | StopCondition::ProbeBelow(name, _) => {
+            let probe = heateq.probes.as_ref()
+               .and_then(|probes| probes.probes.iter().find(|probe| probe.name == *name));
+            match probe {
+               Some(probe) => Ok(Some((probe.x, probe.y))),
+               None => anyhow::bail!("there is no probe called {}", name)
+            }
+         }
+         _ => Ok(None)
+      }).collect::<anyhow::Result<Vec<_>>>()?;
+
+      let staging_buffer = until.conditions.iter().any(StopCondition::needs_field).then(|| {
+         device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("run controller readback"),
+            size: (heateq.width * heateq.height) as u64 * 4,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+         })
+      });
+      let check_every = match until.check_every {
+         0 => heateq.iteration_quantity.max(1),
+         every => every
+      } as u64;
+
+      Ok(Self {
+         until,
+         start_steps: heateq.accepted_steps,
+         check_every,
+         max_rate: None,
+         probe_positions,
+         staging_buffer,
+         in_flight: None,
+         last_check: None,
+         next_check: heateq.accepted_steps,
+      })
+   }
+
+   /// the first time or step condition that holds now
+   pub fn met_on_clock(&self, heateq: &HeatComputer) -> Option<&StopCondition> {
+      let steps = heateq.accepted_steps - self.start_steps;
+      let half_step = 0.5 * heateq.delta_t as f64;
+      self.until.conditions.iter().find(|condition| match condition {
+         StopCondition::Time(time) => heateq.sim_time + half_step >= *time,
+         StopCondition::Steps(total) => steps >= *total,
+         _ => false
+      })
+   }
+
+   /// how many steps may go before a time or step condition or the next copy is due
+   pub fn steps_allowed(&self, heateq: &HeatComputer) -> u64 {
+      let steps = heateq.accepted_steps - self.start_steps;
+      let mut allowed = u64::MAX;
+      for condition in self.until.conditions.iter() {
+         let left = match condition {
+            StopCondition::Time(time) => ((time - heateq.sim_time) / heateq.delta_t as f64).round().max(1.) as u64,
+            StopCondition::Steps(total) => total.saturating_sub(steps).max(1),
+            _ => continue
+         };
+         allowed = allowed.min(left);
+      }
+      if self.staging_buffer.is_some() && self.in_flight.is_none() && self.next_check > heateq.accepted_steps {
+         allowed = allowed.min(self.next_check - heateq.accepted_steps);
+      }
+      allowed
+   }
+
+   /// takes a copy of the field if one is due and the last has been looked at
+   pub fn request_check(&mut self, heateq: &HeatComputer, pending_queue: &mut Vec<wgpu::CommandBuffer>, device: &wgpu::Device) {
+      let Some(staging_buffer) = &self.staging_buffer else {return};
+      if self.in_flight.is_some() || heateq.accepted_steps < self.next_check {return}
+
+      let mut encoder = device.create_command_encoder(&Default::default());
+      encoder.copy_buffer_to_buffer(&heateq.data_buffer, 0, staging_buffer, 0, staging_buffer.size());
+      let mapped = Arc::new(Mutex::new(None));
+      let mapped_there = mapped.clone();
+      encoder.map_buffer_on_submit(staging_buffer, wgpu::MapMode::Read, ..,
+         move |result| {*mapped_there.lock().unwrap() = Some(result.is_ok());});
+      pending_queue.push(encoder.finish());
+
+      self.in_flight = Some(InFlight { time: heateq.sim_time, mapped });
+      self.next_check = heateq.accepted_steps + self.check_every;
+   }
+
+   /// looks at the last copy if it has come back, giving the first field condition it meets
+   pub fn collect_check(&mut self, heateq: &HeatComputer) -> anyhow::Result<Option<StopCondition>> {
+      let (Some(staging_buffer), Some(in_flight)) = (&self.staging_buffer, &self.in_flight) else {return Ok(None)};
+      let mapped = *in_flight.mapped.lock().unwrap();
+      let time = in_flight.time;
+      match mapped {
+         None => return Ok(None),
+         Some(false) => {
+            self.in_flight = None;
+            anyhow::bail!("could not read the field back to check it")
+         }
+         Some(true) => {}
+      }
+      let field: Vec<f32> = cast_slice(&staging_buffer.get_mapped_range(..)).to_vec();
+      staging_buffer.unmap();
+      self.in_flight = None;
+      let field = RectGrid::newfromdata(heateq.width as usize, heateq.height as usize, field);
+
+      if let Some((last_time, last_field)) = &self.last_check && time > *last_time {
+         let (width, height) = (field.width(), field.height());
+         let mut largest = 0f32;
+         for j in 1..height - 1 {
+            for i in 1..width - 1 {
+               largest = largest.max((field.getelement(i, j) - last_field.getelement(i, j)).abs());
+            }
+         }
+         self.max_rate = Some(largest / (time - last_time) as f32);
+      }
+
+      let met = self.until.conditions.iter().zip(self.probe_positions.iter()).find(|(condition, position)| {
+         match (condition, position) {
+            (StopCondition::Steady(tolerance), _) => self.max_rate.is_some_and(|rate| rate < *tolerance),
+            (StopCondition::ProbeAbove(_, threshold), Some((x, y))) => field.samplebilinear(*x, *y) >= *threshold,
+            (StopCondition::ProbeBelow(_, threshold), Some((x, y))) => field.samplebilinear(*x, *y) <= *threshold,
+            _ => false
+         }
+      }).map(|(condition, _)| condition.clone());
+      self.last_check = Some((time, field));
+      Ok(met)
+   }
+
+   pub fn report(&self, heateq: &HeatComputer, reason: Option<&StopCondition>) -> RunReport {
+      RunReport {
+         reason: reason.map(|condition| condition.to_string()),
+         cancelled: reason.is_none(),
+         steps: heateq.accepted_steps - self.start_steps,
+         sim_time: heateq.sim_time,
+         max_rate: self.max_rate,
+      }
+   }
+}
@@ -0,0 +1,68 @@
+// folds every partial from stats_partial.wgsl into one, with a single workgroup
+
+struct StatsDims {
+   width: u32,
+   height: u32,
+   count: u32,
+   _pad: u32,
+}
+
+@group(0) @binding(0) var<storage, read> partials: array<f32>;
+@group(0) @binding(1) var<storage, read_write> result: array<f32>;
+@group(0) @binding(2) var<uniform> dims: StatsDims;
+
+const BIG: f32 = 3.4e38;
+
+var<workgroup> acc: array<f32, 512>;
+
+fn combine(into: u32, other: u32) {
+   let a = into * 8u;
+   let b = other * 8u;
+   acc[a] += acc[b];
+   acc[a + 1u] = min(acc[a + 1u], acc[b + 1u]);
+   acc[a + 2u] = max(acc[a + 2u], acc[b + 2u]);
+   acc[a + 3u] += acc[b + 3u];
+   acc[a + 4u] = max(acc[a + 4u], acc[b + 4u]);
+   acc[a + 5u] += acc[b + 5u];
+   acc[a + 6u] += acc[b + 6u];
+}
+
+@compute
+@workgroup_size(64,1,1)
+fn main(
+   @builtin(local_invocation_id) lid: vec3<u32>
+) {
+   let a = lid.x * 8u;
+   acc[a] = 0.0;
+   acc[a + 1u] = BIG;
+   acc[a + 2u] = -BIG;
+   acc[a + 3u] = 0.0;
+   acc[a + 4u] = 0.0;
+   acc[a + 5u] = 0.0;
+   acc[a + 6u] = 0.0;
+   acc[a + 7u] = 0.0;
+
+   // each invocation first walks its own share of the partials
+   for (var n = lid.x; n < dims.count; n += 64u) {
+      let b = n * 8u;
+      acc[a] += partials[b];
+      acc[a + 1u] = min(acc[a + 1u], partials[b + 1u]);
+      acc[a + 2u] = max(acc[a + 2u], partials[b + 2u]);
+      acc[a + 3u] += partials[b + 3u];
+      acc[a + 4u] = max(acc[a + 4u], partials[b + 4u]);
+      acc[a + 5u] += partials[b + 5u];
+      acc[a + 6u] += partials[b + 6u];
+   }
+   workgroupBarrier();
+
+   for (var stride = 32u; stride > 0u; stride = stride >> 1u) {
+      if (lid.x < stride) {
+         combine(lid.x, lid.x + stride);
+      }
+      workgroupBarrier();
+   }
+
+   if (lid.x < 8u) {
+      result[lid.x] = acc[lid.x];
+   }
+}
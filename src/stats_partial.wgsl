@@ -0,0 +1,81 @@
+// one workgroup's worth of [sum, min, max, sum of squares, max |T|, sum x T, sum y T, unused]
+//    per 64 cells, finished off by stats_final.wgsl
+
+struct StatsDims {
+   width: u32,
+   height: u32,
+   // how many partials there are, only stats_final uses this
+   count: u32,
+   _pad: u32,
+}
+
+@group(0) @binding(0) var<storage, read> field: array<f32>;
+@group(0) @binding(1) var<storage, read_write> partials: array<f32>;
+@group(0) @binding(2) var<uniform> dims: StatsDims;
+
+// invocations in a row of workgroups, see linear_workgroups in webgpuheat.rs
+const ROW_LENGTH: u32 = 4194240u;
+
+const BIG: f32 = 3.4e38;
+
+var<workgroup> acc: array<f32, 512>;
+
+fn combine(into: u32, other: u32) {
+   let a = into * 8u;
+   let b = other * 8u;
+   acc[a] += acc[b];
+   acc[a + 1u] = min(acc[a + 1u], acc[b + 1u]);
+   acc[a + 2u] = max(acc[a + 2u], acc[b + 2u]);
+   acc[a + 3u] += acc[b + 3u];
+   acc[a + 4u] = max(acc[a + 4u], acc[b + 4u]);
+   acc[a + 5u] += acc[b + 5u];
+   acc[a + 6u] += acc[b + 6u];
+}
+
+@compute
+@workgroup_size(64,1,1)
+fn main(
+   @builtin(global_invocation_id) gid: vec3<u32>,
+   @builtin(local_invocation_id) lid: vec3<u32>,
+   @builtin(workgroup_id) wid: vec3<u32>
+) {
+   let a = lid.x * 8u;
+   // cells past the end count as nothing at all
+   acc[a] = 0.0;
+   acc[a + 1u] = BIG;
+   acc[a + 2u] = -BIG;
+   acc[a + 3u] = 0.0;
+   acc[a + 4u] = 0.0;
+   acc[a + 5u] = 0.0;
+   acc[a + 6u] = 0.0;
+   acc[a + 7u] = 0.0;
+
+   let cell = gid.x + gid.y * ROW_LENGTH;
+   if (cell < dims.width * dims.height) {
+      let t = field[cell];
+      // the same coordinates as RectGrid, so that (1,1) is a cell
+      let x = f32(cell % dims.width) / f32(max(dims.width - 1u, 1u));
+      let y = f32(cell / dims.width) / f32(max(dims.height - 1u, 1u));
+      acc[a] = t;
+      acc[a + 1u] = t;
+      acc[a + 2u] = t;
+      acc[a + 3u] = t * t;
+      acc[a + 4u] = abs(t);
+      acc[a + 5u] = x * t;
+      acc[a + 6u] = y * t;
+   }
+   workgroupBarrier();
+
+   for (var stride = 32u; stride > 0u; stride = stride >> 1u) {
+      if (lid.x < stride) {
+         combine(lid.x, lid.x + stride);
+      }
+      workgroupBarrier();
+   }
+
+   // the last row of workgroups can run past the partials there are
+   let group = wid.x + wid.y * 65535u;
+   if (lid.x < 8u && group < dims.count) {
+      partials[group * 8u + lid.x] = acc[lid.x];
+   }
+}
@@ -0,0 +1,37 @@
+struct StageCoefficients {
+   mu: f32,
+   nu: f32,
+   // these two already have kappa * delta_t folded into them
+   mu_tilde: f32,
+   gamma_tilde: f32,
+}
+
+@group(0) @binding(0) var<storage, read> y0: array<f32>;
+@group(0) @binding(1) var<storage, read> laplacian_y0: array<f32>;
+@group(0) @binding(2) var<storage, read> prev: array<f32>;
+@group(0) @binding(3) var<storage, read> prev2: array<f32>;
+@group(0) @binding(4) var<storage, read> laplacian_prev: array<f32>;
+@group(0) @binding(5) var<storage, read_write> out: array<f32>;
+@group(0) @binding(6) var<uniform> width: u32;
+@group(0) @binding(7) var<uniform> height: u32;
+@group(0) @binding(8) var<uniform> coeffs: StageCoefficients;
+
+// invocations in a row of workgroups, see linear_workgroups in webgpuheat.rs
+const ROW_LENGTH: u32 = 4194240u;
+
+// one stage j >= 2 of a Runge-Kutta-Legendre super step, the recursion being
+//    Y_j = mu Y_{j-1} + nu Y_{j-2} + (1 - mu - nu) Y_0 + mu~ tau M(Y_{j-1}) + gamma~ tau M(Y_0)
+@compute
+@workgroup_size(64,1,1)
+fn main(
+   @builtin(global_invocation_id) gid: vec3<u32>
+) {
+   let cell = gid.x + gid.y * ROW_LENGTH;
+   if (cell >= (width * height)) {return;}
+
+   out[cell] = coeffs.mu * prev[cell]
+      + coeffs.nu * prev2[cell]
+      + (1.0f - coeffs.mu - coeffs.nu) * y0[cell]
+      + coeffs.mu_tilde * laplacian_prev[cell]
+      + coeffs.gamma_tilde * laplacian_y0[cell];
+}
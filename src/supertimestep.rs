@@ -0,0 +1,253 @@
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::webgpuheat::{HeatComputer, helper_basic_compute_shader, helper_compute_bind_group, helper_compute_interim_data_buffer};
+
+// uniform buffer offsets have to be multiples of this
+const COEFF_STRIDE: u64 = 256;
+
+/// The explicit (forward euler) stability limit for our 5 point laplacian, where
+///    delta_x = 1/width and delta_y = 1/height as in laplacian.wgsl
+pub fn explicit_stable_delta_t(kappa: f32, width: u32, height: u32) -> f64 {
+   1. / (2. * kappa as f64 * ((width as f64).powi(2) + (height as f64).powi(2)))
+}
+
+/// fewest RKL2 stages s for which delta_t is stable, using that an s stage super step
+///    is stable up to (s^2 + s - 2)/4 explicit steps. RKL2 needs at least 2 stages.
+pub fn rkl2_stage_count(delta_t: f32, kappa: f32, width: u32, height: u32) -> u32 {
+   let ratio = delta_t as f64 / explicit_stable_delta_t(kappa, width, height);
+   let stages = ((-1. + (9. + 16. * ratio).sqrt()) / 2.).ceil() as u32;
+   stages.max(2)
+}
+
+/// mu~_1 and then [mu_j, nu_j, mu~_j, gamma~_j] for j = 2..=s, from Meyer, Balsara and Aslam (2014)
+pub fn rkl2_coefficients(stages: u32) -> (f64, Vec<[f64; 4]>) {
+   let s = stages as f64;
+   let w1 = 4. / (s * s + s - 2.);
+   let b = |j: u32| -> f64 {
+      match j {
+         0..=2 => 1. / 3.,
+         _ => {let j = j as f64; (j * j + j - 2.) / (2. * j * (j + 1.))}
+      }
+   };
+   let a = |j: u32| 1. - b(j);
+
+   let mut coefficients = Vec::new();
+   for j in 2..=stages {
+      let jf = j as f64;
+      let mu = (2. * jf - 1.) / jf * b(j) / b(j - 1);
+      let nu = -(jf - 1.) / jf * b(j) / b(j - 2);
+      let mu_tilde = mu * w1;
+      let gamma_tilde = -a(j - 1) * mu_tilde;
+      coefficients.push([mu, nu, mu_tilde, gamma_tilde]);
+   }
+   (b(1) * w1, coefficients)
+}
+
+/// Runge-Kutta-Legendre (RKL2) super time stepping. One super step of delta_t is s cheap
+///    stages, each one laplacian and one linear combination, and is stable for delta_t
+///    up to about s^2/4 times the explicit limit. Built for one kappa and delta_t, so it
+///    is rebuilt whenever those change.
+pub struct SuperTimeStepper {
+   pub stages: u32,
+   pub kappa: f32,
+   pub delta_t: f32,
+   pub stage_pipeline: wgpu::ComputePipeline,
+   // stages rotate through the heat computer's midpoint and output buffers and this one
+   pub third_stage_buffer: wgpu::Buffer,
+   pub first_stage_delta_t_buffer: wgpu::Buffer,
+   pub coefficient_buffer: wgpu::Buffer,
+   pub first_stage_bind_group: wgpu::BindGroup,
+   // laplacian of each of the three rotating buffers, into midpoint_laplacian_buffer
+   pub laplacian_bind_groups: Vec<wgpu::BindGroup>,
+   // boundary conditions on each of the three rotating buffers, so each stage's laplacian
+   //    sees edges that match that stage
+   pub boundary_bind_groups: Vec<wgpu::BindGroup>,
+   pub stage_bind_groups: Vec<wgpu::BindGroup>,
+   pub move_bind_group: wgpu::BindGroup,
+}
+
+impl SuperTimeStepper {
+   pub fn new(heateq: &HeatComputer, device: &wgpu::Device, kappa: f32, delta_t: f32) -> Self {
+      let stages = rkl2_stage_count(delta_t, kappa, heateq.width, heateq.height);
+      let (first_mu_tilde, coefficients) = rkl2_coefficients(stages);
+      let tau_kappa = (delta_t * kappa) as f64;
+
+      let stage_shader = device.create_shader_module(wgpu::include_wgsl!("sts_stage.wgsl"));
+      let stage_pipeline = helper_basic_compute_shader(device, Some("RKL2 Stage Pipeline"), &stage_shader);
+
+      let third_stage_buffer = helper_compute_interim_data_buffer(
+         device, Some("rkl2 third stage buffer"), heateq.data_buffer.size()
+      );
+      let first_stage_delta_t_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("rkl2 first stage delta_t"),
+         contents: bytemuck::cast_slice(&[(first_mu_tilde * delta_t as f64) as f32]),
+         usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      });
+
+      let mut coefficient_bytes: Vec<u8> = vec![0; (coefficients.len().max(1) as u64 * COEFF_STRIDE) as usize];
+      for (n, [mu, nu, mu_tilde, gamma_tilde]) in coefficients.iter().enumerate() {
+         let stage: [f32; 4] = [*mu as f32, *nu as f32, (mu_tilde * tau_kappa) as f32, (gamma_tilde * tau_kappa) as f32];
+         let start = n * COEFF_STRIDE as usize;
+         coefficient_bytes[start..start + 16].copy_from_slice(bytemuck::cast_slice(&stage));
+      }
+      let coefficient_buffer = device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("rkl2 coefficients"),
+         contents: &coefficient_bytes,
+         usage: wgpu::BufferUsages::UNIFORM,
+      });
+
+      let rotating: [&wgpu::Buffer; 3] = [&heateq.midpoint_buffer, &heateq.output_buffer, &third_stage_buffer];
+      // Y_j lives in rotating[(j - 1) % 3], and Y_0 is the data buffer itself
+      let stage_buffer = |j: u32| -> &wgpu::Buffer {
+         if j == 0 {&heateq.data_buffer} else {rotating[((j - 1) % 3) as usize]}
+      };
+
+      // Y_1 = Y_0 + mu~_1 tau kappa laplacian(Y_0), which is just our iterate pipeline
+      let first_stage_bind_group = helper_compute_bind_group(
+         device, None, &heateq.iterate_pipeline,
+         &[&heateq.data_buffer, &heateq.laplacian_buffer, stage_buffer(1), &heateq.width_buffer,
+            &heateq.height_buffer, &heateq.kappa_buffer, &first_stage_delta_t_buffer]
+      );
+
+      let laplacian_bind_groups = rotating.iter().map(|buffer| helper_compute_bind_group(
+         device, None, &heateq.laplacian_pipeline,
+         &[buffer, &heateq.midpoint_laplacian_buffer, &heateq.width_buffer, &heateq.height_buffer, &heateq.grid_height_buffer]
+      )).collect();
+      let boundary_bind_groups = rotating.iter().map(|buffer| helper_compute_bind_group(
+         device, None, &heateq.fix_boundary_conditions_ppln,
+         &[buffer, &heateq.width_buffer, &heateq.height_buffer, &heateq.boundary_buffer]
+      )).collect();
+
+      let stage_bind_groups = (2..=stages).map(|j| {
+         let mut entries: Vec<wgpu::BindGroupEntry> = [
+            &heateq.data_buffer, &heateq.laplacian_buffer, stage_buffer(j - 1), stage_buffer(j - 2),
+            &heateq.midpoint_laplacian_buffer, stage_buffer(j), &heateq.width_buffer, &heateq.height_buffer
+         ].iter().enumerate().map(|(n, buffer)| wgpu::BindGroupEntry {
+            binding: n as u32,
+            resource: buffer.as_entire_binding()
+         }).collect();
+         entries.push(wgpu::BindGroupEntry {
+            binding: 8,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+               buffer: &coefficient_buffer,
+               offset: (j - 2) as u64 * COEFF_STRIDE,
+               size: wgpu::BufferSize::new(16),
+            })
+         });
+         device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &stage_pipeline.get_bind_group_layout(0),
+            entries: &entries
+         })
+      }).collect();
+
+      let move_bind_group = helper_compute_bind_group(
+         device, None, &heateq.buffer_move_pipeline,
+         &[stage_buffer(stages), &heateq.data_buffer, &heateq.width_buffer, &heateq.height_buffer]
+      );
+
+      Self {
+         stages,
+         kappa,
+         delta_t,
+         stage_pipeline,
+         third_stage_buffer,
+         first_stage_delta_t_buffer,
+         coefficient_buffer,
+         first_stage_bind_group,
+         laplacian_bind_groups,
+         boundary_bind_groups,
+         stage_bind_groups,
+         move_bind_group,
+      }
+   }
+
+   /// one super step of delta_t
+   pub fn record_step(&self, heateq: &HeatComputer, gputodo: &mut wgpu::ComputePass) {
+      let (row_workgroups, rows) = heateq.workgroup_quantity;
+      let x_workgroup_quantity = heateq.width.div_ceil(8);
+      let y_workgroup_quantity = heateq.height.div_ceil(8);
+      let boundary_conds_wg_quant = (heateq.width*2 + heateq.height*2).div_ceil(heateq.workgroup_size);
+
+      heateq.record_boundary_conditions(gputodo);
+
+      gputodo.set_pipeline(&heateq.laplacian_pipeline);
+      gputodo.set_bind_group(0, &heateq.stage_one_bind_group, &[]);
+      gputodo.dispatch_workgroups(x_workgroup_quantity, y_workgroup_quantity, 1);
+
+      gputodo.set_pipeline(&heateq.iterate_pipeline);
+      gputodo.set_bind_group(0, &self.first_stage_bind_group, &[]);
+      gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+
+      for j in 2..=self.stages {
+         gputodo.set_pipeline(&heateq.fix_boundary_conditions_ppln);
+         gputodo.set_bind_group(0, &self.boundary_bind_groups[((j - 2) % 3) as usize], &[]);
+         gputodo.dispatch_workgroups(boundary_conds_wg_quant, 1, 1);
+
+         gputodo.set_pipeline(&heateq.laplacian_pipeline);
+         gputodo.set_bind_group(0, &self.laplacian_bind_groups[((j - 2) % 3) as usize], &[]);
+         gputodo.dispatch_workgroups(x_workgroup_quantity, y_workgroup_quantity, 1);
+
+         gputodo.set_pipeline(&self.stage_pipeline);
+         gputodo.set_bind_group(0, &self.stage_bind_groups[(j - 2) as usize], &[]);
+         gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+      }
+
+      gputodo.set_pipeline(&heateq.buffer_move_pipeline);
+      gputodo.set_bind_group(0, &self.move_bind_group, &[]);
+      gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   // one super step on dy/dt = lambda y, with z = lambda delta_t, run through the same
+   //    recursion sts_stage.wgsl does
+   fn amplification(stages: u32, z: f64) -> f64 {
+      let (first_mu_tilde, coefficients) = rkl2_coefficients(stages);
+      let y0 = 1.;
+      let (mut prev2, mut prev) = (y0, y0 + first_mu_tilde * z * y0);
+      for [mu, nu, mu_tilde, gamma_tilde] in coefficients {
+         let next = mu * prev + nu * prev2 + (1. - mu - nu) * y0 + mu_tilde * z * prev + gamma_tilde * z * y0;
+         (prev2, prev) = (prev, next);
+      }
+      prev
+   }
+
+   #[test]
+   fn stage_count() {
+      // kappa 1 on 10x10 puts the explicit limit at 1/400
+      let stages = |ratio: f64| rkl2_stage_count((ratio / 400.) as f32, 1., 10, 10);
+      assert_eq!(stages(0.5), 2);
+      assert_eq!(stages(1.9), 3);
+      assert_eq!(stages(2.6), 4);
+      assert_eq!(stages(100.), 20);
+      // always the fewest stages whose (s^2 + s - 2)/4 covers the ratio
+      for ratio in [3., 7.3, 41., 250.] {
+         let s = stages(ratio) as f64;
+         assert!((s * s + s - 2.) / 4. >= ratio);
+         assert!(((s - 1.) * (s - 1.) + (s - 1.) - 2.) / 4. < ratio);
+      }
+   }
+
+   #[test]
+   fn coefficients_second_order_and_stable() {
+      for stages in [2, 3, 5, 9, 16] {
+         assert_eq!(rkl2_coefficients(stages).1.len(), stages as usize - 1);
+         // matches exp(z) up to z^2, so the error left is third order
+         for z in [-1e-2, -2e-2] {
+            let error = (amplification(stages, z) - (1. + z + z * z / 2.)).abs();
+            assert!(error < z.abs().powi(3), "{} stages at z = {}: {}", stages, z, error);
+         }
+         // and stays within 1 out to (s^2 + s - 2)/4 times euler's limit of z = -2
+         let s = stages as f64;
+         let reach = -(s * s + s - 2.) / 2.;
+         for n in 0..=200 {
+            let z = reach * n as f64 / 200.;
+            assert!(amplification(stages, z).abs() <= 1. + 1e-9, "{} stages grow at z = {}", stages, z);
+         }
+      }
+   }
+}
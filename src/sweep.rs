@@ -0,0 +1,371 @@
+use crate::analytic::AnalyticSolution;
+use crate::boundary::{BoundaryCondition, Edge};
+use crate::probes::Probe;
+use crate::rectgrid::RectGrid;
+use crate::runcontrol::{RunUntil, StopCondition};
+use crate::vtk::{VtkGeometry, parse_lengths};
+use crate::webgpuheat::{HeatComputer, Integrator};
+
+/// What a sweep writes about the field each run ends with. Everything but the probes is
+///    over the cells inside the edges, the ones that evolve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SweepMetric {
+   /// the integral of T, the same as interior_energy of EdgeFluxReport
+   Energy,
+   Peak,
+   Min,
+   Mean,
+   /// T at every probe, one column each
+   Probes,
+}
+
+impl std::str::FromStr for SweepMetric {
+   type Err = String;
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      match s {
+         "energy" => Ok(SweepMetric::Energy),
+         "peak" => Ok(SweepMetric::Peak),
+         "min" => Ok(SweepMetric::Min),
+         "mean" => Ok(SweepMetric::Mean),
+         "probes" => Ok(SweepMetric::Probes),
+         other => Err(format!("unknown metric {}, expected energy, peak, min, mean or probes", other))
+      }
+   }
+}
+
+/// how many steps a run with only steady or probe stops takes at most, so a sweep never
+///    hangs on a run that does not get there
+pub const FIELD_STOP_STEP_CAP: u64 = 100_000;
+
+/// Every value each parameter takes. A sweep runs every combination of them, each on its
+///    own computer from an analytic solution at t=0, and writes a row per run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepSpec {
+   pub kappa: Vec<f32>,
+   pub delta_t: Vec<f32>,
+   /// width and height
+   pub size: Vec<(u32, u32)>,
+   pub integrator: Vec<Integrator>,
+   /// what each edge does, in the order y=0, x=0, y=1, x=1
+   pub edges: [Vec<BoundaryCondition>; 4],
+   /// names AnalyticSolution::from_parts knows
+   pub init: Vec<String>,
+   /// values for each of the first few parameters of the solution, the rest take defaults
+   pub init_params: Vec<Vec<f64>>,
+   /// when each run stops, as for a controlled run
+   pub stop: RunUntil,
+   /// each probe's name and where it is in domain units
+   pub probes: Vec<(String, f64, f64)>,
+   /// the x and y lengths of the domain between the walls, for placing the probes
+   pub domain: (f64, f64),
+   pub metrics: Vec<SweepMetric>,
+}
+
+/// one combination out of a sweep
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepPoint {
+   pub kappa: f32,
+   pub delta_t: f32,
+   pub width: u32,
+   pub height: u32,
+   pub integrator: Integrator,
+   pub edges: [BoundaryCondition; 4],
+   pub init: String,
+   pub init_params: Vec<f64>,
+}
+
+impl Default for SweepSpec {
+   fn default() -> Self {
+      Self {
+         kappa: vec![1.],
+         delta_t: vec![1e-5],
+         size: vec![(128, 128)],
+         integrator: vec![Integrator::Rk2],
+         edges: [0; 4].map(|_| vec![BoundaryCondition::Insulated]),
+         init: vec!["gaussian".to_string()],
+         init_params: vec![],
+         stop: RunUntil { conditions: vec![StopCondition::Steps(1000)], check_every: 0 },
+         probes: vec![],
+         domain: (1., 1.),
+         metrics: vec![SweepMetric::Energy, SweepMetric::Peak, SweepMetric::Min, SweepMetric::Mean, SweepMetric::Probes],
+      }
+   }
+}
+
+/// Numbers separated by commas, where any of them can be a range `start..end/count` of
+///    count evenly spaced values, both ends included.
+pub fn parse_values(text: &str) -> Result<Vec<f64>, String> {
+   let mut values = Vec::new();
+   for item in text.split(',') {
+      match item.split_once("..") {
+         Some((start, rest)) => {
+            let (end, count) = rest.split_once('/').ok_or(format!("range {} needs a count, like 0..1/5", item))?;
+            let start: f64 = start.parse().map_err(|_| format!("bad range start in {}", item))?;
+            let end: f64 = end.parse().map_err(|_| format!("bad range end in {}", item))?;
+            let count: usize = count.parse().map_err(|_| format!("bad range count in {}", item))?;
+            match count {
+               0 => {}
+               1 => values.push(start),
+               _ => values.extend((0..count).map(|n| start + (end - start) * n as f64 / (count - 1) as f64)),
+            }
+         }
+         None => values.push(item.parse().map_err(|_| format!("{} is not a number", item))?),
+      }
+   }
+   Ok(values)
+}
+
+// "insulated" or a fixed temperature, numbers and ranges as in parse_values
+fn parse_conditions(text: &str) -> Result<Vec<BoundaryCondition>, String> {
+   let mut conditions = Vec::new();
+   for item in text.split(',') {
+      match item {
+         "insulated" => conditions.push(BoundaryCondition::Insulated),
+         _ => conditions.extend(parse_values(item)?.into_iter().map(|value| BoundaryCondition::Fixed(value as f32))),
+      }
+   }
+   Ok(conditions)
+}
+
+// 64 for a square, or 64x32
+fn parse_sizes(text: &str) -> Result<Vec<(u32, u32)>, String> {
+   text.split(',').map(|item| {
+      let (width, height) = item.split_once('x').unwrap_or((item, item));
+      match (width.parse::<u32>(), height.parse::<u32>()) {
+         (Ok(width), Ok(height)) if width >= 3 && height >= 3 => Ok((width, height)),
+         _ => Err(format!("{} is not a size, expected something like 64 or 64x32", item))
+      }
+   }).collect()
+}
+
+impl SweepSpec {
+   /// from `key=value` words, anything not given keeping its default:
+   ///    kappa, delta_t, p0..p3 take numbers and ranges, size takes 64 or 64x32,
+   ///    integrator and init take names, y0 x0 y1 x1 take insulated or temperatures,
+   ///    probe takes name:x:y in domain units and can be repeated, domain takes the lengths
+   ///    between the walls as <x length>x<y length> and is 1x1 unless given, metrics takes
+   ///    energy, peak, min, mean and probes. Runs stop on the first of steps=<n>, until=<t> and any stop=<condition> as
+   ///    `until` takes them, like stop=steady=1e-3 or stop=probe=a>0.5, checked every=<n>
+   ///    steps. Without any they stop after 1000 steps, and with neither steps nor until
+   ///    they also stop after FIELD_STOP_STEP_CAP steps.
+   pub fn from_args(args: &[&str]) -> Result<Self, String> {
+      let mut spec = Self::default();
+      let mut init_params: Vec<Option<Vec<f64>>> = vec![None; 4];
+      let mut stops = Vec::new();
+
+      for arg in args.iter() {
+         let (key, value) = arg.split_once('=').ok_or(format!("{} should look like key=value", arg))?;
+         let floats = |value: &str| -> Result<Vec<f32>, String> {
+            Ok(parse_values(value)?.into_iter().map(|value| value as f32).collect())
+         };
+         match key {
+            "kappa" => spec.kappa = floats(value)?,
+            "delta_t" => spec.delta_t = floats(value)?,
+            "size" => spec.size = parse_sizes(value)?,
+            "integrator" => spec.integrator = value.split(',').map(|x| x.parse()).collect::<Result<_, _>>()?,
+            "y0" | "x0" | "y1" | "x1" => spec.edges[key.parse::<Edge>()?.index()] = parse_conditions(value)?,
+            "init" => spec.init = value.split(',').map(|x| x.to_string()).collect(),
+            "p0" | "p1" | "p2" | "p3" => init_params[key[1..].parse::<usize>().unwrap()] = Some(parse_values(value)?),
+            "steps" => stops.push(StopCondition::Steps(value.parse().map_err(|_| format!("{} is not a step count", value))?)),
+            "until" => stops.push(StopCondition::Time(value.parse().map_err(|_| format!("{} is not a time", value))?)),
+            "stop" => stops.push(value.parse()?),
+            "every" => spec.stop.check_every = value.parse().map_err(|_| format!("{} is not a step count", value))?,
+            "probe" => {
+               let parts: Vec<&str> = value.split(':').collect();
+               match parts.as_slice() {
+                  [name, x, y] => match (x.parse::<f64>(), y.parse::<f64>()) {
+                     (Ok(x), Ok(y)) => spec.probes.push((name.to_string(), x, y)),
+                     _ => return Err(format!("probe {} should be name:x:y with x and y in domain units", value))
+                  },
+                  _ => return Err(format!("probe {} should be name:x:y with x and y in domain units", value))
+               }
+            }
+            "domain" => spec.domain = parse_lengths(value)?,
+            "metrics" => spec.metrics = value.split(',').map(|x| x.parse()).collect::<Result<_, _>>()?,
+            other => return Err(format!("unknown sweep parameter {}", other))
+         }
+      }
+
+      // the solution takes its parameters in order, so a later one needs the earlier ones
+      let given = init_params.iter().take_while(|params| params.is_some()).count();
+      if init_params[given..].iter().any(|params| params.is_some()) {
+         return Err(format!("p{} is missing, init parameters have to be given in order", given))
+      }
+      spec.init_params = init_params.into_iter().flatten().collect();
+      if !stops.is_empty() {spec.stop.conditions = stops}
+      if !spec.stop.conditions.iter().any(|condition| matches!(condition, StopCondition::Steps(_) | StopCondition::Time(_))) {
+         spec.stop.conditions.push(StopCondition::Steps(FIELD_STOP_STEP_CAP));
+      }
+      for condition in spec.stop.conditions.iter() {
+         if let StopCondition::ProbeAbove(name, _) | StopCondition::ProbeBelow(name, _) = condition
+            && !spec.probes.iter().any(|(probe, _, _)| probe == name) {
+            return Err(format!("stop {} needs a probe={}:x:y", condition, name))
+         }
+      }
+
+      let lists = [
+         spec.kappa.len(), spec.delta_t.len(), spec.size.len(), spec.integrator.len(), spec.init.len()
+      ];
+      if lists.contains(&0) || spec.edges.iter().any(|edge| edge.is_empty()) || spec.init_params.iter().any(|p| p.is_empty()) {
+         return Err("every parameter needs at least one value".to_string())
+      }
+      for name in spec.init.iter() {
+         AnalyticSolution::from_parts(name, &[])?;
+      }
+      Ok(spec)
+   }
+
+   /// every combination, the last parameters changing fastest
+   pub fn points(&self) -> Vec<SweepPoint> {
+      let mut lengths = vec![
+         self.kappa.len(), self.delta_t.len(), self.size.len(), self.integrator.len(), self.init.len()
+      ];
+      lengths.extend(self.edges.iter().map(|edge| edge.len()));
+      lengths.extend(self.init_params.iter().map(|params| params.len()));
+      let indices: Vec<Vec<usize>> = lengths.iter().map(|&length| (0..length).collect()).collect();
+
+      combinations(&indices).into_iter().map(|n| SweepPoint {
+         kappa: self.kappa[n[0]],
+         delta_t: self.delta_t[n[1]],
+         width: self.size[n[2]].0,
+         height: self.size[n[2]].1,
+         integrator: self.integrator[n[3]],
+         init: self.init[n[4]].clone(),
+         edges: [0, 1, 2, 3].map(|edge| self.edges[edge][n[5 + edge]]),
+         init_params: self.init_params.iter().enumerate().map(|(k, params)| params[n[9 + k]]).collect(),
+      }).collect()
+   }
+
+   fn header(&self) -> Vec<String> {
+      let mut header: Vec<String> = [
+         "kappa", "delta_t", "width", "height", "integrator", "y0", "x0", "y1", "x1", "init"
+      ].iter().map(|x| x.to_string()).collect();
+      header.extend((0..self.init_params.len()).map(|n| format!("p{}", n)));
+      header.extend(["steps".to_string(), "time".to_string()]);
+      for metric in self.metrics.iter() {
+         match metric {
+            SweepMetric::Energy => header.push("energy".to_string()),
+            SweepMetric::Peak => header.push("peak_T".to_string()),
+            SweepMetric::Min => header.push("min_T".to_string()),
+            SweepMetric::Mean => header.push("mean_T".to_string()),
+            SweepMetric::Probes => header.extend(self.probes.iter().map(|(name, _, _)| format!("probe_{}", name))),
+         }
+      }
+      header.push("error".to_string());
+      header
+   }
+
+   /// Runs every point in turn, writing each row as soon as its run is done so an
+   ///    interrupted sweep keeps what it got through. A run that fails gets a row with its
+   ///    parameters and the error, and the sweep goes on. `on_point` hears (done, total)
+   ///    and the error if there was one after every run.
+   pub async fn run<W: std::io::Write>(
+      &self,
+      device: &wgpu::Device,
+      queue: &wgpu::Queue,
+      output: W,
+      mut on_point: impl FnMut(usize, usize, Option<&anyhow::Error>),
+   ) -> anyhow::Result<()> {
+      let mut thewriter = csv::WriterBuilder::new()
+         .delimiter(b',')
+         .from_writer(output);
+      thewriter.write_record(self.header())?;
+      thewriter.flush()?;
+
+      let points = self.points();
+      for (n, point) in points.iter().enumerate() {
+         let outcome = self.run_point(device, queue, point).await;
+         thewriter.write_record(self.row(point, &outcome))?;
+         thewriter.flush()?;
+         on_point(n + 1, points.len(), outcome.as_ref().err());
+      }
+      Ok(())
+   }
+
+   async fn run_point(&self, device: &wgpu::Device, queue: &wgpu::Queue, point: &SweepPoint) -> anyhow::Result<(RectGrid, u64, f64)> {
+      HeatComputer::check_size(point.width, point.height, &device.limits())?;
+      let solution = AnalyticSolution::from_parts(&point.init, &point.init_params).map_err(anyhow::Error::msg)?;
+      let initial = solution.on_grid(point.width as usize, point.height as usize, 0., point.kappa as f64);
+
+      let mut heateq = HeatComputer::new(initial.getarray(), point.width, point.height, device);
+      heateq.set_integrator(point.integrator)?;
+      heateq.set_kappa(queue, point.kappa);
+      heateq.set_delta_t(queue, point.delta_t);
+      for (edge, condition) in Edge::ALL.iter().zip(point.edges) {
+         heateq.set_boundary_condition(queue, *edge, condition);
+      }
+
+      // the probes are read off the last field, these are only where the stop looks
+      if self.stop.conditions.iter().any(|condition| matches!(condition, StopCondition::ProbeAbove(..) | StopCondition::ProbeBelow(..))) {
+         heateq.set_probes(device, self.probes_on(point.width, point.height), u32::MAX, 1)?;
+      }
+      let report = heateq.run_controlled(device, queue, self.stop.clone())?;
+
+      Ok((heateq.read_field(device, queue).await?, report.steps, report.sim_time))
+   }
+
+   // the probes placed on a width x height grid over the domain
+   fn probes_on(&self, width: u32, height: u32) -> Vec<Probe> {
+      let geometry = VtkGeometry::solver_grid(width as usize, height as usize, self.domain.0, self.domain.1);
+      self.probes.iter().map(|(name, x, y)| Probe::in_domain(name, *x, *y, &geometry)).collect()
+   }
+
+   fn row(&self, point: &SweepPoint, outcome: &anyhow::Result<(RectGrid, u64, f64)>) -> Vec<String> {
+      let condition = |condition: BoundaryCondition| match condition {
+         BoundaryCondition::Insulated => "insulated".to_string(),
+         BoundaryCondition::Fixed(value) => format!("{}", value),
+      };
+      let integrator = match point.integrator {
+         Integrator::Rk2 => "rk2",
+         Integrator::Rkl2 => "rkl2",
+         Integrator::Adi => "adi",
+      };
+      let mut row = vec![
+         format!("{}", point.kappa), format!("{}", point.delta_t),
+         format!("{}", point.width), format!("{}", point.height), integrator.to_string(),
+      ];
+      row.extend(point.edges.map(condition));
+      row.push(point.init.clone());
+      row.extend(point.init_params.iter().map(|value| format!("{}", value)));
+      let (grid, steps, time) = match outcome {
+         Ok(outcome) => outcome,
+         Err(e) => {
+            // nothing to measure, so blanks up to the error column
+            row.resize(self.header().len() - 1, String::new());
+            row.push(format!("{}", e));
+            return row
+         }
+      };
+      row.extend([format!("{}", steps), format!("{}", time)]);
+
+      let (width, height) = (grid.width(), grid.height());
+      let interior: Vec<f32> = (1..height - 1)
+         .flat_map(|j| (1..width - 1).map(move |i| (i, j)))
+         .map(|(i, j)| grid.getelement(i, j))
+         .collect();
+      let sum: f64 = interior.iter().map(|&value| value as f64).sum();
+      for metric in self.metrics.iter() {
+         match metric {
+            SweepMetric::Energy => row.push(format!("{}", sum / (width * height) as f64)),
+            SweepMetric::Peak => row.push(format!("{}", interior.iter().copied().fold(f32::NEG_INFINITY, f32::max))),
+            SweepMetric::Min => row.push(format!("{}", interior.iter().copied().fold(f32::INFINITY, f32::min))),
+            SweepMetric::Mean => row.push(format!("{}", sum / interior.len() as f64)),
+            SweepMetric::Probes => row.extend(self.probes_on(point.width, point.height).iter()
+               .map(|probe| format!("{}", grid.samplebilinear(probe.x, probe.y)))),
+         }
+      }
+      row.push(String::new());
+      row
+   }
+}
+
+// every way of taking one value from each list, the last list changing fastest
+fn combinations<T: Copy>(lists: &[Vec<T>]) -> Vec<Vec<T>> {
+   lists.iter().fold(vec![vec![]], |combinations, list| {
+      combinations.iter().flat_map(|combination| list.iter().map(move |&value| {
+         let mut longer = combination.clone();
+         longer.push(value);
+         longer
+      })).collect()
+   })
+}
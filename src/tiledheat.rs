@@ -0,0 +1,233 @@
+use bytemuck::cast_slice;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::rectgrid::RectGrid;
+use crate::webgpuheat::{HeatComputer, helper_basic_compute_shader, helper_compute_bind_group};
+
+/// A grid too big for one storage binding, cut into strips of whole rows. Each strip is its
+///    own HeatComputer holding its rows plus one halo row above and below (except at the
+///    top and bottom of the grid, where the edge row is a real edge). The halo rows are
+///    swapped with the neighbouring strips before each laplacian, so a tiled step is the same
+///    RK2 step the untiled solver takes.
+pub struct HeatTiles {
+   pub width: u32,
+   pub height: u32,
+   pub tiles: Vec<HeatTile>,
+   pub halo_pipeline: wgpu::ComputePipeline,
+   // one per pair of neighbouring tiles, for their data and their midpoint buffers
+   pub data_halo_bind_groups: Vec<wgpu::BindGroup>,
+   pub midpoint_halo_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+pub struct HeatTile {
+   pub computer: HeatComputer,
+   // first grid row this tile owns, and how many it owns
+   pub first_row: u32,
+   pub owned_rows: u32,
+   // 1 if local row 0 is a halo from the tile above, 0 if it is the top of the grid
+   pub halo_above: u32,
+   pub halo_below: u32,
+}
+
+impl HeatTile {
+   pub fn rows(&self) -> u32 {
+      self.halo_above + self.owned_rows + self.halo_below
+   }
+}
+
+impl HeatTiles {
+   /// the most rows a tile of a grid this wide can own under these limits, leaving room for
+   ///    its two halo rows
+   pub fn rows_per_tile(width: u32, limits: &wgpu::Limits) -> anyhow::Result<u32> {
+      let rows = HeatComputer::max_cells(limits) / width as u64;
+      // the rows are spread evenly, and four to a tile leaves every tile at least two
+      if rows < 6 {
+         anyhow::bail!("a row {} wide is too long to tile, a tile has to fit six rows in one buffer", width)
+      }
+      Ok((rows - 2).min(u32::MAX as u64) as u32)
+   }
+
+   pub fn new(
+      initial_data: &[f32],
+      width: u32,
+      height: u32,
+      rows_per_tile: u32,
+      device: &wgpu::Device,
+   ) -> anyhow::Result<Self> {
+      assert_eq!(initial_data.len() as u32, width*height);
+      if rows_per_tile == 0 {
+         anyhow::bail!("tiles must own at least one row");
+      }
+
+      // spread the rows evenly, so we never end up with a sliver of a last tile
+      let tile_count = height.div_ceil(rows_per_tile);
+      let mut tiles = Vec::new();
+      let mut first_row = 0;
+      for n in 0..tile_count {
+         let owned_rows = height / tile_count + if n < height % tile_count {1} else {0};
+         // the edge rows copy the row next to them, which has to be one of ours
+         if owned_rows < 2 {
+            anyhow::bail!("a {} row grid cut into {} tiles leaves tiles of fewer than two rows", height, tile_count);
+         }
+         let halo_above = if first_row > 0 {1} else {0};
+         let halo_below = if first_row + owned_rows < height {1} else {0};
+
+         let start = ((first_row - halo_above) * width) as usize;
+         let end = ((first_row + owned_rows + halo_below) * width) as usize;
+         let computer = HeatComputer::new_with_grid_height(
+            &initial_data[start..end].to_vec(), width, halo_above + owned_rows + halo_below, height, device
+         );
+
+         tiles.push(HeatTile {computer, first_row, owned_rows, halo_above, halo_below});
+         first_row += owned_rows;
+      }
+
+      let halo_shader = device.create_shader_module(wgpu::include_wgsl!("halo_exchange.wgsl"));
+      let halo_pipeline = helper_basic_compute_shader(device, Some("Halo Exchange Pipeline"), &halo_shader);
+      let mut data_halo_bind_groups = Vec::new();
+      let mut midpoint_halo_bind_groups = Vec::new();
+      for pair in tiles.windows(2) {
+         let (above, below) = (&pair[0].computer, &pair[1].computer);
+         let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("halo params"),
+            contents: cast_slice(&[width, pair[0].rows(), 0, 0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+         });
+         data_halo_bind_groups.push(helper_compute_bind_group(
+            device, None, &halo_pipeline, &[&above.data_buffer, &below.data_buffer, &param_buffer]
+         ));
+         midpoint_halo_bind_groups.push(helper_compute_bind_group(
+            device, None, &halo_pipeline, &[&above.midpoint_buffer, &below.midpoint_buffer, &param_buffer]
+         ));
+      }
+
+      Ok(Self {width, height, tiles, halo_pipeline, data_halo_bind_groups, midpoint_halo_bind_groups})
+   }
+
+   fn record_halo_exchange(&self, gputodo: &mut wgpu::ComputePass, bind_groups: &[wgpu::BindGroup]) {
+      gputodo.set_pipeline(&self.halo_pipeline);
+      for bind_group in bind_groups {
+         gputodo.set_bind_group(0, bind_group, &[]);
+         gputodo.dispatch_workgroups(self.width.div_ceil(64), 1, 1);
+      }
+   }
+
+   /// one RK2 step of every tile. The boundary shader treats halo rows as edges, so the
+   ///    halos are swapped after each time it runs.
+   pub fn record_step(&self, gputodo: &mut wgpu::ComputePass) {
+      for tile in self.tiles.iter() {
+         tile.computer.record_boundary_conditions(gputodo);
+      }
+      self.record_halo_exchange(gputodo, &self.data_halo_bind_groups);
+      for tile in self.tiles.iter() {
+         tile.computer.record_midpoint_half(gputodo);
+      }
+      self.record_halo_exchange(gputodo, &self.midpoint_halo_bind_groups);
+      for tile in self.tiles.iter() {
+         tile.computer.record_final_half(gputodo);
+      }
+   }
+
+   /// colors every tile into its own heatmap buffer
+   pub fn record_color(&self, encoder: &mut wgpu::CommandEncoder) {
+      for tile in self.tiles.iter() {
+         tile.computer.unsafe_queue_color_job(encoder);
+      }
+   }
+
+   /// copies the rows each tile owns out of its heatmap into their place in `texture`,
+   ///    which is the size of the whole grid
+   pub fn record_copy_to_texture(&self, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+      let bytes_per_row = 256 * self.width.div_ceil(64);
+      for tile in self.tiles.iter() {
+         encoder.copy_buffer_to_texture(
+            wgpu::TexelCopyBufferInfo {
+               buffer: &tile.computer.heat_map_buffer,
+               layout: wgpu::TexelCopyBufferLayout {
+                  offset: (tile.halo_above * bytes_per_row) as u64,
+                  bytes_per_row: Some(bytes_per_row),
+                  rows_per_image: Some(tile.owned_rows)
+               }
+            },
+            wgpu::TexelCopyTextureInfo {
+               aspect: wgpu::TextureAspect::All,
+               texture,
+               mip_level: 0,
+               origin: wgpu::Origin3d { x: 0, y: tile.first_row, z: 0 }
+            },
+            wgpu::Extent3d { width: self.width, height: tile.owned_rows, depth_or_array_layers: 1 }
+         );
+      }
+   }
+
+   /// reads the whole field back a tile at a time, since it may not fit in any one buffer
+   pub async fn read_field(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<RectGrid> {
+      let mut field: Vec<f32> = Vec::with_capacity(self.width as usize * self.height as usize);
+      for tile in self.tiles.iter() {
+         let strip = tile.computer.read_buffer_as_grid(device, queue, &tile.computer.data_buffer).await?;
+         let start = (tile.halo_above * self.width) as usize;
+         let end = start + (tile.owned_rows * self.width) as usize;
+         field.extend_from_slice(&strip.getarray()[start..end]);
+      }
+      Ok(RectGrid::newfromdata(self.width as usize, self.height as usize, field))
+   }
+
+   /// overwrites the field, halo rows and all
+   pub fn write_field(&self, queue: &wgpu::Queue, grid: &RectGrid) -> anyhow::Result<()> {
+      for tile in self.tiles.iter() {
+         let start = ((tile.first_row - tile.halo_above) * self.width) as usize;
+         let end = start + (tile.rows() * self.width) as usize;
+         let strip = RectGrid::newfromdata(self.width as usize, tile.rows() as usize, grid.getarray()[start..end].to_vec());
+         tile.computer.write_field(queue, &strip)?;
+      }
+      Ok(())
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::analytic::AnalyticSolution;
+   use crate::boundary::{BoundaryCondition, Edge};
+   use crate::wgpuworkhorse::headless_device;
+
+   // the same steps tiled and untiled, with a fixed edge on the top and bottom so heat crosses
+   //    the seams both ways
+   #[tokio::test]
+   async fn tiled_steps_match_untiled() {
+      let Ok((device, queue)) = headless_device().await else {
+         println!("no adapter, skipping");
+         return
+      };
+      let (width, height) = (37u32, 29u32);
+      let initial = AnalyticSolution::from_parts("gaussian", &[0.15, 10., 0.4, 0.55]).unwrap()
+         .on_grid(width as usize, height as usize, 0., 1.);
+
+      let mut untiled = HeatComputer::new(initial.getarray(), width, height, &device);
+      let mut tiled = HeatComputer::new_tiled(initial.getarray(), width, height, 8, &device).unwrap();
+      assert_eq!(tiled.tiling.as_ref().unwrap().tiles.len(), 4);
+
+      let delta_t = 1. / (4. * (width.pow(2) + height.pow(2)) as f32);
+      for heateq in [&mut untiled, &mut tiled] {
+         heateq.update_values(&queue, 50, 1., delta_t, 0., 10.);
+         heateq.set_boundary_condition(&queue, Edge::Y0, BoundaryCondition::Fixed(4.));
+         heateq.set_boundary_condition(&queue, Edge::Y1, BoundaryCondition::Fixed(-2.));
+         let mut pending_queue = Vec::new();
+         heateq.send_compute_job(&mut pending_queue, &device);
+         heateq.submit(&queue, pending_queue);
+      }
+
+      let expected = untiled.read_field(&device, &queue).await.unwrap();
+      let got = tiled.read_field(&device, &queue).await.unwrap();
+      assert_eq!(got.getarray(), expected.getarray());
+      assert_eq!(tiled.sim_time, untiled.sim_time);
+      assert!(tiled.field_stats(&device, &queue).await.is_err());
+   }
+
+   #[test]
+   fn rows_per_tile_leaves_room_for_halos() {
+      let limits = wgpu::Limits {max_storage_buffer_binding_size: 4 * 100, max_buffer_size: 4 * 100, ..Default::default()};
+      assert_eq!(HeatTiles::rows_per_tile(10, &limits).unwrap(), 8);
+      assert!(HeatTiles::rows_per_tile(20, &limits).is_err());
+   }
+}
@@ -0,0 +1,31 @@
+struct PcrParams {
+   n: u32,
+   batch: u32,
+   elem_stride: u32,
+   sys_stride: u32,
+   stride: u32,
+   _pad0: u32,
+   _pad1: u32,
+   _pad2: u32,
+}
+
+@group(0) @binding(0) var<storage, read> src: array<vec4<f32>>;
+@group(0) @binding(1) var<storage, read_write> solution: array<f32>;
+@group(0) @binding(2) var<uniform> params: PcrParams;
+
+// invocations in a row of workgroups, see linear_workgroups in webgpuheat.rs
+const ROW_LENGTH: u32 = 4194240u;
+
+// once cyclic reduction has run its course every equation is just b x = d
+@compute
+@workgroup_size(64,1,1)
+fn main(
+   @builtin(global_invocation_id) gid: vec3<u32>
+) {
+   let n = gid.x + gid.y * ROW_LENGTH;
+   if (n >= params.n * params.batch) {return;}
+
+   let index = (n / params.n) * params.sys_stride + (n % params.n) * params.elem_stride;
+   let equation = src[index];
+   solution[index] = equation.w / equation.y;
+}
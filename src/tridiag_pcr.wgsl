@@ -0,0 +1,55 @@
+// where a batch of tridiagonal systems lives in the buffers, and how far apart the
+//    equations we combine are on this pass of parallel cyclic reduction
+struct PcrParams {
+   n: u32,
+   batch: u32,
+   elem_stride: u32,
+   sys_stride: u32,
+   stride: u32,
+   _pad0: u32,
+   _pad1: u32,
+   _pad2: u32,
+}
+
+// each equation is (a, b, c, d) meaning a x_{k-1} + b x_k + c x_{k+1} = d
+@group(0) @binding(0) var<storage, read> src: array<vec4<f32>>;
+@group(0) @binding(1) var<storage, read_write> dst: array<vec4<f32>>;
+@group(0) @binding(2) var<uniform> params: PcrParams;
+
+// invocations in a row of workgroups, see linear_workgroups in webgpuheat.rs
+const ROW_LENGTH: u32 = 4194240u;
+
+fn index_of(system: u32, k: u32) -> u32 {
+   return system * params.sys_stride + k * params.elem_stride;
+}
+
+@compute
+@workgroup_size(64,1,1)
+fn main(
+   @builtin(global_invocation_id) gid: vec3<u32>
+) {
+   let equation = gid.x + gid.y * ROW_LENGTH;
+   if (equation >= params.n * params.batch) {return;}
+
+   let system = equation / params.n;
+   let k = equation % params.n;
+   let me = src[index_of(system, k)];
+
+   // past either end of the system we pretend there is the equation x = 0
+   var lower = vec4<f32>(0.0f, 1.0f, 0.0f, 0.0f);
+   var upper = vec4<f32>(0.0f, 1.0f, 0.0f, 0.0f);
+   if (k >= params.stride) {lower = src[index_of(system, k - params.stride)];}
+   if (k + params.stride < params.n) {upper = src[index_of(system, k + params.stride)];}
+
+   // eliminate x_{k-stride} and x_{k+stride} from our equation using our neighbours, which
+   //    leaves us coupled to x_{k-2 stride} and x_{k+2 stride} instead
+   let alpha = -me.x / lower.y;
+   let gamma = -me.z / upper.y;
+
+   dst[index_of(system, k)] = vec4<f32>(
+      alpha * lower.x,
+      me.y + alpha * lower.z + gamma * upper.x,
+      gamma * upper.z,
+      me.w + alpha * lower.w + gamma * upper.w
+   );
+}
@@ -0,0 +1,132 @@
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::webgpuheat::{helper_basic_compute_shader, helper_compute_bind_group, linear_workgroups};
+
+/// Where `batch` tridiagonal systems of `n` equations sit in a buffer. Equation k of
+///    system s is at s * sys_stride + k * elem_stride, so rows of a width x height grid
+///    are (width, height, 1, width) and columns are (height, width, width, 1).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TridiagonalLayout {
+   pub n: u32,
+   pub batch: u32,
+   pub elem_stride: u32,
+   pub sys_stride: u32,
+}
+
+impl TridiagonalLayout {
+   pub fn rows(width: u32, height: u32) -> Self {
+      Self { n: width, batch: height, elem_stride: 1, sys_stride: width }
+   }
+
+   pub fn columns(width: u32, height: u32) -> Self {
+      Self { n: height, batch: width, elem_stride: width, sys_stride: 1 }
+   }
+
+   pub fn equation_count(&self) -> u32 {self.n * self.batch}
+
+   fn params(&self, stride: u32) -> [u32; 8] {
+      [self.n, self.batch, self.elem_stride, self.sys_stride, stride, 0, 0, 0]
+   }
+}
+
+/// Solves a whole batch of tridiagonal systems at once with parallel cyclic reduction.
+///    Fill system_buffer with one vec4 (a, b, c, d) per equation, meaning
+///    a x_{k-1} + b x_k + c x_{k+1} = d (a of the first and c of the last equation being 0),
+///    then record_solve writes every x into the output buffer at the same index.
+///    Each of the ceil(log2 n) passes halves how far apart coupled equations are, so the
+///    systems are decoupled at the end. There is no pivoting, so the systems should be
+///    diagonally dominant, which ours always are.
+pub struct BatchedTridiagonalSolver {
+   pub layout: TridiagonalLayout,
+   pub pass_count: u32,
+   pub pcr_pipeline: wgpu::ComputePipeline,
+   pub finish_pipeline: wgpu::ComputePipeline,
+   pub system_buffer: wgpu::Buffer,
+   pub scratch_buffer: wgpu::Buffer,
+   pub param_buffers: Vec<wgpu::Buffer>,
+   pub pass_bind_groups: Vec<wgpu::BindGroup>,
+   pub finish_bind_group: wgpu::BindGroup,
+}
+
+impl BatchedTridiagonalSolver {
+   pub fn new(device: &wgpu::Device, layout: TridiagonalLayout, output: &wgpu::Buffer) -> Self {
+      let size = layout.equation_count() as u64 * 16;
+      let system_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("tridiagonal systems"),
+         size,
+         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+         mapped_at_creation: false,
+      });
+      let scratch_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("tridiagonal scratch"),
+         size,
+         usage: wgpu::BufferUsages::STORAGE,
+         mapped_at_creation: false,
+      });
+      Self::new_sharing(device, layout, &system_buffer, &scratch_buffer, output)
+   }
+
+   /// for solving systems of another layout in the same buffers as some other solver,
+   ///    which is fine so long as the two are never in flight at once.
+   pub fn new_sharing(
+      device: &wgpu::Device,
+      layout: TridiagonalLayout,
+      system_buffer: &wgpu::Buffer,
+      scratch_buffer: &wgpu::Buffer,
+      output: &wgpu::Buffer,
+   ) -> Self {
+      assert!(system_buffer.size() >= layout.equation_count() as u64 * 16);
+
+      let pcr_shader = device.create_shader_module(wgpu::include_wgsl!("tridiag_pcr.wgsl"));
+      let pcr_pipeline = helper_basic_compute_shader(device, Some("PCR Pipeline"), &pcr_shader);
+      let finish_shader = device.create_shader_module(wgpu::include_wgsl!("tridiag_finish.wgsl"));
+      let finish_pipeline = helper_basic_compute_shader(device, Some("PCR Finish Pipeline"), &finish_shader);
+
+      let pass_count = layout.n.max(1).next_power_of_two().trailing_zeros();
+
+      let param_buffers: Vec<wgpu::Buffer> = (0..=pass_count).map(|pass| {
+         device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("pcr params"),
+            contents: bytemuck::cast_slice(&layout.params(1 << pass)),
+            usage: wgpu::BufferUsages::UNIFORM,
+         })
+      }).collect();
+
+      // ping pong between the two buffers, starting from the systems
+      let buffers = [system_buffer, scratch_buffer];
+      let pass_bind_groups = (0..pass_count).map(|pass| helper_compute_bind_group(
+         device, None, &pcr_pipeline,
+         &[buffers[(pass % 2) as usize], buffers[((pass + 1) % 2) as usize], &param_buffers[pass as usize]]
+      )).collect();
+      let finish_bind_group = helper_compute_bind_group(
+         device, None, &finish_pipeline,
+         &[buffers[(pass_count % 2) as usize], output, &param_buffers[pass_count as usize]]
+      );
+
+      Self {
+         layout,
+         pass_count,
+         pcr_pipeline,
+         finish_pipeline,
+         system_buffer: system_buffer.clone(),
+         scratch_buffer: scratch_buffer.clone(),
+         param_buffers,
+         pass_bind_groups,
+         finish_bind_group,
+      }
+   }
+
+   pub fn record_solve(&self, gputodo: &mut wgpu::ComputePass) {
+      let (row_workgroups, rows) = linear_workgroups(self.layout.equation_count());
+
+      for bind_group in self.pass_bind_groups.iter() {
+         gputodo.set_pipeline(&self.pcr_pipeline);
+         gputodo.set_bind_group(0, bind_group, &[]);
+         gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+      }
+
+      gputodo.set_pipeline(&self.finish_pipeline);
+      gputodo.set_bind_group(0, &self.finish_bind_group, &[]);
+      gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+   }
+}
@@ -0,0 +1,27 @@
+// counts of the field's values in bins evenly spaced between its min and max,
+//    which stats_final.wgsl has already left in stats
+
+@group(0) @binding(0) var<storage, read> field: array<f32>;
+@group(0) @binding(1) var<storage, read> stats: array<f32>;
+@group(0) @binding(2) var<storage, read_write> bins: array<atomic<u32>>;
+@group(0) @binding(3) var<uniform> cells: u32;
+
+// invocations in a row of workgroups, see linear_workgroups in webgpuheat.rs
+const ROW_LENGTH: u32 = 4194240u;
+
+@compute
+@workgroup_size(64,1,1)
+fn main(
+   @builtin(global_invocation_id) gid: vec3<u32>
+) {
+   let cell = gid.x + gid.y * ROW_LENGTH;
+   if (cell >= cells) {return;}
+
+   let low = stats[1];
+   let high = stats[2];
+   let bin_count = arrayLength(&bins);
+   // a flat field all lands in the first bin
+   let fraction = select(0.0, (field[cell] - low) / (high - low), high > low);
+   let bin = min(u32(max(fraction, 0.0) * f32(bin_count)), bin_count - 1u);
+   atomicAdd(&bins[bin], 1u);
+}
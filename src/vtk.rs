@@ -0,0 +1,179 @@
+use crate::rectgrid::RectGrid;
+
+/// Where the points of a width x height grid sit for VTK. Cells are where solver_position puts
+///    them, stretched onto a domain x_length by y_length with the walls at 0 and the lengths,
+///    so the edge cells sit half a cell outside it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VtkGeometry {
+   pub width: usize,
+   pub height: usize,
+   pub origin: (f64, f64),
+   pub spacing: (f64, f64),
+}
+
+impl VtkGeometry {
+   pub fn solver_grid(width: usize, height: usize, x_length: f64, y_length: f64) -> Self {
+      let spacing = (x_length / (width - 2) as f64, y_length / (height - 2) as f64);
+      Self { width, height, origin: (-0.5 * spacing.0, -0.5 * spacing.1), spacing }
+   }
+
+   /// a point in domain units in RectGrid's [0,1]x[0,1] coordinates, which run from the
+   ///    first cell to the last
+   pub fn grid_coordinates(&self, x: f64, y: f64) -> (f32, f32) {
+      (((x - self.origin.0) / self.spacing.0 / (self.width - 1) as f64) as f32,
+         ((y - self.origin.1) / self.spacing.1 / (self.height - 1) as f64) as f32)
+   }
+}
+
+/// the lengths of a domain as <x_length>x<y_length>
+#[cfg(not(target_arch = "wasm32"))]
+pub fn parse_lengths(s: &str) -> Result<(f64, f64), String> {
+   let (x_length, y_length) = s.split_once('x').ok_or(format!("{} should be <x length>x<y length>", s))?;
+   let x_length: f64 = x_length.parse().map_err(|_| format!("{} is not a length", x_length))?;
+   let y_length: f64 = y_length.parse().map_err(|_| format!("{} is not a length", y_length))?;
+   if x_length <= 0. || y_length <= 0. {return Err("a domain needs lengths above 0".to_string())}
+   Ok((x_length, y_length))
+}
+
+/// One grid of scalars and vectors as a VTK ImageData .vti, point data in binary base64 as
+///    ParaView writes it, x fastest as RectGrid already is. The first scalar and vector are
+///    the active ones. Vectors get a zero z so ParaView can glyph them.
+pub fn write_vti(geometry: &VtkGeometry, scalars: &[(&str, &RectGrid)], vectors: &[(&str, &RectGrid, &RectGrid)]) -> anyhow::Result<Vec<u8>> {
+   let (width, height) = (geometry.width, geometry.height);
+   let grids = scalars.iter().map(|(name, grid)| (name, *grid))
+      .chain(vectors.iter().flat_map(|(name, x, y)| [(name, *x), (name, *y)]));
+   for (name, grid) in grids {
+      if (grid.width(), grid.height()) != (width, height) {
+         anyhow::bail!("{} is {}x{}, the image is {}x{}", name, grid.width(), grid.height(), width, height)
+      }
+   }
+
+   let extent = format!("0 {} 0 {} 0 0", width - 1, height - 1);
+   let mut xml = String::new();
+   xml.push_str("<?xml version=\"1.0\"?>\n");
+   xml.push_str("<VTKFile type=\"ImageData\" version=\"1.0\" byte_order=\"LittleEndian\" header_type=\"UInt32\">\n");
+   xml.push_str(&format!("  <ImageData WholeExtent=\"{}\" Origin=\"{} {} 0\" Spacing=\"{} {} 1\">\n",
+      extent, geometry.origin.0, geometry.origin.1, geometry.spacing.0, geometry.spacing.1));
+   xml.push_str(&format!("    <Piece Extent=\"{}\">\n", extent));
+   let mut active = String::new();
+   if let Some((name, _)) = scalars.first() {active.push_str(&format!(" Scalars=\"{}\"", name))}
+   if let Some((name, _, _)) = vectors.first() {active.push_str(&format!(" Vectors=\"{}\"", name))}
+   xml.push_str(&format!("      <PointData{}>\n", active));
+
+   for (name, grid) in scalars.iter() {
+      xml.push_str(&data_array(name, 1, grid.getarray().iter().copied()));
+   }
+   for (name, x, y) in vectors.iter() {
+      let components = x.getarray().iter().zip(y.getarray()).flat_map(|(&x, &y)| [x, y, 0.]);
+      xml.push_str(&data_array(name, 3, components));
+   }
+
+   xml.push_str("      </PointData>\n");
+   xml.push_str("    </Piece>\n");
+   xml.push_str("  </ImageData>\n");
+   xml.push_str("</VTKFile>\n");
+   Ok(xml.into_bytes())
+}
+
+// a Float32 DataArray, base64 of the byte count as a UInt32 and then the values
+fn data_array(name: &str, components: u32, values: impl Iterator<Item = f32>) -> String {
+   let mut bytes = vec![0u8; 4];
+   for value in values {
+      bytes.extend_from_slice(&value.to_le_bytes());
+   }
+   let count = (bytes.len() - 4) as u32;
+   bytes[..4].copy_from_slice(&count.to_le_bytes());
+   format!("        <DataArray type=\"Float32\" Name=\"{}\" NumberOfComponents=\"{}\" format=\"binary\">\n          {}\n        </DataArray>\n",
+      name, components, base64(&bytes))
+}
+
+fn base64(bytes: &[u8]) -> String {
+   const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+   let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+   for chunk in bytes.chunks(3) {
+      let word = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+      for n in 0..4 {
+         match n <= chunk.len() {
+            true => text.push(ALPHABET[(word >> (18 - 6 * n) & 63) as usize] as char),
+            false => text.push('='),
+         }
+      }
+   }
+   text
+}
+
+/// The .vti files of a run, for a .pvd collection that loads them in ParaView with a time
+///    slider. Files are named from the prefix and their number, and listed in the .pvd
+///    without the prefix's folders, so the .pvd goes next to them.
+pub struct VtkSeries {
+   pub prefix: String,
+   pub frames: Vec<(f64, String)>,
+}
+
+impl VtkSeries {
+   pub fn new(prefix: &str) -> Self {
+      Self { prefix: prefix.to_string(), frames: Vec::new() }
+   }
+
+   /// notes a frame at `time` and gives back the path to write it to
+   pub fn add(&mut self, time: f64) -> String {
+      let path = format!("{}_{:04}.vti", self.prefix, self.frames.len());
+      self.frames.push((time, path.clone()));
+      path
+   }
+
+   pub fn pvd_path(&self) -> String {
+      format!("{}.pvd", self.prefix)
+   }
+
+   pub fn as_pvd(&self) -> String {
+      let mut xml = String::new();
+      xml.push_str("<?xml version=\"1.0\"?>\n");
+      xml.push_str("<VTKFile type=\"Collection\" version=\"0.1\" byte_order=\"LittleEndian\">\n");
+      xml.push_str("  <Collection>\n");
+      for (time, path) in self.frames.iter() {
+         let file = std::path::Path::new(path).file_name().map_or(path.clone(), |file| file.to_string_lossy().to_string());
+         xml.push_str(&format!("    <DataSet timestep=\"{}\" group=\"\" part=\"0\" file=\"{}\"/>\n", time, file));
+      }
+      xml.push_str("  </Collection>\n");
+      xml.push_str("</VTKFile>\n");
+      xml
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn base64_known_vectors() {
+      // the test vectors of RFC 4648
+      for (plain, encoded) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")] {
+         assert_eq!(base64(plain.as_bytes()), encoded);
+      }
+      assert_eq!(base64(&[0xff, 0xfe, 0xfd, 0x00]), "//79AA==");
+   }
+
+   #[test]
+   fn data_array_counts_bytes() {
+      // a UInt32 byte count of 8, then 1.0 and 2.0 as little endian f32
+      let xml = data_array("T", 1, [1f32, 2.].into_iter());
+      assert!(xml.contains(&base64(&[8, 0, 0, 0, 0, 0, 0x80, 0x3f, 0, 0, 0, 0x40])));
+   }
+
+   #[test]
+   fn vti_checks_sizes() {
+      let geometry = VtkGeometry::solver_grid(4, 3, 2., 1.);
+      assert_eq!(geometry.spacing, (1., 1.));
+      assert_eq!(geometry.origin, (-0.5, -0.5));
+      // the walls sit half a cell in from the first and last cells
+      assert_eq!(geometry.grid_coordinates(0., 0.), (1. / 6., 0.25));
+      assert_eq!(geometry.grid_coordinates(2., 1.), (5. / 6., 0.75));
+      let field = RectGrid::newfromdata(4, 3, vec![0.; 12]);
+      let small = RectGrid::newfromdata(3, 3, vec![0.; 9]);
+      let xml = String::from_utf8(write_vti(&geometry, &[("T", &field)], &[("flux", &field, &field)]).unwrap()).unwrap();
+      assert!(xml.contains("WholeExtent=\"0 3 0 2 0 0\""));
+      assert!(xml.contains("<PointData Scalars=\"T\" Vectors=\"flux\">"));
+      assert!(write_vti(&geometry, &[("T", &small)], &[]).is_err());
+   }
+}
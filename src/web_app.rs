@@ -36,6 +36,59 @@ thread_local! {
    pub static CSV_BUFFER : RefCell<Option<(Vec<f32>, u32, u32)>> = RefCell::new(None);
 }
 
+// the exact solution the field was last started from, for check_analytic to compare against
+thread_local! {
+   pub static ANALYTIC_SOLUTION : RefCell<Option<crate::analytic::AnalyticSolution>> = RefCell::new(None);
+}
+
+// the statistics of the last run_monte_carlo, for get_monte_carlo_csv
+thread_local! {
+   pub static MONTE_CARLO : RefCell<Option<crate::montecarlo::MonteCarloReport>> = RefCell::new(None);
+}
+
+// shared by every state made, so the listeners subscribe_events adds survive a resize. The
+//    stream's own listeners have to be Send, so one of them hands events on to the js ones.
+thread_local! {
+   pub static WEB_EVENTS : crate::events::EventStream = {
+      let events = crate::events::EventStream::default();
+      events.subscribe(Box::new(tell_js_listeners));
+      events
+   };
+}
+
+thread_local! {
+   pub static JS_LISTENERS : RefCell<(u32, Vec<(u32, js_sys::Function)>)> = RefCell::new((0, Vec::new()));
+}
+
+fn tell_js_listeners(event: &crate::webgpuheat::ComputeRelevantEvent) {
+   let name = JsValue::from_str(event.name());
+   let detail = match event {
+      crate::webgpuheat::ComputeRelevantEvent::Error(message) => JsValue::from_str(message),
+      event => event.submission().map_or(JsValue::UNDEFINED, |n| JsValue::from_f64(n as f64))
+   };
+   let listeners: Vec<js_sys::Function> = JS_LISTENERS.with_borrow(|(_, listeners)| {
+      listeners.iter().map(|(_, listener)| listener.clone()).collect()
+   });
+   // after whatever wasm call caused it has returned, so a listener is free to call back in
+   wasm_bindgen_futures::spawn_local(async move {
+      for listener in listeners {
+         if let Err(e) = listener.call2(&JsValue::NULL, &name, &detail) {
+            log::info!("event listener threw {:?}", e);
+         }
+      }
+   });
+}
+
+// whoever is awaiting run_until, told by run_a_chunk when the controlled run ends
+thread_local! {
+   pub static RUN_UNTIL_DONE : RefCell<Option<tokio::sync::oneshot::Sender<crate::runcontrol::RunReport>>> = RefCell::new(None);
+}
+
+// the .vti frames taken so far for a .pvd, by add_vtk_frame
+thread_local! {
+   pub static VTK_SERIES : RefCell<Option<crate::vtk::VtkSeries>> = RefCell::new(None);
+}
+
 // Now expose all wgpu heat equation and rendering functionality to javascript
 #[wasm_bindgen]
 pub fn update_values(
@@ -111,8 +164,28 @@ pub fn run_a_compute_iter() -> Result<(), JsValue> {
    Ok(())
 }
 
+/// calls `listener(name, detail)` for every event from now on, where name is one of submitted,
+///    compute_done, color_done, copy_done, readback_ready or error, and detail is the
+///    submission number, or the message for an error. Calls happen after whatever wasm call
+///    caused them has returned, so a listener is free to call back in. Gives back an id for
+///    unsubscribe_events.
 #[wasm_bindgen]
-pub fn render_a_frame() -> Result<(), JsValue> {
+pub fn subscribe_events(listener: js_sys::Function) -> u32 {
+   JS_LISTENERS.with_borrow_mut(|(next_id, listeners)| {
+      *next_id += 1;
+      listeners.push((*next_id, listener));
+      *next_id
+   })
+}
+
+#[wasm_bindgen]
+pub fn unsubscribe_events(id: u32) {
+   JS_LISTENERS.with_borrow_mut(|(_, listeners)| listeners.retain(|(listener_id, _)| *listener_id != id));
+}
+
+/// begins a run of exactly total_steps steps, which run_a_chunk then works through
+#[wasm_bindgen]
+pub fn start_chunked_run(total_steps: u32) -> Result<(), JsValue> {
 
    let globalstate = THE_STATE.replace(WebApp::Uninitialized);
 
@@ -124,23 +197,50 @@ pub fn render_a_frame() -> Result<(), JsValue> {
       WebApp::Idle(state) => state
    };
 
-   let result = state.render().map_err(|e| {
-      log::info!("gpu failure: {}",e);
-      JsValue::from_str(&format!("gpu failure: {}",e))
-   });
+   state.heateq.begin_chunked_run(total_steps as u64);
+   // a run_until still waiting will not hear back now
+   _ = RUN_UNTIL_DONE.take();
 
    THE_STATE.set(WebApp::Idle(state));
 
-   result?;
-
    Ok(())
 }
 
-// implement this https://donatstudios.com/Read-User-Files-With-Go-WASM
+/// begins a run that stops once any of the conditions in `spec` holds, separated by spaces:
+///    time=<t>, steps=<n>, steady=<max |dT/dt|>, probe=<name>><T>, probe=<name><<T>, plus
+///    every=<n> for how often to read the field back for the last two. run_a_chunk works
+///    through it like any other run, and the promise resolves with the report once it stops.
+#[wasm_bindgen]
+pub async fn run_until(spec: String) -> Result<crate::runcontrol::RunReport, JsValue> {
+   let until = spec.parse::<crate::runcontrol::RunUntil>().map_err(|e| JsValue::from_str(&e))?;
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let begun = state.heateq.begin_controlled_run(&state.device, until);
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   begun.map_err(|e| JsValue::from_str(&e.to_string()))?;
+   let (sender, receiver) = tokio::sync::oneshot::channel();
+   RUN_UNTIL_DONE.set(Some(sender));
+   receiver.await.map_err(|_| JsValue::from_str("the run was replaced before it stopped"))
+}
 
+/// queues the next bounded chunk of the run plus the colouring, and returns how many
+///    steps of the run are done. Returns -1 once the run is over (finished or cancelled).
 #[wasm_bindgen]
-pub fn send_output_to_export() -> Result<(), JsValue> {
+pub fn run_a_chunk() -> Result<f64, JsValue> {
+
    let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
    let mut state: WgpuState = match globalstate {
       WebApp::Uninitialized => {
          log::info!("Can not do! Uninitialized");
@@ -150,65 +250,80 @@ pub fn send_output_to_export() -> Result<(), JsValue> {
    };
 
    let mut pending_queue = state.pending_queue.replace(vec![]);
-   let mut encoder = state.device.create_command_encoder(&Default::default());
 
-   state.heateq.export_buffer.unmap();
-   let (sender,receiver) = tokio::sync::oneshot::channel();
-   encoder.copy_buffer_to_buffer(&state.heateq.output_buffer, 0, &state.heateq.export_buffer, 0, state.heateq.output_buffer.size());
-   encoder.map_buffer_on_submit(&state.heateq.export_buffer, wgpu::MapMode::Read, ..,
-      move |result| {
-         match sender.send(result) {
-            Ok(()) => {}
-            Err(x) => log::info!("sender failed to send, with message {:?}",x)
-         }
-      });
-   pending_queue.push(encoder.finish());
-   INTERNAL_MESSAGE.set(Some(receiver));
+   let progress = match state.heateq.send_controlled_chunk(&mut pending_queue,&state.device) {
+      Some(crate::runcontrol::RunStatus::Running) => state.heateq.run_controller.as_ref()
+         .map(|controller| (state.heateq.accepted_steps - controller.start_steps) as f64),
+      // the last chunk may still need drawing, so this colours too before saying -1
+      Some(crate::runcontrol::RunStatus::Finished(report)) => {
+         if let Some(sender) = RUN_UNTIL_DONE.take() {_ = sender.send(report)}
+         Some(-1.0)
+      }
+      None => state.heateq.send_run_chunk(&mut pending_queue,&state.device)
+         .map(|run| run.done_steps as f64)
+   };
+   if progress.is_some() {
+      state.heateq.send_color_job(&mut pending_queue,&state.device);
+      state.heateq.color_to_texture(&mut pending_queue,&state.device,&state.texture_buffer);
+   }
 
    _ = state.pending_queue.replace(pending_queue);
+
    THE_STATE.set(WebApp::Idle(state));
-   Ok(())
+
+   Ok(progress.unwrap_or(-1.0))
 }
 
-// #[wasm_bindgen]
-// pub fn setup_temp_receiver() -> Result<(), JsValue> {
-//    let globalstate = THE_STATE.replace(WebApp::Uninitialized);
-//    let mut state: WgpuState = match globalstate {
-//       WebApp::Uninitialized => {
-//          log::info!("Can not do! Uninitialized");
-//          return Err(JsValue::from_str("Can not do! Uninitialized"));
-//       }
-//       WebApp::Idle(state) => state
-//    };
+#[wasm_bindgen]
+pub fn cancel_chunked_run() -> Result<(), JsValue> {
 
-//    let (sender,receiver) = tokio::sync::oneshot::channel();
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
 
-//    state.heateq.export_buffer.map_async(wgpu::MapMode::Read, ..,
-//       move |result| sender.send(result).unwrap());
+   let state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
 
-//    state.device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+   state.heateq.cancel_chunked_run();
 
-//    THE_STATE.set(WebApp::Idle(state));
-//    INTERNAL_MESSAGE.set(Some(receiver));
-//    Ok(())
-// }
+   THE_STATE.set(WebApp::Idle(state));
+
+   Ok(())
+}
 
 #[wasm_bindgen]
-pub fn is_receiver_ready() -> Result<bool, JsValue> {
-   let Some(mut receiver) = INTERNAL_MESSAGE.replace(None) else {return Ok(false)};
-   let is_val = receiver.try_recv();
-   match is_val {
-      Ok(Ok(())) => {return Ok(true);}
-      Ok(Err(_)) => {log::info!("gpu export cooked")}
-      _ => {INTERNAL_MESSAGE.replace(Some(receiver));}
+pub fn set_max_steps_per_submission(max_steps: u32) -> Result<(), JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
    };
 
-   Ok(false)
+   state.heateq.max_steps_per_submission = max_steps.max(1);
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   Ok(())
 }
 
+/// "rk2", "rkl2" or "adi". with rkl2, delta_t may go well past the explicit limit since the
+///    number of stages per step is picked to keep it stable, and adi is stable for any delta_t.
 #[wasm_bindgen]
-pub fn get_export_to_num() -> Result<f32, JsValue> {
+pub fn set_integrator(name: String) -> Result<(), JsValue> {
+
+   let integrator = name.parse::<crate::webgpuheat::Integrator>()
+      .map_err(|e| JsValue::from_str(&e))?;
+
    let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
    let mut state: WgpuState = match globalstate {
       WebApp::Uninitialized => {
          log::info!("Can not do! Uninitialized");
@@ -217,60 +332,99 @@ pub fn get_export_to_num() -> Result<f32, JsValue> {
       WebApp::Idle(state) => state
    };
 
-   let thedata: Vec<f32> = {
-      let output_data = state.heateq.export_buffer.get_mapped_range(..);
-      bytemuck::cast_slice(&output_data).to_vec()
+   let result = state.heateq.set_integrator(integrator);
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   result.map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// turns on adaptive stepping, where delta_t is picked to keep each step's error under
+///    atol + rtol*|T| everywhere
+#[wasm_bindgen]
+pub fn set_adaptive_tolerance(atol: f32, rtol: f32) -> Result<(), JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
    };
 
-   //let length = state.heateq.length.clone();
+   let result = state.heateq.enable_adaptive(&state.device, atol, rtol);
 
    THE_STATE.set(WebApp::Idle(state));
 
-   let mut the_sum: f64 = 0.0;
-   // for i in 0..(length as usize) {
-   //    the_sum += thedata[i] as f64;
-   // }
-   for i in thedata.iter() {the_sum += *i as f64}
-   return Ok(the_sum as f32)
+   result.map_err(|e| JsValue::from_str(&e.to_string()))
 }
 
-// we need this separate so that rust knows to add instructions to drop the gpu memory
+/// up to `attempts` adaptive steps, rejected ones included. Gives back the simulated time.
 #[wasm_bindgen]
-pub fn junk_current_state() -> Result<(), wasm_bindgen::JsValue> {
-   _ = THE_STATE.replace(WebApp::Uninitialized);
-   _ = INTERNAL_MESSAGE.replace(None);
+pub async fn run_adaptive_steps(attempts: u32) -> Result<f64, JsValue> {
 
-   Ok(())
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let mut result = Ok(());
+   for _ in 0..attempts {
+      if let Err(e) = state.heateq.adaptive_step(&state.device, &state.queue, None).await {
+         result = Err(JsValue::from_str(&format!("adaptive step failed: {}", e)));
+         break
+      }
+   }
+   let sim_time = state.heateq.sim_time;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   result.map(|_| sim_time)
 }
 
+/// adaptive steps until the simulated time reaches end_time, landing on it exactly, or
+///    until `max_attempts` attempts have gone by. Gives back the simulated time.
 #[wasm_bindgen]
-pub async fn rinit_with_xy(width: u32, height: u32) -> Result<(), wasm_bindgen::JsValue> {
-    let window = wgpu::web_sys::window().unwrap_throw();
-    let document = window.document().unwrap_throw();
-    //let canvas = body.get_element_by_id(CANVAS_ID).unwrap_throw();
-    let canvas: web_sys::Element = document
-       .query_selector("canvas")
-       .expect("could not find canvas")
-       .expect("canvas query returned empty");
-    let html_canvas_element: HtmlCanvasElement = canvas
-       .dyn_into()
-       .expect("man your canvas is bonked or somethin");
-
-    let mut webstate = WgpuState::new_with(html_canvas_element, width, height)
-        .await
-        .map_err(|e| JsValue::from_str(&format!("error {}",e)))?;
+pub async fn run_adaptive_until(end_time: f64, max_attempts: u32) -> Result<f64, JsValue> {
 
-    webstate.render().map_err(|e| JsValue::from_str(&format!("error {}",e)));
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
 
-    console_error_panic_hook::set_once();
-    THE_STATE.set(WebApp::Idle(webstate));
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
 
-    Ok(())
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let result = state.heateq.adaptive_advance_to(&state.device, &state.queue, end_time, max_attempts).await
+      .map_err(|e| JsValue::from_str(&format!("adaptive step failed: {}", e)));
+   let sim_time = state.heateq.sim_time;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   result.map(|_| sim_time)
 }
 
+/// [simulated time, accepted steps, rejected steps, delta_t the adaptive stepper tries next]
 #[wasm_bindgen]
-pub fn give_current_width() -> Result<u32, JsValue> {
+pub fn get_step_stats() -> Result<Vec<f64>, JsValue> {
+
    let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
    let state: WgpuState = match globalstate {
       WebApp::Uninitialized => {
          log::info!("Can not do! Uninitialized");
@@ -279,14 +433,25 @@ pub fn give_current_width() -> Result<u32, JsValue> {
       WebApp::Idle(state) => state
    };
 
-   let answer: u32 = state.heateq.width.clone();
-   THE_STATE.replace(WebApp::Idle(state));
-   Ok(answer)
+   let heateq = &state.heateq;
+   let stats = vec![
+      heateq.sim_time,
+      heateq.accepted_steps as f64,
+      heateq.rejected_steps as f64,
+      heateq.adaptive.as_ref().map_or(heateq.delta_t as f64, |a| a.delta_t),
+   ];
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   Ok(stats)
 }
 
+/// the simulated time, advanced by every step however it was run
 #[wasm_bindgen]
-pub fn give_current_height() -> Result<u32, JsValue> {
+pub fn get_sim_time() -> Result<f64, JsValue> {
+
    let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
    let state: WgpuState = match globalstate {
       WebApp::Uninitialized => {
          log::info!("Can not do! Uninitialized");
@@ -295,132 +460,93 @@ pub fn give_current_height() -> Result<u32, JsValue> {
       WebApp::Idle(state) => state
    };
 
-   let answer: u32 = state.heateq.height.clone();
-   THE_STATE.replace(WebApp::Idle(state));
-   Ok(answer)
+   let sim_time = state.heateq.sim_time;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   Ok(sim_time)
 }
 
+/// starts the simulated time and step counts over from 0
 #[wasm_bindgen]
-pub fn parse_csv(csv_as_string: String) -> Result<String, JsValue> {
-   let mut csvrdr = csv::ReaderBuilder::new()
-      .delimiter(b',')
-      .has_headers(false)
-      .from_reader(csv_as_string.as_bytes());
+pub fn reset_clock() -> Result<(), JsValue> {
 
-   let mut width: u32 = 0;
-   let mut height: u32 = 0;
-   let mut newbuffer: Vec<f32> = Vec::new();
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
 
-   if let Some(result) = csvrdr.records().next() {
-      let first_line = match result {
-         Ok(is_ok) => is_ok,
-         Err(_) => {return Ok(String::from_str("failed to read first line of csv").unwrap());}
-      };
-      for i in first_line.iter() {
-         match i.parse::<f32>() {
-            Ok(num) => { newbuffer.push(num); width += 1; }
-            Err(_) => {return Ok(String::from_str(&format!(
-               "could not read csv entry (0,{}) as float32",
-               width)).unwrap());}
-         }
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
       }
-      height += 1;
-   } else {
-      return Ok(String::from_str("couldnt find first row").unwrap());
-   }
+      WebApp::Idle(state) => state
+   };
 
-   loop {
-      if let Some(result) = csvrdr.records().next() {
-         let next_line = match result {
-            Ok(is_ok) => is_ok,
-            Err(_) => {return Ok(String::from_str(&format!(
-               "failed to read {}'th line of csv, was it longer or shorter than other lines?",
-               height + 1)).unwrap());}
-         };
-         let mut x_coord = 0;
-         for i in next_line.iter() {
-            match i.parse::<f32>() {
-               Ok(num) => {newbuffer.push(num); x_coord += 1;}
-               Err(_) => {return Ok(String::from_str(&format!(
-                  "failed to read element ({},{}) of csv as float32?",
-                  x_coord, height)).unwrap());
-               }
-            };
-         }
-         if !(x_coord == width) {return Ok(String::from_str(&format!(
-            "{}'th line of csv was {} long instead of {}", height, x_coord, width)).unwrap());}
-         height += 1;
-      } else {
-         break;
-      }
-   }
-
-   if !(newbuffer.len() == (width * height) as usize) {
-      return Ok(String::from_str(&format!(
-         "csv failed data-length is width times height test (width {} and height {})", width, height)).unwrap());
-   }
+   state.heateq.reset_clock(&state.queue);
 
-   _ = CSV_BUFFER.replace(Some((newbuffer, width, height)));
+   THE_STATE.set(WebApp::Idle(state));
 
-   Ok(String::from_str("success!").unwrap())
+   Ok(())
 }
 
+/// "fixed", "auto", "hysteresis" (a is the padding fraction) or "percentile" (a and b are
+///    the low and high fractions of cells kept). Leave a and b out for the defaults.
 #[wasm_bindgen]
-pub async fn init_from_csv_buffer() -> Result<(), JsValue> {
-   let Some((newbuffer, width, height)) = CSV_BUFFER.replace(None) else {
-      return Err(JsValue::from_str("tried to load from csv buffer but it was empty"));
+pub fn set_color_range(mode: String, a: Option<f32>, b: Option<f32>) -> Result<(), JsValue> {
+
+   let color_range = crate::colorrange::ColorRange::from_parts(&mode, a, b)
+      .map_err(|e| JsValue::from_str(&e))?;
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
    };
 
-   let (sender, receiver) = tokio::sync::oneshot::channel::<Result<(), wgpu::BufferAsyncError>>();
-   _ = INTERNAL_MESSAGE.replace(Some(receiver));
+   // any color job already queued still ranges the old way, so it goes first
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+   let result = state.heateq.set_color_range(&state.device, &state.queue, color_range);
 
-   let window = wgpu::web_sys::window().unwrap_throw();
-   let document = window.document().unwrap_throw();
-   let canvas: web_sys::Element = document
-      .query_selector("canvas")
-      .expect("could not find canvas")
-      .expect("canvas query returned empty");
-   let html_canvas_element: HtmlCanvasElement = canvas
-      .dyn_into()
-      .expect("man your canvas is bonked or somethin");
-   let mut state = WgpuState::new_with(html_canvas_element, width, height)
-      .await
-      .map_err(|e| JsValue::from_str(&format!("error {}",e)))?;
+   THE_STATE.set(WebApp::Idle(state));
 
-   state.queue.write_buffer(
-      &state.heateq.data_buffer,
-      0,
-      bytemuck::cast_slice(newbuffer.as_slice())
-   );
+   result.map_err(|e| JsValue::from_str(&e.to_string()))
+}
 
-   let mut pending_queue: Vec<wgpu::CommandBuffer> = Vec::new();
-   state.heateq.send_color_job(&mut pending_queue, &state.device);
-   state.heateq.color_to_texture(&mut pending_queue, &state.device, &state.texture_buffer);
-   _ = state.pending_queue.replace(pending_queue);
-   state.render();
+#[wasm_bindgen]
+pub fn render_a_frame() -> Result<(), JsValue> {
 
-   THE_STATE.replace(WebApp::Idle(state));
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
 
-   sender.send(Ok(()));
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let result = state.render().map_err(|e| {
+      log::info!("gpu failure: {}",e);
+      JsValue::from_str(&format!("gpu failure: {}",e))
+   });
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   result?;
 
    Ok(())
 }
 
-#[wasm_bindgen]
-pub async fn get_total_energy_in_one() -> Result<f32, JsValue> {
-   if let Some(receiver) = INTERNAL_MESSAGE.replace(None) {
-      let receiver_result = receiver.await;
-      match (receiver_result) {
-         Ok(Ok(())) => {},
-         Err(err) => {return Err(JsValue::from_str(
-            &format!("error at receiver in get_total_energy_in_one: {:?}", err)
-         ));},
-         _ => {return Err(JsValue::from_str("wgpu export failure"));}
-      };
-   } else {log::info!("no receiver to block on in get_total_energy_in_one, continuing")};
+// implement this https://donatstudios.com/Read-User-Files-With-Go-WASM
 
+#[wasm_bindgen]
+pub fn send_output_to_export() -> Result<(), JsValue> {
    let globalstate = THE_STATE.replace(WebApp::Uninitialized);
-   let state: WgpuState = match globalstate {
+   let mut state: WgpuState = match globalstate {
       WebApp::Uninitialized => {
          log::info!("Can not do! Uninitialized");
          return Err(JsValue::from_str("Can not do! Uninitialized"));
@@ -428,61 +554,140 @@ pub async fn get_total_energy_in_one() -> Result<f32, JsValue> {
       WebApp::Idle(state) => state
    };
 
-   let mut pending_queue = state.pending_queue.replace(Vec::new());
+   // a tiled grid has no one output buffer to copy, and get_total_energy_in_one and
+   //    writeStateAsCSV read it back a tile at a time without waiting on this
+   if state.heateq.tiling.is_some() {
+      THE_STATE.set(WebApp::Idle(state));
+      return Ok(());
+   }
+
+   let mut pending_queue = state.pending_queue.replace(vec![]);
    let mut encoder = state.device.create_command_encoder(&Default::default());
 
    state.heateq.export_buffer.unmap();
    let (sender,receiver) = tokio::sync::oneshot::channel();
-   encoder.copy_buffer_to_buffer(
-      &state.heateq.data_buffer,
-      0,
-      &state.heateq.export_buffer,
-      0,
-      state.heateq.data_buffer.size());
+   encoder.copy_buffer_to_buffer(&state.heateq.output_buffer, 0, &state.heateq.export_buffer, 0, state.heateq.output_buffer.size());
+   let events = state.heateq.events.clone();
    encoder.map_buffer_on_submit(&state.heateq.export_buffer, wgpu::MapMode::Read, ..,
       move |result| {
+         events.readback(&result);
          match sender.send(result) {
             Ok(()) => {}
             Err(x) => log::info!("sender failed to send, with message {:?}",x)
          }
       });
    pending_queue.push(encoder.finish());
-   state.queue.submit(pending_queue.into_boxed_slice());
+   INTERNAL_MESSAGE.set(Some(receiver));
+
+   _ = state.pending_queue.replace(pending_queue);
+   THE_STATE.set(WebApp::Idle(state));
+   Ok(())
+}
+
+// #[wasm_bindgen]
+// pub fn setup_temp_receiver() -> Result<(), JsValue> {
+//    let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+//    let mut state: WgpuState = match globalstate {
+//       WebApp::Uninitialized => {
+//          log::info!("Can not do! Uninitialized");
+//          return Err(JsValue::from_str("Can not do! Uninitialized"));
+//       }
+//       WebApp::Idle(state) => state
+//    };
+
+//    let (sender,receiver) = tokio::sync::oneshot::channel();
+
+//    state.heateq.export_buffer.map_async(wgpu::MapMode::Read, ..,
+//       move |result| sender.send(result).unwrap());
+
+//    state.device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+
+//    THE_STATE.set(WebApp::Idle(state));
+//    INTERNAL_MESSAGE.set(Some(receiver));
+//    Ok(())
+// }
+
+#[wasm_bindgen]
+pub fn is_receiver_ready() -> Result<bool, JsValue> {
+   let Some(mut receiver) = INTERNAL_MESSAGE.replace(None) else {return Ok(false)};
+   let is_val = receiver.try_recv();
+   match is_val {
+      Ok(Ok(())) => {return Ok(true);}
+      Ok(Err(_)) => {log::info!("gpu export cooked")}
+      _ => {INTERNAL_MESSAGE.replace(Some(receiver));}
+   };
+
+   Ok(false)
+}
+
+#[wasm_bindgen]
+pub fn get_export_to_num() -> Result<f32, JsValue> {
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
 
    let thedata: Vec<f32> = {
-      match state.device.poll(wgpu::PollType::wait_indefinitely()) {
-         Err(_) => {return Err(JsValue::from_str(&format!("poll failed in get_total_energy_in_one"))); }
-         Ok(_) => {}
-      };
-      _ = match receiver.await {
-         Err(_) => {return Err(JsValue::from_str(&format!("receiver failed in get_total_energy_in_one"))); }
-         Ok(_) => ()
-      };
       let output_data = state.heateq.export_buffer.get_mapped_range(..);
       bytemuck::cast_slice(&output_data).to_vec()
    };
 
+   //let length = state.heateq.length.clone();
+
+   THE_STATE.set(WebApp::Idle(state));
+
    let mut the_sum: f64 = 0.0;
+   // for i in 0..(length as usize) {
+   //    the_sum += thedata[i] as f64;
+   // }
    for i in thedata.iter() {the_sum += *i as f64}
+   return Ok(the_sum as f32)
+}
 
-   _ = THE_STATE.replace(WebApp::Idle(state));
+// we need this separate so that rust knows to add instructions to drop the gpu memory
+#[wasm_bindgen]
+pub fn junk_current_state() -> Result<(), wasm_bindgen::JsValue> {
+   _ = THE_STATE.replace(WebApp::Uninitialized);
+   _ = INTERNAL_MESSAGE.replace(None);
+   _ = ANALYTIC_SOLUTION.replace(None);
+
+   Ok(())
+}
 
-   Ok(the_sum as f32)
+/// a new state of width x height drawing to the page's canvas
+pub async fn state_on_canvas(width: u32, height: u32) -> Result<WgpuState, JsValue> {
+   let window = wgpu::web_sys::window().unwrap_throw();
+   let document = window.document().unwrap_throw();
+   let canvas: web_sys::Element = document
+      .query_selector("canvas")
+      .expect("could not find canvas")
+      .expect("canvas query returned empty");
+   let html_canvas_element: HtmlCanvasElement = canvas
+      .dyn_into()
+      .expect("man your canvas is bonked or somethin");
+   WgpuState::new_with(html_canvas_element, width, height)
+      .await
+      .map_err(|e| JsValue::from_str(&format!("error {}",e)))
 }
 
 #[wasm_bindgen]
-pub async fn writeStateAsCSV() -> Result<String,JsValue> {
-   if let Some(receiver) = INTERNAL_MESSAGE.replace(None) {
-      let receiver_result = receiver.await;
-      match (receiver_result) {
-         Ok(Ok(())) => {},
-         Err(err) => {return Err(JsValue::from_str(
-            &format!("error at receiver in get_total_energy_in_one: {:?}", err)
-         ));},
-         _ => {return Err(JsValue::from_str("wgpu export failure"));}
-      };
-   } else {log::info!("no receiver to block on in get_total_energy_in_one, continuing")};
+pub async fn rinit_with_xy(width: u32, height: u32) -> Result<(), wasm_bindgen::JsValue> {
+    let mut webstate = state_on_canvas(width, height).await?;
+
+    webstate.render().map_err(|e| JsValue::from_str(&format!("error {}",e)));
+
+    console_error_panic_hook::set_once();
+    THE_STATE.set(WebApp::Idle(webstate));
 
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn give_current_width() -> Result<u32, JsValue> {
    let globalstate = THE_STATE.replace(WebApp::Uninitialized);
    let state: WgpuState = match globalstate {
       WebApp::Uninitialized => {
@@ -492,57 +697,1105 @@ pub async fn writeStateAsCSV() -> Result<String,JsValue> {
       WebApp::Idle(state) => state
    };
 
-   let mut pending_queue = state.pending_queue.replace(Vec::new());
-   let mut encoder = state.device.create_command_encoder(&Default::default());
-
-   state.heateq.export_buffer.unmap();
-   let (sender,receiver) = tokio::sync::oneshot::channel();
-   encoder.copy_buffer_to_buffer(
-      &state.heateq.data_buffer,
-      0,
-      &state.heateq.export_buffer,
-      0,
-      state.heateq.data_buffer.size());
-   encoder.map_buffer_on_submit(&state.heateq.export_buffer, wgpu::MapMode::Read, ..,
-      move |result| {
-         match sender.send(result) {
-            Ok(()) => {}
-            Err(x) => log::info!("sender failed to send, with message {:?}",x)
-         }
-      });
-   pending_queue.push(encoder.finish());
-   state.queue.submit(pending_queue.into_boxed_slice());
+   let answer: u32 = state.heateq.width.clone();
+   THE_STATE.replace(WebApp::Idle(state));
+   Ok(answer)
+}
 
-   let mut thedata: Vec<f32> = {
-      match state.device.poll(wgpu::PollType::wait_indefinitely()) {
-         Err(_) => {return Err(JsValue::from_str(&format!("poll failed in get_total_energy_in_one"))); }
-         Ok(_) => {}
-      };
-      _ = match receiver.await {
-         Err(_) => {return Err(JsValue::from_str(&format!("receiver failed in get_total_energy_in_one"))); }
-         Ok(_) => ()
-      };
-      let output_data = state.heateq.export_buffer.get_mapped_range(..);
-      bytemuck::cast_slice(&output_data).to_vec()
+#[wasm_bindgen]
+pub fn give_current_height() -> Result<u32, JsValue> {
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+   let state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
    };
-   let width = state.heateq.width;
-   let height = state.heateq.height;
-   _ = THE_STATE.replace(WebApp::Idle(state));
 
-   let mut thewriter = csv::WriterBuilder::new()
+   let answer: u32 = state.heateq.height.clone();
+   THE_STATE.replace(WebApp::Idle(state));
+   Ok(answer)
+}
+
+#[wasm_bindgen]
+pub fn parse_csv(csv_as_string: String) -> Result<String, JsValue> {
+   let mut csvrdr = csv::ReaderBuilder::new()
       .delimiter(b',')
       .has_headers(false)
-      .from_writer(vec![]);
-   for j in 0..height {
-      thewriter.write_record(
-         thedata.drain(0..(width as usize)).map(|f| format!("{}",f))
-      ).map_err(|e| {JsValue::from_str(&format!("writer failed on line {}", j))})?
-   }
-   let innerwriter = thewriter.into_inner();
-   let finalstr = match innerwriter {
-      Ok(thevec) => String::from_utf8(thevec).map_err(|e| JsValue::from_str("Could not convert bytestring to utf8 string")),
+      .from_reader(csv_as_string.as_bytes());
+
+   let mut width: u32 = 0;
+   let mut height: u32 = 0;
+   let mut newbuffer: Vec<f32> = Vec::new();
+
+   if let Some(result) = csvrdr.records().next() {
+      let first_line = match result {
+         Ok(is_ok) => is_ok,
+         Err(_) => {return Ok(String::from_str("failed to read first line of csv").unwrap());}
+      };
+      for i in first_line.iter() {
+         match i.parse::<f32>() {
+            Ok(num) => { newbuffer.push(num); width += 1; }
+            Err(_) => {return Ok(String::from_str(&format!(
+               "could not read csv entry (0,{}) as float32",
+               width)).unwrap());}
+         }
+      }
+      height += 1;
+   } else {
+      return Ok(String::from_str("couldnt find first row").unwrap());
+   }
+
+   loop {
+      if let Some(result) = csvrdr.records().next() {
+         let next_line = match result {
+            Ok(is_ok) => is_ok,
+            Err(_) => {return Ok(String::from_str(&format!(
+               "failed to read {}'th line of csv, was it longer or shorter than other lines?",
+               height + 1)).unwrap());}
+         };
+         let mut x_coord = 0;
+         for i in next_line.iter() {
+            match i.parse::<f32>() {
+               Ok(num) => {newbuffer.push(num); x_coord += 1;}
+               Err(_) => {return Ok(String::from_str(&format!(
+                  "failed to read element ({},{}) of csv as float32?",
+                  x_coord, height)).unwrap());
+               }
+            };
+         }
+         if !(x_coord == width) {return Ok(String::from_str(&format!(
+            "{}'th line of csv was {} long instead of {}", height, x_coord, width)).unwrap());}
+         height += 1;
+      } else {
+         break;
+      }
+   }
+
+   if !(newbuffer.len() == (width * height) as usize) {
+      return Ok(String::from_str(&format!(
+         "csv failed data-length is width times height test (width {} and height {})", width, height)).unwrap());
+   }
+
+   _ = CSV_BUFFER.replace(Some((newbuffer, width, height)));
+
+   Ok(String::from_str("success!").unwrap())
+}
+
+#[wasm_bindgen]
+pub async fn init_from_csv_buffer() -> Result<(), JsValue> {
+   let Some((newbuffer, width, height)) = CSV_BUFFER.replace(None) else {
+      return Err(JsValue::from_str("tried to load from csv buffer but it was empty"));
+   };
+
+   let (sender, receiver) = tokio::sync::oneshot::channel::<Result<(), wgpu::BufferAsyncError>>();
+   _ = INTERNAL_MESSAGE.replace(Some(receiver));
+
+   let mut state = state_on_canvas(width, height).await?;
+
+   state.heateq.write_field(&state.queue, &crate::rectgrid::RectGrid::newfromdata(width as usize, height as usize, newbuffer))
+      .map_err(|e| JsValue::from_str(&format!("could not load the csv: {}", e)))?;
+
+   let mut pending_queue: Vec<wgpu::CommandBuffer> = Vec::new();
+   state.heateq.send_color_job(&mut pending_queue, &state.device);
+   state.heateq.color_to_texture(&mut pending_queue, &state.device, &state.texture_buffer);
+   _ = state.pending_queue.replace(pending_queue);
+   state.render();
+
+   THE_STATE.replace(WebApp::Idle(state));
+
+   sender.send(Ok(()));
+
+   Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn get_total_energy_in_one() -> Result<f32, JsValue> {
+   if let Some(receiver) = INTERNAL_MESSAGE.replace(None) {
+      let receiver_result = receiver.await;
+      match (receiver_result) {
+         Ok(Ok(())) => {},
+         Err(err) => {return Err(JsValue::from_str(
+            &format!("error at receiver in get_total_energy_in_one: {:?}", err)
+         ));},
+         _ => {return Err(JsValue::from_str("wgpu export failure"));}
+      };
+   } else {log::info!("no receiver to block on in get_total_energy_in_one, continuing")};
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   // summed here in f64 rather than by the f32 reduction, so the total stays exact enough
+   //    to watch energy conservation on big grids
+   let field = state.heateq.read_field(&state.device, &state.queue).await;
+
+   _ = THE_STATE.replace(WebApp::Idle(state));
+
+   field.map(|field| field.getarray().iter().map(|x| *x as f64).sum::<f64>() as f32)
+      .map_err(|e| JsValue::from_str(&format!("readback failed in get_total_energy_in_one: {}", e)))
+}
+
+/// sum, min, max, mean, l2 and linf norms and the centroid of the current field, without
+///    reading the field itself back
+#[wasm_bindgen]
+pub async fn get_field_stats() -> Result<crate::reduction::FieldStats, JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let stats = state.heateq.field_stats(&state.device, &state.queue).await;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   stats.map_err(|e| JsValue::from_str(&format!("field stats failed: {}", e)))
+}
+
+/// places probes at (xs[n], ys[n]) in domain units, on a domain x_length by y_length with its
+///    walls at 0 and the lengths, named names[n], sampled every `every` steps with the last
+///    `capacity` samples kept. Any earlier probes and their samples go.
+#[wasm_bindgen]
+pub fn set_probes(names: Vec<String>, xs: Vec<f64>, ys: Vec<f64>, x_length: f64, y_length: f64, every: u32, capacity: u32) -> Result<(), JsValue> {
+
+   if names.len() != xs.len() || names.len() != ys.len() {
+      return Err(JsValue::from_str("names, xs and ys should be the same length"));
+   }
+   if !(x_length > 0. && y_length > 0.) {return Err(JsValue::from_str("a domain needs lengths above 0"))}
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let geometry = crate::vtk::VtkGeometry::solver_grid(state.heateq.width as usize, state.heateq.height as usize, x_length, y_length);
+   let probes = names.iter().zip(xs).zip(ys)
+      .map(|((name, x), y)| crate::probes::Probe::in_domain(name, x, y, &geometry))
+      .collect();
+   let result = state.heateq.set_probes(&state.device, probes, every, capacity)
+      .map_err(|e| JsValue::from_str(&format!("{}", e)));
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   result
+}
+
+/// the probe time series, a time column then a column per probe
+#[wasm_bindgen]
+pub async fn get_probes_csv() -> Result<String, JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let csv = state.heateq.probes_as_csv(&state.device, &state.queue).await;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   csv.map_err(|e| JsValue::from_str(&format!("probe export failed: {}", e)))
+}
+
+/// the field along a line from (x0,y0) to (x1,y1), or along row j or column i when `kind` is
+///    "row" or "col" (then x0 is the index and the rest are ignored). Gives back the profile as
+///    csv with distance and T columns.
+#[wasm_bindgen]
+pub async fn get_line_profile_csv(kind: String, x0: f32, y0: f32, x1: f32, y1: f32, samples: u32) -> Result<String, JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let grid = state.heateq.read_field(&state.device, &state.queue).await;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   let grid = grid.map_err(|e| JsValue::from_str(&format!("readback failed: {}", e)))?;
+   let profile = match kind.as_str() {
+      "line" => grid.lineprofile(x0, y0, x1, y1, samples.max(1) as usize),
+      "row" if (x0 as usize) < grid.height() => grid.rowprofile(x0 as usize),
+      "col" if (x0 as usize) < grid.width() => grid.columnprofile(x0 as usize),
+      _ => {return Err(JsValue::from_str("kind should be line, row or col, with the index inside the grid"));}
+   };
+
+   crate::rectgrid::profile_as_csv(&profile).map_err(|e| JsValue::from_str(&format!("{}", e)))
+}
+
+/// "temperature", "flux_x", "flux_y", "flux_mag", or once start_accumulators has been called
+///    "peak_T", "time_above" or "first_arrival", what the heatmap shows from now on
+#[wasm_bindgen]
+pub fn set_view_field(name: String) -> Result<(), JsValue> {
+
+   let view = name.parse::<crate::flux::ViewField>()
+      .map_err(|e| JsValue::from_str(&e))?;
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let result = state.heateq.set_view_field(&state.device, view);
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   result.map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// the k in flux = -k grad T
+#[wasm_bindgen]
+pub fn set_conductivity(conductivity: f32) -> Result<(), JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let result = state.heateq.set_conductivity(&state.device, &state.queue, conductivity);
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   result.map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// like writeStateAsCSV, but for any of the fields set_view_field takes
+#[wasm_bindgen]
+pub async fn write_field_as_csv(name: String) -> Result<String, JsValue> {
+
+   let view = name.parse::<crate::flux::ViewField>()
+      .map_err(|e| JsValue::from_str(&e))?;
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let grid = state.heateq.read_view_field(&state.device, &state.queue, view).await;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   grid.and_then(|grid| grid.writecsv())
+      .map_err(|e| JsValue::from_str(&format!("export failed: {}", e)))
+}
+
+#[wasm_bindgen]
+pub async fn writeStateAsCSV() -> Result<String,JsValue> {
+   if let Some(receiver) = INTERNAL_MESSAGE.replace(None) {
+      let receiver_result = receiver.await;
+      match (receiver_result) {
+         Ok(Ok(())) => {},
+         Err(err) => {return Err(JsValue::from_str(
+            &format!("error at receiver in get_total_energy_in_one: {:?}", err)
+         ));},
+         _ => {return Err(JsValue::from_str("wgpu export failure"));}
+      };
+   } else {log::info!("no receiver to block on in get_total_energy_in_one, continuing")};
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   // read_field goes a tile at a time when the grid is tiled
+   let field = state.heateq.read_field(&state.device, &state.queue).await;
+   let mut thedata: Vec<f32> = match field {
+      Ok(field) => field.getarray().clone(),
+      Err(e) => {
+         _ = THE_STATE.replace(WebApp::Idle(state));
+         return Err(JsValue::from_str(&format!("readback failed in writeStateAsCSV: {}", e)));
+      }
+   };
+   let width = state.heateq.width;
+   let height = state.heateq.height;
+   _ = THE_STATE.replace(WebApp::Idle(state));
+
+   let mut thewriter = csv::WriterBuilder::new()
+      .delimiter(b',')
+      .has_headers(false)
+      .from_writer(vec![]);
+   for j in 0..height {
+      thewriter.write_record(
+         thedata.drain(0..(width as usize)).map(|f| format!("{}",f))
+      ).map_err(|e| {JsValue::from_str(&format!("writer failed on line {}", j))})?
+   }
+   let innerwriter = thewriter.into_inner();
+   let finalstr = match innerwriter {
+      Ok(thevec) => String::from_utf8(thevec).map_err(|e| JsValue::from_str("Could not convert bytestring to utf8 string")),
       _ => {return Err(JsValue::from_str("Could not convert csv-writer into bytestring"));}
    };
 
-   finalstr
+   finalstr
+}
+
+/// edge is y0, x0, y1 or x1 and kind is insulated or fixed, with value the temperature of a
+///    fixed edge. Takes effect from the next step.
+#[wasm_bindgen]
+pub fn set_boundary_condition(edge: String, kind: String, value: Option<f32>) -> Result<(), JsValue> {
+
+   let edge = edge.parse::<crate::boundary::Edge>()
+      .map_err(|e| JsValue::from_str(&e))?;
+   let condition = crate::boundary::BoundaryCondition::from_parts(&kind, value)
+      .map_err(|e| JsValue::from_str(&e))?;
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   state.heateq.set_boundary_condition(&state.queue, edge, condition);
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   Ok(())
+}
+
+/// starts counting the heat through each edge from the field as it is now
+#[wasm_bindgen]
+pub async fn start_edge_flux_tracking() -> Result<(), JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let started = state.heateq.track_edge_flux(&state.device, &state.queue).await;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   started.map_err(|e| JsValue::from_str(&format!("edge flux tracking failed: {}", e)))
+}
+
+/// heat through each edge since start_edge_flux_tracking, with the energy balance
+#[wasm_bindgen]
+pub async fn get_edge_flux_report() -> Result<crate::boundary::EdgeFluxReport, JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let report = state.heateq.edge_flux_report(&state.device, &state.queue).await;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   report.map_err(|e| JsValue::from_str(&format!("edge flux report failed: {}", e)))
+}
+
+/// the isotherms of the current field at each level, as time,level,line,x,y rows
+#[wasm_bindgen]
+pub async fn get_isotherms_csv(levels: Vec<f32>) -> Result<String, JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let isotherms = state.heateq.isotherms(&state.device, &state.queue, &levels).await;
+   let csv = isotherms.and_then(|isotherms| crate::isotherms::isotherms_as_csv(&isotherms, state.heateq.sim_time));
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   csv.map_err(|e| JsValue::from_str(&format!("isotherm export failed: {}", e)))
+}
+
+/// the same isotherms as an svg the size of the grid, to lay over the heatmap
+#[wasm_bindgen]
+pub async fn get_isotherms_svg(levels: Vec<f32>) -> Result<String, JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let isotherms = state.heateq.isotherms(&state.device, &state.queue, &levels).await;
+   let svg = isotherms.map(|isotherms| crate::isotherms::isotherms_as_svg(
+      &isotherms, state.heateq.width as usize, state.heateq.height as usize));
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   svg.map_err(|e| JsValue::from_str(&format!("isotherm export failed: {}", e)))
+}
+
+/// draws isotherms at these levels over the heatmap, an empty list turns them off
+#[wasm_bindgen]
+pub fn set_isotherm_overlay(levels: Vec<f32>) -> Result<(), JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let result = state.heateq.set_isotherm_overlay(&state.device, levels);
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   result.map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// starts the peak temperature, time above `threshold` and first arrival maps from the field
+///    as it is now. They can then be shown with set_view_field and saved with
+///    write_field_as_csv.
+#[wasm_bindgen]
+pub fn start_accumulators(threshold: f32) -> Result<(), JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let result = state.heateq.start_accumulators(&state.device, &state.queue, threshold);
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   result.map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen]
+pub fn stop_accumulators() -> Result<(), JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   state.heateq.stop_accumulators(&state.device);
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   Ok(())
+}
+
+/// replaces the field with an exact solution at time 0 and restarts the clock. name is
+///    cosine [m n amplitude offset], gaussian [sigma amplitude x0 y0], erf_x [x0 left right]
+///    or erf_y [y0 left right], with positions from 0 to 1 between the insulated walls.
+#[wasm_bindgen]
+pub fn init_analytic(name: String, params: Vec<f64>) -> Result<(), JsValue> {
+
+   let solution = crate::analytic::AnalyticSolution::from_parts(&name, &params)
+      .map_err(|e| JsValue::from_str(&e))?;
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let (width, height) = (state.heateq.width as usize, state.heateq.height as usize);
+   if let Err(e) = crate::analytic::check_grid_size(width, height) {
+      THE_STATE.set(WebApp::Idle(state));
+      return Err(JsValue::from_str(&e));
+   }
+   let grid = solution.on_grid(width, height, 0., state.heateq.kappa as f64);
+   let result = state.heateq.write_field(&state.queue, &grid);
+   state.heateq.reset_clock(&state.queue);
+
+   THE_STATE.set(WebApp::Idle(state));
+   ANALYTIC_SOLUTION.set(Some(solution));
+
+   result.map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// L2 and Linf error of the field against the solution init_analytic started it from, at
+///    the current simulated time
+#[wasm_bindgen]
+pub async fn check_analytic() -> Result<crate::analytic::ErrorNorms, JsValue> {
+
+   let Some(solution) = ANALYTIC_SOLUTION.with_borrow(|solution| *solution) else {
+      return Err(JsValue::from_str("the field was not started from an analytic solution"));
+   };
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   if let Err(e) = crate::analytic::check_grid_size(state.heateq.width as usize, state.heateq.height as usize) {
+      THE_STATE.set(WebApp::Idle(state));
+      return Err(JsValue::from_str(&e));
+   }
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let grid = state.heateq.read_field(&state.device, &state.queue).await;
+   let errors = grid.map(|grid| solution.error(&grid, state.heateq.sim_time, state.heateq.kappa as f64));
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   errors.map_err(|e| JsValue::from_str(&format!("readback failed: {}", e)))
+}
+
+/// reruns the solution init_analytic started from at each resolution up to t_end, with the
+///    current kappa, integrator and edges and delta_t following diffusion_number, and gives
+///    back the convergence table as csv
+#[wasm_bindgen]
+pub async fn run_convergence_study(resolutions: Vec<u32>, t_end: f64, diffusion_number: f32) -> Result<String, JsValue> {
+
+   let Some(solution) = ANALYTIC_SOLUTION.with_borrow(|solution| *solution) else {
+      return Err(JsValue::from_str("a study reruns the solution the field was started from, see init_analytic"));
+   };
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let heateq = &state.heateq;
+   let study = crate::convergence::ConvergenceStudy::diffusive(
+      &resolutions, t_end, heateq.kappa, diffusion_number, heateq.integrator, heateq.boundary_conditions);
+   // the analytic solutions are only exact with every edge insulated
+   let insulated = heateq.boundary_conditions.iter().all(|x| *x == crate::boundary::BoundaryCondition::Insulated);
+   let initial = |x: f64, y: f64| solution.evaluate(x, y, 0., 0., 0.);
+   let report = study.run(&state.device, &state.queue, initial, insulated.then_some(solution)).await;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   report.and_then(|report| {
+      log::info!("{}", report.as_table());
+      report.as_csv()
+   }).map_err(|e| JsValue::from_str(&format!("convergence study failed: {}", e)))
+}
+
+/// starts one ensemble member per kappa and delta_t pair, each from the field as it is now,
+///    replacing any earlier ensemble
+#[wasm_bindgen]
+pub fn start_ensemble(kappas: Vec<f32>, delta_ts: Vec<f32>) -> Result<(), JsValue> {
+
+   if kappas.len() != delta_ts.len() {
+      return Err(JsValue::from_str("every member needs both a kappa and a delta_t"));
+   }
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let members: Vec<crate::ensemble::EnsembleMember> = kappas.iter().zip(delta_ts.iter())
+      .map(|(&kappa, &delta_t)| crate::ensemble::EnsembleMember { kappa, delta_t, initial: None, kappa_map: None })
+      .collect();
+   let started = state.heateq.start_ensemble(&state.device, &state.queue, &members);
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   started.map_err(|e| JsValue::from_str(&format!("{}", e)))
+}
+
+/// queues `steps` steps of every ensemble member, giving back each member's simulated time
+#[wasm_bindgen]
+pub fn run_ensemble(steps: u32) -> Result<Vec<f64>, JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let mut pending_queue = state.pending_queue.replace(Vec::new());
+   let sent = state.heateq.send_ensemble_job(&mut pending_queue, &state.device, steps as u64);
+   _ = state.pending_queue.replace(pending_queue);
+   let member_times = state.heateq.ensemble.as_ref().map(|ensemble| ensemble.member_times.clone());
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   sent.map(|()| member_times.unwrap_or_default()).map_err(|e| JsValue::from_str(&format!("{}", e)))
+}
+
+/// one ensemble member's field as csv
+#[wasm_bindgen]
+pub async fn get_ensemble_member_csv(member: u32) -> Result<String, JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let grid = state.heateq.read_ensemble_member(&state.device, &state.queue, member).await;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   grid.and_then(|grid| grid.writecsv()).map_err(|e| JsValue::from_str(&format!("{}", e)))
+}
+
+/// fits kappa per region of a columns x rows split to fields observed at `times`, given as
+///    csv like get_ensemble_member_csv's, and to a probe series like the probe csv, matched
+///    to the placed probes by name. Runs from the field as it is now with the current kappa
+///    as the first guess, and gives back the kappas row by row.
+#[wasm_bindgen]
+pub async fn fit_kappa(columns: u32, rows: u32, times: Vec<f64>, fields: Vec<String>, probe_series: Option<String>) -> Result<Vec<f32>, JsValue> {
+
+   if times.len() != fields.len() {
+      return Err(JsValue::from_str("every observed field needs a time"));
+   }
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let heateq = &state.heateq;
+   let observations = times.iter().zip(fields.iter())
+      .map(|(&time, field)| crate::rectgrid::RectGrid::readcsv(field)
+         .map(|field| crate::inverse::Observation::Field { time, field }))
+      .collect::<anyhow::Result<Vec<crate::inverse::Observation>>>()
+      .and_then(|mut observations| {
+         if let Some(series) = &probe_series {
+            let probes = heateq.probes.as_ref().map_or(&[][..], |recorder| &recorder.probes[..]);
+            observations.extend(crate::inverse::probe_observations(series, probes)?);
+         }
+         Ok(observations)
+      });
+   let estimate = match observations {
+      Ok(observations) => crate::inverse::InverseProblem::new(observations, columns, rows, heateq.kappa)
+         .solve(heateq, &state.device, &state.queue).await,
+      Err(e) => Err(e)
+   };
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   estimate.map(|estimate| {
+      log::info!("{}", estimate.as_table());
+      estimate.kappas
+   }).map_err(|e| JsValue::from_str(&format!("fit failed: {}", e)))
+}
+
+/// runs `samples` samples from the field as it is now to each of `times`, with kappa and the
+///    starting field randomised by gaussian random fields of the given std and correlation
+///    length, a std of 0 leaving that one alone. Read the maps with get_monte_carlo_csv.
+#[wasm_bindgen]
+pub async fn run_monte_carlo(
+   samples: u32,
+   seed: u64,
+   times: Vec<f64>,
+   kappa_std: f64,
+   kappa_length: f64,
+   initial_std: f64,
+   initial_length: f64,
+) -> Result<(), JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let field = |std_dev: f64, correlation_length: f64| (std_dev > 0.)
+      .then_some(crate::montecarlo::RandomField { std_dev, correlation_length });
+   let study = crate::montecarlo::MonteCarloStudy {
+      samples,
+      seed,
+      kappa_field: field(kappa_std, kappa_length),
+      initial_field: field(initial_std, initial_length),
+      times,
+      batch: 0,
+   };
+   let report = study.run(&state.heateq, &state.device, &state.queue).await;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   let report = report.map_err(|e| JsValue::from_str(&format!("monte carlo failed: {}", e)))?;
+   log::info!("{}", report.as_table());
+   _ = MONTE_CARLO.replace(Some(report));
+   Ok(())
+}
+
+/// one map of the last monte carlo run at its `time`th time as csv, statistic being mean,
+///    std, or lower or upper for the 95% band
+#[wasm_bindgen]
+pub fn get_monte_carlo_csv(time: u32, statistic: String) -> Result<String, JsValue> {
+   MONTE_CARLO.with_borrow(|report| {
+      let Some(report) = report else {return Err(JsValue::from_str("no monte carlo run yet"))};
+      let Some(maps) = report.maps.get(time as usize) else {
+         return Err(JsValue::from_str(&format!("the run only has {} times", report.maps.len())))
+      };
+      let csv = match statistic.as_str() {
+         "mean" => maps.mean.writecsv(),
+         "std" => maps.std_dev.writecsv(),
+         "lower" => maps.band(1.96).0.writecsv(),
+         "upper" => maps.band(1.96).1.writecsv(),
+         _ => return Err(JsValue::from_str("statistic should be mean, std, lower or upper"))
+      };
+      csv.map_err(|e| JsValue::from_str(&format!("{}", e)))
+   })
+}
+
+/// everything needed to carry on this run later, as the bytes of a checkpoint file
+#[wasm_bindgen]
+pub async fn get_checkpoint() -> Result<Vec<u8>, JsValue> {
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let checkpoint = state.heateq.checkpoint(&state.device, &state.queue).await;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   checkpoint.map(|checkpoint| checkpoint.to_bytes())
+      .map_err(|e| JsValue::from_str(&format!("checkpoint failed: {}", e)))
+}
+
+/// the field as a .npy, shape (height, width), in dtype f32 or f64
+#[wasm_bindgen]
+pub async fn get_field_npy(dtype: String) -> Result<Vec<u8>, JsValue> {
+   let dtype = dtype.parse::<crate::npy::NpyDtype>().map_err(|e| JsValue::from_str(&e))?;
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let bytes = state.heateq.field_as_npy(&state.device, &state.queue, dtype).await;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   bytes.map_err(|e| JsValue::from_str(&format!("npy export failed: {}", e)))
+}
+
+/// the field, kappa and a mask of the cells between the walls as one .npz, in dtype f32 or f64
+#[wasm_bindgen]
+pub async fn get_fields_npz(dtype: String) -> Result<Vec<u8>, JsValue> {
+   let dtype = dtype.parse::<crate::npy::NpyDtype>().map_err(|e| JsValue::from_str(&e))?;
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let bytes = state.heateq.fields_as_npz(&state.device, &state.queue, dtype).await;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   bytes.map_err(|e| JsValue::from_str(&format!("npz export failed: {}", e)))
+}
+
+/// Takes the field from a .npy or .npz, starting over at its size if it is not ours. Gives
+///    back kappa, which a .npz may have set, so the controls can show it before update_values.
+#[wasm_bindgen]
+pub async fn load_numpy(bytes: Vec<u8>) -> Result<f32, JsValue> {
+
+   let (width, height) = crate::npy::field_shape(&bytes)
+      .map_err(|e| JsValue::from_str(&format!("could not read numpy file: {}", e)))?;
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Idle(state) if (state.heateq.width as usize, state.heateq.height as usize) == (width, height) => state,
+      _ => state_on_canvas(width as u32, height as u32).await?
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   if let Err(e) = state.heateq.load_numpy(&state.device, &state.queue, &bytes).await {
+      THE_STATE.set(WebApp::Idle(state));
+      return Err(JsValue::from_str(&format!("could not load numpy file: {}", e)));
+   }
+   _ = ANALYTIC_SOLUTION.replace(None);
+
+   let mut pending_queue: Vec<wgpu::CommandBuffer> = Vec::new();
+   state.heateq.send_color_job(&mut pending_queue, &state.device);
+   state.heateq.color_to_texture(&mut pending_queue, &state.device, &state.texture_buffer);
+   _ = state.pending_queue.replace(pending_queue);
+   _ = state.render();
+   let kappa = state.heateq.kappa;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   Ok(kappa)
+}
+
+/// carries on from a checkpoint get_checkpoint gave, starting over at its size if it is not
+///    ours. Gives back the settings the controls should show, since update_values would
+///    otherwise put the old ones straight back.
+#[wasm_bindgen]
+pub async fn restore_checkpoint(bytes: Vec<u8>) -> Result<crate::checkpoint::RunSettings, JsValue> {
+
+   let checkpoint = crate::checkpoint::Checkpoint::from_bytes(&bytes)
+      .map_err(|e| JsValue::from_str(&format!("could not read checkpoint: {}", e)))?;
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Idle(state) if (state.heateq.width, state.heateq.height) == (checkpoint.width, checkpoint.height) => state,
+      _ => state_on_canvas(checkpoint.width, checkpoint.height).await?
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   if let Err(e) = state.heateq.restore(&state.device, &state.queue, &checkpoint) {
+      THE_STATE.set(WebApp::Idle(state));
+      return Err(JsValue::from_str(&format!("could not restore checkpoint: {}", e)));
+   }
+   _ = ANALYTIC_SOLUTION.replace(None);
+
+   let mut pending_queue: Vec<wgpu::CommandBuffer> = Vec::new();
+   state.heateq.send_color_job(&mut pending_queue, &state.device);
+   state.heateq.color_to_texture(&mut pending_queue, &state.device, &state.texture_buffer);
+   _ = state.pending_queue.replace(pending_queue);
+   _ = state.render();
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   Ok(checkpoint.settings())
+}
+
+
+/// the field with kappa, the heat flux and any accumulated maps as a VTK .vti, on a domain
+///    x_length by y_length
+#[wasm_bindgen]
+pub async fn get_vti(x_length: f64, y_length: f64) -> Result<Vec<u8>, JsValue> {
+   if !(x_length > 0. && y_length > 0.) {return Err(JsValue::from_str("a domain needs lengths above 0"))}
+
+   let globalstate = THE_STATE.replace(WebApp::Uninitialized);
+
+   let mut state: WgpuState = match globalstate {
+      WebApp::Uninitialized => {
+         log::info!("Can not do! Uninitialized");
+         return Err(JsValue::from_str("Can not do! Uninitialized"));
+      }
+      WebApp::Idle(state) => state
+   };
+
+   let pending_queue = state.pending_queue.replace(Vec::new());
+   state.heateq.submit(&state.queue, pending_queue);
+
+   let bytes = state.heateq.fields_as_vti(&state.device, &state.queue, x_length, y_length).await;
+
+   THE_STATE.set(WebApp::Idle(state));
+
+   bytes.map_err(|e| JsValue::from_str(&format!("vti export failed: {}", e)))
+}
+
+/// a .vti of a series, and the name the .pvd expects it under
+#[wasm_bindgen(getter_with_clone)]
+pub struct VtkFrame {
+   pub name: String,
+   pub bytes: Vec<u8>,
+}
+
+/// Takes the field as it is now as the next frame of a series, starting the series over if
+///    the prefix is not the one it was started with. get_vtk_pvd then lists every frame.
+#[wasm_bindgen]
+pub async fn add_vtk_frame(prefix: String, x_length: f64, y_length: f64) -> Result<VtkFrame, JsValue> {
+   let bytes = get_vti(x_length, y_length).await?;
+   let sim_time = get_sim_time()?;
+   let name = VTK_SERIES.with_borrow_mut(|series| {
+      let series = match series {
+         Some(series) if series.prefix == prefix => series,
+         _ => series.insert(crate::vtk::VtkSeries::new(&prefix))
+      };
+      series.add(sim_time)
+   });
+   Ok(VtkFrame { name, bytes })
+}
+
+/// the .pvd of the series add_vtk_frame has been taking, and its name
+#[wasm_bindgen]
+pub fn get_vtk_pvd() -> Result<VtkFrame, JsValue> {
+   VTK_SERIES.with_borrow(|series| match series {
+      Some(series) => Ok(VtkFrame { name: series.pvd_path(), bytes: series.as_pvd().into_bytes() }),
+      None => Err(JsValue::from_str("no frames taken yet"))
+   })
+}
+
+#[wasm_bindgen]
+pub fn clear_vtk_series() {
+   _ = VTK_SERIES.take();
 }
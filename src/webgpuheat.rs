@@ -3,10 +3,29 @@ use bytemuck::cast_slice;
 
 
 #[cfg(not(target_arch = "wasm32"))]
-use tokio::sync::oneshot::{Receiver, channel};
-
-#[cfg(target_arch = "wasm32")]
-use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot::channel;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::supertimestep::SuperTimeStepper;
+use crate::adi::AdiStepper;
+use crate::adaptive::{AdaptiveOutcome, AdaptiveStepper};
+use crate::reduction::{FieldReducer, FieldStats};
+use crate::colorrange::{AutoRanger, ColorRange};
+use crate::probes::{Probe, ProbeRecorder};
+use crate::rectgrid::RectGrid;
+use crate::flux::{FluxComputer, ViewField};
+use crate::boundary::{BoundaryCondition, Edge, EdgeFluxReport, EdgeFluxTracker};
+use crate::isotherms::{Isotherm, IsothermOverlay, extract_isotherms};
+use crate::accumulators::AccumulatorMaps;
+use crate::ensemble::{Ensemble, EnsembleMember};
+use crate::checkpoint::Checkpoint;
+use crate::runcontrol::{RunController, RunStatus, RunUntil, StopCondition};
+use crate::events::{EventStream, RecordedWork};
+use crate::npy::{NpyDtype, read_npy, read_npz, write_npy, write_npz};
+use crate::vtk::{VtkGeometry, write_vti};
+use crate::tiledheat::HeatTiles;
 
 fn gen_print<T>(s: T) where T: core::fmt::Display {
    #[cfg(target_arch = "wasm32")]
@@ -15,20 +34,136 @@ fn gen_print<T>(s: T) where T: core::fmt::Display {
    println!("{}",s)
 }
 
-#[derive(Debug)]
+/// What the gpu has got to, as heard through an EventStream. Submissions are numbered from 1
+///    in the order they went to the queue, so the done events can be matched to them.
+#[derive(Debug, Clone, PartialEq)]
 pub enum ComputeRelevantEvent {
-   ComputeDoneNowColor,
-   ComputeIsWorking,
-   ColorIsDone,
-   ColorIsWorking,
-   ColorIsCopying,
-   ColorCopyDone
+   Submitted(u64),
+   /// the steps in that submission are done, so the field is ready to colour
+   ComputeDoneNowColor(u64),
+   ColorIsDone(u64),
+   /// the heatmap has reached the texture
+   ColorCopyDone(u64),
+   /// a buffer being read back is mapped and can be looked at
+   ReadbackReady,
+   Error(String),
+}
+
+impl ComputeRelevantEvent {
+   pub fn name(&self) -> &'static str {
+      match self {
+         Self::Submitted(_) => "submitted",
+         Self::ComputeDoneNowColor(_) => "compute_done",
+         Self::ColorIsDone(_) => "color_done",
+         Self::ColorCopyDone(_) => "copy_done",
+         Self::ReadbackReady => "readback_ready",
+         Self::Error(_) => "error",
+      }
+   }
+
+   pub fn submission(&self) -> Option<u64> {
+      match self {
+         Self::Submitted(n) | Self::ComputeDoneNowColor(n) | Self::ColorIsDone(n) | Self::ColorCopyDone(n) => Some(*n),
+         _ => None
+      }
+   }
+}
+
+impl std::fmt::Display for ComputeRelevantEvent {
+   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      match self {
+         Self::Error(message) => write!(f, "error: {}", message),
+         event => match event.submission() {
+            Some(submission) => write!(f, "{} {}", event.name(), submission),
+            None => write!(f, "{}", event.name()),
+         }
+      }
+   }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Integrator {
+   /// explicit midpoint RK2, stable up to the explicit limit
+   Rk2,
+   /// RKL2 super time stepping, as many stages as the chosen delta_t needs to be stable
+   Rkl2,
+   /// Peaceman-Rachford ADI, implicit so stable for any delta_t
+   Adi,
+}
+
+impl std::str::FromStr for Integrator {
+   type Err = String;
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      match s {
+         "rk2" => Ok(Integrator::Rk2),
+         "rkl2" => Ok(Integrator::Rkl2),
+         "adi" => Ok(Integrator::Adi),
+         other => Err(format!("unknown integrator {}, expected rk2, rkl2 or adi", other))
+      }
+   }
+}
+
+/// A long run handed to the gpu a bounded chunk at a time, so that no single
+///    submission is big enough to hang the gpu or trip a driver watchdog.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkedRun {
+   pub total_steps: u64,
+   pub done_steps: u64,
+   pub cancelled: bool,
+}
+
+impl ChunkedRun {
+   pub fn remaining(&self) -> u64 {self.total_steps - self.done_steps}
+   pub fn is_finished(&self) -> bool {self.cancelled || self.done_steps >= self.total_steps}
 }
 
 
 #[allow(non_snake_case)]
 pub struct HeatComputer {
    pub iteration_quantity: u32,
+   // no single submission will carry more steps than this, whatever iteration_quantity says
+   pub max_steps_per_submission: u32,
+   pub active_run: Option<ChunkedRun>,
+   // a run that stops on conditions rather than a step count, never at once with active_run
+   pub run_controller: Option<RunController>,
+   // shared so whoever started a run (or another thread) can cancel it between chunks
+   pub cancel_flag: Arc<AtomicBool>,
+   // the values last sent through update_values, kept so we know them without a readback
+   pub kappa: f32,
+   pub delta_t: f32,
+   pub integrator: Integrator,
+   // built on demand for the current kappa and delta_t when integrator is Rkl2
+   pub sts: Option<SuperTimeStepper>,
+   pub adi: Option<AdiStepper>,
+   // only there once adaptive stepping has been asked for
+   pub adaptive: Option<AdaptiveStepper>,
+   /// simulated time so far, advanced by every step taken
+   pub sim_time: f64,
+   /// fixed steps all count as accepted
+   pub accepted_steps: u64,
+   pub rejected_steps: u64,
+   // stats of data_buffer, built the first time someone asks
+   pub reducer: Option<FieldReducer>,
+   pub color_range: ColorRange,
+   // what vis_minT and vis_maxT were last set to by hand, the auto ranges never come back
+   pub vis_min_t: f32,
+   pub vis_max_t: f32,
+   pub auto_ranger: Option<AutoRanger>,
+   pub probes: Option<ProbeRecorder>,
+   pub view_field: ViewField,
+   pub flux: Option<FluxComputer>,
+   /// what each edge does, in the order y=0, x=0, y=1, x=1. All insulated to begin with.
+   pub boundary_conditions: [BoundaryCondition; 4],
+   // only there once someone asks for the heat through the edges
+   pub edge_flux: Option<EdgeFluxTracker>,
+   // isotherms drawn over the heatmap, if any
+   pub isotherm_overlay: Option<IsothermOverlay>,
+   // peak, time above and first arrival maps, once started
+   pub accumulators: Option<AccumulatorMaps>,
+   // a batch of other fields the same size, stepped alongside but apart from this one
+   pub ensemble: Option<Ensemble>,
+   // the strips of a grid too big for one buffer, which then hold the field instead of us
+   pub tiling: Option<HeatTiles>,
    pub width: u32,
    pub height: u32,
    pub pad_per_line: u32,
@@ -49,15 +184,18 @@ pub struct HeatComputer {
    pub export_buffer: wgpu::Buffer,
    pub width_buffer: wgpu::Buffer,
    pub height_buffer: wgpu::Buffer,
+   pub grid_height_buffer: wgpu::Buffer,
    pub kappa_buffer: wgpu::Buffer,
    pub delta_t_buffer: wgpu::Buffer,
    pub delta_t_2_buffer: wgpu::Buffer,
    pub pad_buffer: wgpu::Buffer,
+   pub boundary_buffer: wgpu::Buffer,
 
    // When i have more confidence, these should be an vec of 'steps'
    //    although that may require the above pipelines to be changed to
    //    Arc<wgpu::Buffer> types.
    pub fix_boundary_conditions_bg: wgpu::BindGroup,
+   pub midpoint_boundary_conditions_bg: wgpu::BindGroup,
    pub stage_one_bind_group: wgpu::BindGroup,
    pub stage_two_bind_group: wgpu::BindGroup,
    pub stage_three_bind_group: wgpu::BindGroup,
@@ -71,32 +209,22 @@ pub struct HeatComputer {
    pub heat_hue_pipeline: wgpu::ComputePipeline,
    pub heat_hue_bind_group: wgpu::BindGroup,
 
-   pub workgroup_quantity: u32,
-
-
-   // NOTE: my understanding is that wasm will not start threads in the same way rust will.
-   //    for this reason it makes sense to use a sender-receiver in rust to avoid thread stuff
-   //    and an arc-mutex in wasm which ostensibly just lets us send a message that doesnt anger
-   //    the rust-compiler borrow checker.
+   /// the workgroups for the shaders that run once per cell, see linear_workgroups
+   pub workgroup_quantity: (u32, u32),
 
-   #[cfg(not(target_arch = "wasm32"))]
-   pub progress: Option<
-      (ComputeRelevantEvent, Receiver<ComputeRelevantEvent>)
-   >,
 
-   #[cfg(target_arch = "wasm32")]
-   pub progress: Arc<
-      Mutex<
-         Option<ComputeRelevantEvent>
-      >
-   >
+   /// who to tell when work is submitted and done. Only what goes through submit is heard
+   ///    about as submitted.
+   pub events: EventStream,
+   // what has been recorded into a pending queue since the last submit
+   pub recorded: RecordedWork,
 }
 
 
 
 
 
-fn helper_basic_compute_shader(
+pub(crate) fn helper_basic_compute_shader(
    device: &wgpu::Device,
    label: Option<&str>,
    shader_module: &wgpu::ShaderModule
@@ -111,7 +239,7 @@ fn helper_basic_compute_shader(
    })
 }
 
-fn helper_compute_interim_data_buffer(
+pub(crate) fn helper_compute_interim_data_buffer(
    device: &wgpu::Device,
    label: Option<&str>,
    size: u64,
@@ -119,12 +247,13 @@ fn helper_compute_interim_data_buffer(
    device.create_buffer(&wgpu::BufferDescriptor {
       label: label,
       size: size,
-      usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::STORAGE,
+      // COPY_SRC so they can be copied out of
+      usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::STORAGE,
       mapped_at_creation: false,
    })
 }
 
-fn helper_param_buffer(
+pub(crate) fn helper_param_buffer(
    device: &wgpu::Device,
    label: Option<&str>,
    size: u64,
@@ -137,7 +266,19 @@ fn helper_param_buffer(
    })
 }
 
-fn helper_compute_bind_group(
+/// the most workgroups we dispatch along x. the downlevel limits we negotiate up from
+///    promise 65535, so the per cell shaders can hard code it
+pub const WORKGROUPS_PER_ROW: u32 = 65535;
+
+/// workgroups of 64 for one invocation per item, laid out as rows of WORKGROUPS_PER_ROW so
+///    no single dimension runs past the limit. the shaders find their item at
+///    gid.x + gid.y * WORKGROUPS_PER_ROW * 64
+pub fn linear_workgroups(items: u32) -> (u32, u32) {
+   let groups = items.div_ceil(64);
+   (groups.min(WORKGROUPS_PER_ROW), groups.div_ceil(WORKGROUPS_PER_ROW))
+}
+
+pub(crate) fn helper_compute_bind_group(
    device: &wgpu::Device,
    label: Option<&str>,
    pipeline: &wgpu::ComputePipeline,
@@ -163,12 +304,81 @@ fn helper_compute_bind_group(
 
 
 impl HeatComputer {
+   /// the most cells one HeatComputer can hold under these limits, which is however many
+   ///    fit data_buffer in one storage binding
+   pub fn max_cells(limits: &wgpu::Limits) -> u64 {
+      (limits.max_storage_buffer_binding_size as u64 / 4)
+         .min(limits.max_buffer_size / 4)
+   }
+
+   /// whether a width x height grid fits on a device with these limits, in one buffer or
+   ///    cut into tiles
+   pub fn check_size(width: u32, height: u32, limits: &wgpu::Limits) -> anyhow::Result<()> {
+      if (width as u64) * (height as u64) <= Self::max_cells(limits) {return Ok(())}
+      HeatTiles::rows_per_tile(width, limits).map(|_| ())
+   }
+
+   /// a computer for the field, cut into tiles if it is more than max_cells. Check the size
+   ///    with check_size first, a grid too wide to tile panics here.
    pub fn new(
       initial_data: &Vec<f32>,
       width: u32,
       height: u32,
       device: &wgpu::Device,
       //queue: &wgpu::Queue,
+   ) -> Self {
+      let limits = device.limits();
+      if (width as u64) * (height as u64) <= Self::max_cells(&limits) {
+         return Self::new_with_grid_height(initial_data, width, height, height, device)
+      }
+      HeatTiles::rows_per_tile(width, &limits)
+         .and_then(|rows_per_tile| Self::new_tiled(initial_data, width, height, rows_per_tile, device))
+         .expect("the grid was checked with check_size")
+   }
+
+   /// the field cut into strips of at most rows_per_tile rows, each its own HeatComputer. This
+   ///    one keeps a placeholder 3x3 field and hands the steps, the settings, coloring and
+   ///    reading or writing the field to the tiles; see untiled for what they can't do.
+   pub fn new_tiled(
+      initial_data: &[f32],
+      width: u32,
+      height: u32,
+      rows_per_tile: u32,
+      device: &wgpu::Device,
+   ) -> anyhow::Result<Self> {
+      let tiling = HeatTiles::new(initial_data, width, height, rows_per_tile, device)?;
+      let mut placeholder = Self::new_with_grid_height(&vec![0.; 9], 3, 3, 3, device);
+      placeholder.width = width;
+      placeholder.height = height;
+      placeholder.tiling = Some(tiling);
+      Ok(placeholder)
+   }
+
+   /// fails when we are tiled, for the things that need the whole field in one buffer. Tiles
+   ///    take RK2 steps, their edges, coloring and whole field reads and writes, and that is all.
+   pub fn untiled(&self, what: &str) -> anyhow::Result<()> {
+      match &self.tiling {
+         Some(tiling) => anyhow::bail!(
+            "{} needs the whole field in one buffer, and this {}x{} grid is cut into {} tiles",
+            what, self.width, self.height, tiling.tiles.len()
+         ),
+         None => Ok(())
+      }
+   }
+
+   // the tiles' computers, which follow every setting we take
+   fn tile_computers(&mut self) -> impl Iterator<Item = &mut HeatComputer> {
+      self.tiling.iter_mut().flat_map(|tiling| tiling.tiles.iter_mut().map(|tile| &mut tile.computer))
+   }
+
+   /// the same as new, but for when this is a strip of rows out of a taller grid of
+   ///    grid_height rows, so the laplacian still uses the spacing of the whole grid.
+   pub(crate) fn new_with_grid_height(
+      initial_data: &Vec<f32>,
+      width: u32,
+      height: u32,
+      grid_height: u32,
+      device: &wgpu::Device,
    ) -> Self {
       assert_eq!(initial_data.len() as u32, width*height);
 
@@ -224,6 +434,11 @@ impl HeatComputer {
           contents: bytemuck::cast_slice(&[height]),
           usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
       });
+      let grid_height_buffer = device.create_buffer_init(&BufferInitDescriptor {
+          label: Some("grid height"),
+          contents: bytemuck::cast_slice(&[grid_height]),
+          usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      });
       let kappa_buffer = helper_param_buffer(device,Some("kappa"),4);
       let delta_t_buffer = helper_param_buffer(device,Some("delta_t"),4);
       // this is just the same number divided by two so we can reuse the pipeline
@@ -233,21 +448,33 @@ impl HeatComputer {
           contents: bytemuck::cast_slice(&[pad_per_line]),
           usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
       });
+      let boundary_conditions = [BoundaryCondition::Insulated; 4];
+      let boundary_buffer = device.create_buffer_init(&BufferInitDescriptor {
+          label: Some("boundary conditions"),
+          contents: &BoundaryCondition::as_uniform(&boundary_conditions),
+          usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      });
 
       // if pipelines are like gpu function calls, this is where we identify our variables in address space.
       // in that sense we may freely put in different buffers like swapping arguments to a function
 
-      // shader that fixes the insulating boundary conditions. we want to apply this before we compute
-      //    the laplacian since it effectively fixes the laplacian equal to zero on the boundary.
+      // shader that fixes the boundary conditions. we want to apply this before we compute
+      //    the laplacian since the edge cells are what the laplacian next to the edge sees.
       let fix_boundary_conditions_bg = helper_compute_bind_group(
          device, None, &fix_boundary_conditions_ppln,
-         &[&data_buffer, &width_buffer, &height_buffer]
+         &[&data_buffer, &width_buffer, &height_buffer, &boundary_buffer]
+      );
+      // the same for the midpoint, so the second laplacian sees edges that match the midpoint
+      //    field rather than the copies made at the start of the step
+      let midpoint_boundary_conditions_bg = helper_compute_bind_group(
+         device, None, &fix_boundary_conditions_ppln,
+         &[&midpoint_buffer, &width_buffer, &height_buffer, &boundary_buffer]
       );
 
       // compute laplacian of data
       let stage_one_bind_group = helper_compute_bind_group(
          device, None, &laplacian_pipeline,
-         &[&data_buffer, &laplacian_buffer, &width_buffer, &height_buffer]
+         &[&data_buffer, &laplacian_buffer, &width_buffer, &height_buffer, &grid_height_buffer]
       );
       // compute RK2 midpoint using laplacian
       let stage_two_bind_group = helper_compute_bind_group(
@@ -257,7 +484,7 @@ impl HeatComputer {
       // reuse laplacian pipeline to compute laplacian using midpoint buffer
       let stage_three_bind_group = helper_compute_bind_group(
          device, None, &laplacian_pipeline,
-         &[&midpoint_buffer, &midpoint_laplacian_buffer, &width_buffer, &height_buffer]
+         &[&midpoint_buffer, &midpoint_laplacian_buffer, &width_buffer, &height_buffer, &grid_height_buffer]
       );
       // reuse RK2 midpoint pipeline but with delta_t instead of delta_t/2 to compute RK2 result
       let stage_four_bind_group = helper_compute_bind_group(
@@ -308,12 +535,15 @@ impl HeatComputer {
          export_buffer,
          width_buffer,
          height_buffer,
+         grid_height_buffer,
          kappa_buffer,
          delta_t_buffer,
          delta_t_2_buffer,
          pad_buffer,
+         boundary_buffer,
 
          fix_boundary_conditions_bg,
+         midpoint_boundary_conditions_bg,
          stage_one_bind_group,
          stage_two_bind_group,
          stage_three_bind_group,
@@ -328,14 +558,38 @@ impl HeatComputer {
          heat_hue_bind_group,
 
          iteration_quantity: 100,
-
-         workgroup_quantity: initial_data.len().div_ceil(64) as u32,
-
-         #[cfg(not(target_arch = "wasm32"))]
-         progress: None,
-
-         #[cfg(target_arch = "wasm32")]
-         progress: Arc::new(Mutex::new(None))
+         max_steps_per_submission: 1000,
+         active_run: None,
+         run_controller: None,
+         cancel_flag: Arc::new(AtomicBool::new(false)),
+         kappa: 0.,
+         delta_t: 0.,
+         integrator: Integrator::Rk2,
+         sts: None,
+         adi: None,
+         adaptive: None,
+         sim_time: 0.,
+         accepted_steps: 0,
+         rejected_steps: 0,
+         reducer: None,
+         color_range: ColorRange::Fixed,
+         vis_min_t: 0.,
+         vis_max_t: 0.,
+         auto_ranger: None,
+         probes: None,
+         view_field: ViewField::Temperature,
+         flux: None,
+         boundary_conditions,
+         edge_flux: None,
+         isotherm_overlay: None,
+         accumulators: None,
+         ensemble: None,
+         tiling: None,
+
+         workgroup_quantity: linear_workgroups(width * height),
+
+         events: EventStream::default(),
+         recorded: RecordedWork::default(),
       }
    }
 
@@ -349,90 +603,946 @@ impl HeatComputer {
       #[allow(non_snake_case)] maxT: f32
    ) {
       self.iteration_quantity = n_times;
+      self.kappa = kappa;
+      self.delta_t = delta_t;
+      self.vis_min_t = minT;
+      self.vis_max_t = maxT;
       queue.write_buffer(&self.kappa_buffer, 0, cast_slice(&[kappa]));
       queue.write_buffer(&self.delta_t_buffer, 0, cast_slice(&[delta_t.clone()]));
       queue.write_buffer(&self.delta_t_2_buffer, 0, cast_slice(&[delta_t / 2.0]));
       queue.write_buffer(&self.vis_minT_buffer, 0, cast_slice(&[minT]));
       queue.write_buffer(&self.vis_maxT_buffer, 0, cast_slice(&[maxT]));
+      self.sync_step_trackers(queue);
+      for computer in self.tile_computers() {
+         computer.update_values(queue, n_times, kappa, delta_t, minT, maxT);
+      }
       queue.submit([]);
 
 
    }
 
+   /// the colormap range while color_range is Fixed
+   pub fn set_vis_range(&mut self, queue: &wgpu::Queue, min_t: f32, max_t: f32) {
+      self.vis_min_t = min_t;
+      self.vis_max_t = max_t;
+      queue.write_buffer(&self.vis_minT_buffer, 0, cast_slice(&[min_t]));
+      queue.write_buffer(&self.vis_maxT_buffer, 0, cast_slice(&[max_t]));
+      for computer in self.tile_computers() {
+         computer.set_vis_range(queue, min_t, max_t);
+      }
+   }
+
+   pub fn set_kappa(&mut self, queue: &wgpu::Queue, kappa: f32) {
+      self.kappa = kappa;
+      queue.write_buffer(&self.kappa_buffer, 0, cast_slice(&[kappa]));
+      self.sync_step_trackers(queue);
+      for computer in self.tile_computers() {
+         computer.set_kappa(queue, kappa);
+      }
+   }
+
+   pub fn set_delta_t(&mut self, queue: &wgpu::Queue, delta_t: f32) {
+      self.delta_t = delta_t;
+      queue.write_buffer(&self.delta_t_buffer, 0, cast_slice(&[delta_t]));
+      queue.write_buffer(&self.delta_t_2_buffer, 0, cast_slice(&[delta_t / 2.0]));
+      self.sync_step_trackers(queue);
+      for computer in self.tile_computers() {
+         computer.set_delta_t(queue, delta_t);
+      }
+   }
+
+   // whatever runs after every step integrates with the same kappa and delta_t the steps take
+   fn sync_step_trackers(&mut self, queue: &wgpu::Queue) {
+      self.set_step_tracker_delta_t(queue, self.delta_t);
+   }
+
+   fn set_step_tracker_delta_t(&mut self, queue: &wgpu::Queue, delta_t: f32) {
+      if let Some(mut edge_flux) = self.edge_flux.take() {
+         edge_flux.set_params(self, queue, self.kappa, delta_t);
+         self.edge_flux = Some(edge_flux);
+      }
+      if let Some(mut accumulators) = self.accumulators.take() {
+         accumulators.set_delta_t(self, queue, delta_t);
+         self.accumulators = Some(accumulators);
+      }
+   }
+
+   /// whatever has to see the field at the end of every step
+   fn record_step_trackers(&self, gputodo: &mut wgpu::ComputePass) {
+      if let Some(edge_flux) = &self.edge_flux {
+         self.record_boundary_conditions(gputodo);
+         edge_flux.record(gputodo);
+      }
+      if let Some(accumulators) = &self.accumulators {
+         accumulators.record(self, gputodo);
+      }
+   }
+
+   /// what one edge does from the next step on
+   pub fn set_boundary_condition(&mut self, queue: &wgpu::Queue, edge: Edge, condition: BoundaryCondition) {
+      self.boundary_conditions[edge.index()] = condition;
+      queue.write_buffer(&self.boundary_buffer, 0, &BoundaryCondition::as_uniform(&self.boundary_conditions));
+      for computer in self.tile_computers() {
+         computer.set_boundary_condition(queue, edge, condition);
+      }
+   }
+
+   /// the integrator for the next steps. Tiles only take RK2 steps.
+   pub fn set_integrator(&mut self, integrator: Integrator) -> anyhow::Result<()> {
+      if integrator != Integrator::Rk2 {
+         self.untiled(&format!("the {:?} integrator", integrator))?;
+      }
+      self.integrator = integrator;
+      Ok(())
+   }
+
+   /// makes sure whatever the integrator needs is built for the current kappa and delta_t.
+   ///    called before we record any steps.
+   pub fn prepare_integrator(&mut self, device: &wgpu::Device) {
+      if self.tiling.is_some() {return}
+      match self.integrator {
+         Integrator::Rk2 => {}
+         Integrator::Rkl2 => {
+            let stale = match &self.sts {
+               Some(sts) => sts.kappa != self.kappa || sts.delta_t != self.delta_t,
+               None => true
+            };
+            if stale {
+               let sts = SuperTimeStepper::new(self, device, self.kappa, self.delta_t);
+               gen_print(format!("rkl2 using {} stages per step", sts.stages));
+               self.sts = Some(sts);
+            }
+         }
+         Integrator::Adi => {
+            let stale = match &self.adi {
+               Some(adi) => adi.kappa != self.kappa || adi.delta_t != self.delta_t,
+               None => true
+            };
+            if stale {
+               self.adi = Some(AdiStepper::new(self, device, self.kappa, self.delta_t));
+            }
+         }
+      }
+   }
+
+   /// records `steps` full steps of the current integrator into a compute pass.
+   pub fn record_steps(&self, gputodo: &mut wgpu::ComputePass, steps: u32) {
+      // this is also where we define the steps the gpu should take. so far as
+      //    i can tell, this is similar to sending an io monad to the gpu
+      for _ in 0..steps {
+         match (&self.tiling, self.integrator, &self.sts, &self.adi) {
+            (Some(tiling), _, _, _) => tiling.record_step(gputodo),
+            (None, Integrator::Rkl2, Some(sts), _) => sts.record_step(self, gputodo),
+            (None, Integrator::Adi, _, Some(adi)) => adi.record_step(self, gputodo),
+            _ => {
+               self.record_boundary_conditions(gputodo);
+               self.record_midpoint_half(gputodo);
+               self.record_final_half(gputodo);
+            }
+         }
+         self.record_step_trackers(gputodo);
+      }
+   }
+
+   // a step is split in three so the other integrators can reuse the pieces
+
+   pub fn record_boundary_conditions(&self, gputodo: &mut wgpu::ComputePass) {
+      let boundary_conds_wg_quant = (self.width*2 + self.height*2).div_ceil(self.workgroup_size);
+
+      gputodo.set_pipeline(&self.fix_boundary_conditions_ppln);
+      gputodo.set_bind_group(0, &self.fix_boundary_conditions_bg, &[]);
+      gputodo.dispatch_workgroups(boundary_conds_wg_quant, 1 , 1);
+   }
+
+   /// laplacian of data, then the RK2 midpoint from it
+   pub fn record_midpoint_half(&self, gputodo: &mut wgpu::ComputePass) {
+      let (row_workgroups, rows) = self.workgroup_quantity;
+      let x_workgroup_quantity = self.width.div_ceil(8);
+      let y_workgroup_quantity = self.height.div_ceil(8);
+
+      gputodo.set_pipeline(&self.laplacian_pipeline);
+      gputodo.set_bind_group(0, &self.stage_one_bind_group, &[]);
+      gputodo.dispatch_workgroups(x_workgroup_quantity, y_workgroup_quantity, 1);
+
+      gputodo.set_pipeline(&self.iterate_pipeline);
+      gputodo.set_bind_group(0, &self.stage_two_bind_group, &[]);
+      gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+
+      self.record_midpoint_boundary_conditions(gputodo);
+   }
+
+   /// the boundary conditions again, on the midpoint buffer
+   pub fn record_midpoint_boundary_conditions(&self, gputodo: &mut wgpu::ComputePass) {
+      let boundary_conds_wg_quant = (self.width*2 + self.height*2).div_ceil(self.workgroup_size);
+
+      gputodo.set_pipeline(&self.fix_boundary_conditions_ppln);
+      gputodo.set_bind_group(0, &self.midpoint_boundary_conditions_bg, &[]);
+      gputodo.dispatch_workgroups(boundary_conds_wg_quant, 1 , 1);
+   }
+
+   /// laplacian of the midpoint, the full step from it, and the move back into data
+   pub fn record_final_half(&self, gputodo: &mut wgpu::ComputePass) {
+      let (row_workgroups, rows) = self.workgroup_quantity;
+      let x_workgroup_quantity = self.width.div_ceil(8);
+      let y_workgroup_quantity = self.height.div_ceil(8);
+
+      gputodo.set_pipeline(&self.laplacian_pipeline);
+      gputodo.set_bind_group(0, &self.stage_three_bind_group, &[]);
+      gputodo.dispatch_workgroups(x_workgroup_quantity, y_workgroup_quantity, 1);
+
+      gputodo.set_pipeline(&self.iterate_pipeline);
+      gputodo.set_bind_group(0, &self.stage_four_bind_group, &[]);
+      gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+
+      gputodo.set_pipeline(&self.buffer_move_pipeline);
+      gputodo.set_bind_group(0, &self.stage_five_bind_group, &[]);
+      gputodo.dispatch_workgroups(row_workgroups, rows, 1);
+   }
+
+   /// `steps` steps as command buffers of no more than max_steps_per_submission steps each
+   fn encode_steps(&self, device: &wgpu::Device, steps: u32) -> Vec<wgpu::CommandBuffer> {
+      let mut command_buffers = Vec::new();
+
+      let mut remaining = steps;
+      let mut step_count = self.accepted_steps;
+      while remaining > 0 {
+         let chunk = remaining.min(self.max_steps_per_submission.max(1));
+         let mut encoder = device.create_command_encoder(&Default::default());
+         // these braces are to make sure we return any refs borrowed in them.
+         //    principly, encoder must be returned since it is borrowed by
+         //    begin_compute_pass, and is needed so we can call encoder.finish().
+         {
+            let mut gputodo = encoder.begin_compute_pass(&Default::default());
+            match &self.probes {
+               None => self.record_steps(&mut gputodo, chunk),
+               // stop at every multiple of probes.every for a sample, advance_clock
+               //    notes the times of the same samples
+               Some(probes) => {
+                  let every = probes.every as u64;
+                  let mut left = chunk as u64;
+                  while left > 0 {
+                     let run = left.min(every - step_count % every);
+                     self.record_steps(&mut gputodo, run as u32);
+                     step_count += run;
+                     left -= run;
+                     if step_count.is_multiple_of(every) {probes.record_sample(&mut gputodo)}
+                  }
+               }
+            }
+         }
+         command_buffers.push(encoder.finish());
+         remaining -= chunk;
+      }
+
+      command_buffers
+   }
+
+   /// queues iteration_quantity steps, cut into command buffers of at most
+   ///    max_steps_per_submission steps so no one of them keeps the gpu busy for long
    pub fn send_compute_job(
       &mut self,
       pending_queue: &mut Vec<wgpu::CommandBuffer>,
       device: &wgpu::Device,
    ) {
+      self.prepare_integrator(device);
+      pending_queue.extend(self.encode_steps(device, self.iteration_quantity));
+      self.advance_clock(self.iteration_quantity as u64);
+   }
+
+   /// starts the simulated time and step counts over from 0, for a run starting afresh.
+   ///    Probe samples already taken are moved to match, so they come out before 0.
+   pub fn reset_clock(&mut self, queue: &wgpu::Queue) {
+      if let Some(probes) = &mut self.probes {
+         for time in probes.sample_times.iter_mut() {
+            *time -= self.sim_time;
+         }
+      }
+      if let Some(accumulators) = &self.accumulators {
+         accumulators.set_clock(queue, 0.);
+      }
+      self.sim_time = 0.;
+      self.accepted_steps = 0;
+      self.rejected_steps = 0;
+   }
+
+   fn advance_clock(&mut self, steps: u64) {
+      self.recorded.compute = true;
+      if let Some(probes) = &mut self.probes {
+         let every = probes.every as u64;
+         let mut sampled_at = (self.accepted_steps / every + 1) * every;
+         while sampled_at <= self.accepted_steps + steps {
+            probes.note_sample(self.sim_time + (sampled_at - self.accepted_steps) as f64 * self.delta_t as f64);
+            sampled_at += every;
+         }
+      }
+      self.sim_time += steps as f64 * self.delta_t as f64;
+      self.accepted_steps += steps;
+   }
+
+   /// submits a pending queue and tells the event stream what was in it
+   pub fn submit(&mut self, queue: &wgpu::Queue, pending_queue: Vec<wgpu::CommandBuffer>) {
+      queue.submit(pending_queue);
+      self.events.submitted(queue, std::mem::take(&mut self.recorded));
+   }
+
+   /// the current field read back into a RectGrid. Anything still in a pending queue should
+   ///    be submitted first.
+   pub async fn read_field(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<RectGrid> {
+      if let Some(tiling) = &self.tiling {return tiling.read_field(device, queue).await}
+      self.read_buffer_as_grid(device, queue, &self.data_buffer).await
+   }
+
+   /// reads back any width x height field of ours. Uses its own staging buffer so it never
+   ///    fights over export_buffer.
+   pub async fn read_buffer_as_grid(&self, device: &wgpu::Device, queue: &wgpu::Queue, buffer: &wgpu::Buffer) -> anyhow::Result<RectGrid> {
+      let size = (self.width * self.height) as u64 * 4;
+      let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("field readback"),
+         size,
+         usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+         mapped_at_creation: false,
+      });
+
       let mut encoder = device.create_command_encoder(&Default::default());
+      encoder.copy_buffer_to_buffer(buffer, 0, &staging_buffer, 0, size);
+      let (sender, receiver) = tokio::sync::oneshot::channel();
+      let events = self.events.clone();
+      encoder.map_buffer_on_submit(&staging_buffer, wgpu::MapMode::Read, ..,
+         move |result| {events.readback(&result); _ = sender.send(result);});
+      queue.submit([encoder.finish()]);
+
+      device.poll(wgpu::PollType::wait_indefinitely())?;
+      receiver.await??;
+
+      let field: Vec<f32> = cast_slice(&staging_buffer.get_mapped_range(..)).to_vec();
+      staging_buffer.unmap();
+
+      Ok(RectGrid::newfromdata(self.width as usize, self.height as usize, field))
+   }
+
+   /// overwrites the field with a grid of the same size, leaving the clock alone
+   pub fn write_field(&self, queue: &wgpu::Queue, grid: &RectGrid) -> anyhow::Result<()> {
+      if (grid.width(), grid.height()) != (self.width as usize, self.height as usize) {
+         anyhow::bail!("a {}x{} grid does not fit a {}x{} field", grid.width(), grid.height(), self.width, self.height)
+      }
+      match &self.tiling {
+         Some(tiling) => tiling.write_field(queue, grid)?,
+         None => queue.write_buffer(&self.data_buffer, 0, cast_slice(grid.getarray())),
+      }
+      Ok(())
+   }
+
+   /// Reads back the field and gathers the rest. Anything still in a pending queue should be
+   ///    submitted first, or it will be missing from the field but counted on the clock.
+   pub async fn checkpoint(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<Checkpoint> {
+      Ok(Checkpoint {
+         width: self.width,
+         height: self.height,
+         iteration_quantity: self.iteration_quantity,
+         max_steps_per_submission: self.max_steps_per_submission,
+         kappa: self.kappa,
+         delta_t: self.delta_t,
+         integrator: self.integrator,
+         vis_min_t: self.vis_min_t,
+         vis_max_t: self.vis_max_t,
+         color_range: self.color_range,
+         boundary_conditions: self.boundary_conditions,
+         sim_time: self.sim_time,
+         accepted_steps: self.accepted_steps,
+         rejected_steps: self.rejected_steps,
+         field: self.read_field(device, queue).await?,
+      })
+   }
+
+   /// Puts everything in a checkpoint back, which has to be our size; a checkpoint of
+   ///    another size needs a computer made for it. Any run in progress is dropped, and the
+   ///    integrator's helpers are rebuilt for the restored kappa and delta_t on the next step.
+   pub fn restore(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, checkpoint: &Checkpoint) -> anyhow::Result<()> {
+      if (checkpoint.width, checkpoint.height) != (self.width, self.height) {
+         anyhow::bail!("the checkpoint is {}x{}, the field is {}x{}", checkpoint.width, checkpoint.height, self.width, self.height)
+      }
+      if checkpoint.color_range != ColorRange::Fixed {
+         self.untiled("an automatic color range")?;
+      }
+      self.set_integrator(checkpoint.integrator)?;
+      self.write_field(queue, &checkpoint.field)?;
+      self.active_run = None;
+      self.run_controller = None;
+      self.iteration_quantity = checkpoint.iteration_quantity;
+      self.max_steps_per_submission = checkpoint.max_steps_per_submission;
+      self.set_kappa(queue, checkpoint.kappa);
+      self.set_delta_t(queue, checkpoint.delta_t);
+      self.set_vis_range(queue, checkpoint.vis_min_t, checkpoint.vis_max_t);
+      self.set_color_range(device, queue, checkpoint.color_range)?;
+      for (edge, condition) in Edge::ALL.iter().zip(checkpoint.boundary_conditions) {
+         self.set_boundary_condition(queue, *edge, condition);
+      }
+      self.sim_time = checkpoint.sim_time;
+      self.accepted_steps = checkpoint.accepted_steps;
+      self.rejected_steps = checkpoint.rejected_steps;
+      Ok(())
+   }
+
+   /// the field as a .npy, shape (height, width). Anything still in a pending queue should be
+   ///    submitted first.
+   pub async fn field_as_npy(&self, device: &wgpu::Device, queue: &wgpu::Queue, dtype: NpyDtype) -> anyhow::Result<Vec<u8>> {
+      Ok(write_npy(&self.read_field(device, queue).await?, dtype))
+   }
+
+   /// The field as temperature, kappa at every cell as kappa, and 1 between the walls and 0 on
+   ///    them as mask, in one .npz. Anything still in a pending queue should be submitted first.
+   pub async fn fields_as_npz(&self, device: &wgpu::Device, queue: &wgpu::Queue, dtype: NpyDtype) -> anyhow::Result<Vec<u8>> {
+      let temperature = self.read_field(device, queue).await?;
+      let (width, height) = (self.width as usize, self.height as usize);
+      let kappa = RectGrid::newfromdata(width, height, vec![self.kappa; width * height]);
+      let mask = RectGrid::newfromdata(width, height, (0..width * height).map(|n| {
+         let (i, j) = (n % width, n / width);
+         if i == 0 || j == 0 || i == width - 1 || j == height - 1 {0.} else {1.}
+      }).collect());
+      write_npz(&[("temperature", &temperature), ("kappa", &kappa), ("mask", &mask)], dtype)
+   }
+
+   /// Takes the field from a .npy, or from a .npz as fields_as_npz writes it. In a .npz only
+   ///    temperature is needed, or a single array of any name; a kappa has to be the same
+   ///    everywhere as the solver has one, and cells where a mask is 0 keep the temperature
+   ///    they had. Either has to be our size.
+   pub async fn load_numpy(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, bytes: &[u8]) -> anyhow::Result<()> {
+      if !bytes.starts_with(b"PK") {return self.write_field(queue, &read_npy(bytes)?)}
+
+      let mut fields = read_npz(bytes)?;
+      let mut take = |name: &str| fields.iter().position(|(field, _)| field == name).map(|n| fields.remove(n).1);
+      let (kappa, mask) = (take("kappa"), take("mask"));
+      let Some(temperature) = take("temperature").or_else(|| (fields.len() == 1).then(|| fields.remove(0).1)) else {
+         anyhow::bail!("the .npz needs a temperature, it has {:?}", fields.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>())
+      };
+      for (name, grid) in [("kappa", &kappa), ("mask", &mask)] {
+         if let Some(grid) = grid && (grid.width(), grid.height()) != (temperature.width(), temperature.height()) {
+            anyhow::bail!("{} is {}x{}, temperature is {}x{}", name, grid.width(), grid.height(), temperature.width(), temperature.height())
+         }
+      }
+      let kappa = match kappa {
+         Some(kappa) => {
+            let first = kappa.getarray()[0];
+            if kappa.getarray().iter().any(|&value| (value - first).abs() > 1e-6 * first.abs()) {
+               anyhow::bail!("kappa varies across the field, the solver takes one kappa")
+            }
+            Some(first)
+         }
+         None => None
+      };
+      if (temperature.width(), temperature.height()) != (self.width as usize, self.height as usize) {
+         anyhow::bail!("a {}x{} grid does not fit a {}x{} field", temperature.width(), temperature.height(), self.width, self.height)
+      }
+      let temperature = match mask {
+         Some(mask) => {
+            let current = self.read_field(device, queue).await?;
+            temperature.newbytemplate(temperature.getarray().iter().zip(mask.getarray()).zip(current.getarray())
+               .map(|((&new, &mask), &old)| if mask != 0. {new} else {old})
+               .collect())
+         }
+         None => temperature
+      };
+
+      self.write_field(queue, &temperature)?;
+      if let Some(kappa) = kappa {self.set_kappa(queue, kappa)}
+      Ok(())
+   }
+
+   /// The field as a VTK ImageData .vti on a domain x_length by y_length, with kappa, the heat
+   ///    flux as a vector and its magnitude, and the accumulated maps if they were started.
+   ///    The flux shader takes its differences over cells 1/width wide, so it is rescaled to
+   ///    the spacing of the cells here.
+   ///    Anything still in a pending queue should be submitted first.
+   pub async fn fields_as_vti(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, x_length: f64, y_length: f64) -> anyhow::Result<Vec<u8>> {
+      let (width, height) = (self.width as usize, self.height as usize);
+      let geometry = VtkGeometry::solver_grid(width, height, x_length, y_length);
+      let temperature = self.read_field(device, queue).await?;
+      let kappa = RectGrid::newfromdata(width, height, vec![self.kappa; width * height]);
+      let flux_x = self.read_view_field(device, queue, ViewField::FluxX).await?;
+      let x_scale = 1. / (width as f64 * geometry.spacing.0) as f32;
+      let flux_x = flux_x.newbytemplate(flux_x.getarray().iter().map(|&value| value * x_scale).collect());
+      let flux_y = self.read_view_field(device, queue, ViewField::FluxY).await?;
+      let y_scale = 1. / (height as f64 * geometry.spacing.1) as f32;
+      let flux_y = flux_y.newbytemplate(flux_y.getarray().iter().map(|&value| value * y_scale).collect());
+      let flux_magnitude = flux_x.newbytemplate(flux_x.getarray().iter().zip(flux_y.getarray())
+         .map(|(&x, &y)| x.hypot(y)).collect());
+
+      let mut accumulated = Vec::new();
+      if self.accumulators.is_some() {
+         for (name, view) in [("peak_T", ViewField::PeakTemperature), ("time_above", ViewField::TimeAbove), ("first_arrival", ViewField::FirstArrival)] {
+            accumulated.push((name, self.read_view_field(device, queue, view).await?));
+         }
+      }
+
+      let mut scalars = vec![("temperature", &temperature), ("kappa", &kappa), ("flux_mag", &flux_magnitude)];
+      scalars.extend(accumulated.iter().map(|(name, grid)| (*name, grid)));
+      write_vti(&geometry, &scalars, &[("flux", &flux_x, &flux_y)])
+   }
+
+   /// places probes, dropping any earlier ones along with what they recorded. They are
+   ///    sampled every `every` steps, and the last `capacity` samples are kept.
+   pub fn set_probes(
+      &mut self,
+      device: &wgpu::Device,
+      probes: Vec<Probe>,
+      every: u32,
+      capacity: u32,
+   ) -> anyhow::Result<()> {
+      self.untiled("probes")?;
+      self.probes = Some(ProbeRecorder::new(self, device, probes, every, capacity)?);
+      Ok(())
+   }
+
+   /// the probe time series as csv, oldest sample first
+   pub async fn probes_as_csv(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<String> {
+      let Some(probes) = &self.probes else {anyhow::bail!("no probes placed")};
+      let series = probes.read_series(device, queue).await?;
+      probes.series_as_csv(&series)
+   }
+
+   pub fn begin_chunked_run(&mut self, total_steps: u64) {
+      self.cancel_flag.store(false, Ordering::SeqCst);
+      self.run_controller = None;
+      self.active_run = Some(ChunkedRun { total_steps, done_steps: 0, cancelled: false });
+   }
+
+   /// takes effect before the next chunk is recorded, chunks already queued still run.
+   pub fn cancel_chunked_run(&self) {
+      self.cancel_flag.store(true, Ordering::SeqCst);
+   }
+
+   /// queues the next chunk of the active run, at most iteration_quantity steps and never
+   ///    more than max_steps_per_submission. Gives back the progress including this chunk,
+   ///    or None if there is no run. A finished or cancelled run is cleared once reported.
+   pub fn send_run_chunk(
+      &mut self,
+      pending_queue: &mut Vec<wgpu::CommandBuffer>,
+      device: &wgpu::Device,
+   ) -> Option<ChunkedRun> {
+      self.send_run_chunk_of(pending_queue, device, self.iteration_quantity.max(1) as u64)
+   }
+
+   // the next chunk of the active run, at most `most` steps and never more than
+   //    max_steps_per_submission
+   fn send_run_chunk_of(
+      &mut self,
+      pending_queue: &mut Vec<wgpu::CommandBuffer>,
+      device: &wgpu::Device,
+      most: u64,
+   ) -> Option<ChunkedRun> {
+      let mut run = self.active_run?;
+
+      if self.cancel_flag.load(Ordering::SeqCst) {
+         run.cancelled = true;
+      } else {
+         let chunk = run.remaining()
+            .min(most)
+            .min(self.max_steps_per_submission.max(1) as u64) as u32;
+         if chunk > 0 {
+            self.prepare_integrator(device);
+            pending_queue.extend(self.encode_steps(device, chunk));
+            self.advance_clock(chunk as u64);
+            run.done_steps += chunk as u64;
+         }
+      }
+
+      self.active_run = if run.is_finished() {None} else {Some(run)};
+      Some(run)
+   }
+
+   /// begins a run that goes until one of `until`'s conditions holds, which
+   ///    send_controlled_chunk then works through. Replaces any run already going.
+   pub fn begin_controlled_run(&mut self, device: &wgpu::Device, until: RunUntil) -> anyhow::Result<()> {
+      if until.conditions.iter().any(StopCondition::needs_field) {
+         self.untiled("stopping on the field")?;
+      }
+      let controller = RunController::new(self, device, until)?;
+      self.cancel_flag.store(false, Ordering::SeqCst);
+      self.active_run = None;
+      self.run_controller = Some(controller);
+      Ok(())
+   }
 
-      // these braces are to make sure we return any refs borrowed in them.
-      //    principly, encoder must be returned since it is borrowed by
-      //    begin_compute_pass, and is needed so we can call encoder.finish().
+   /// queues the next chunk of the controlled run, cut short so time and step conditions
+   ///    land on their step, and a copy of the field when the other conditions want one.
+   ///    Gives back Finished with the report once a condition holds or the run is cancelled
+   ///    (with cancel_chunked_run), and None if there is no controlled run.
+   pub fn send_controlled_chunk(
+      &mut self,
+      pending_queue: &mut Vec<wgpu::CommandBuffer>,
+      device: &wgpu::Device,
+   ) -> Option<RunStatus> {
+      let mut controller = self.run_controller.take()?;
+      // lets the map callback of an earlier copy through without waiting on it
+      _ = device.poll(wgpu::PollType::Poll);
+
+      let met = match controller.collect_check(self) {
+         Ok(met) => met,
+         Err(e) => {
+            gen_print(format!("controlled run cancelled: {}", e));
+            return Some(RunStatus::Finished(controller.report(self, None)))
+         }
+      };
+      if self.cancel_flag.load(Ordering::SeqCst) {
+         return Some(RunStatus::Finished(controller.report(self, None)))
+      }
+      if let Some(condition) = met.as_ref().or(controller.met_on_clock(self)) {
+         return Some(RunStatus::Finished(controller.report(self, Some(condition))))
+      }
+
+      controller.request_check(self, pending_queue, device);
+      let chunk = controller.steps_allowed(self)
+         .min(self.iteration_quantity.max(1) as u64)
+         .min(self.max_steps_per_submission.max(1) as u64) as u32;
+      self.prepare_integrator(device);
+      pending_queue.extend(self.encode_steps(device, chunk));
+      self.advance_clock(chunk as u64);
+
+      // no need to wait a frame to hear that the clock ran out
+      if let Some(condition) = controller.met_on_clock(self) {
+         return Some(RunStatus::Finished(controller.report(self, Some(condition))))
+      }
+      self.run_controller = Some(controller);
+      Some(RunStatus::Running)
+   }
+
+   /// runs `total_steps` steps in submissions of max_steps_per_submission, whatever
+   ///    iteration_quantity is, waiting for each to finish before sending the next.
+   ///    `on_progress` hears about every chunk and may return false to cancel. Gives back
+   ///    how far we got.
+   pub fn run_chunked(
+      &mut self,
+      device: &wgpu::Device,
+      queue: &wgpu::Queue,
+      total_steps: u64,
+      mut on_progress: impl FnMut(&ChunkedRun) -> bool,
+   ) -> anyhow::Result<ChunkedRun> {
+      self.begin_chunked_run(total_steps);
+      let mut progress = ChunkedRun { total_steps, done_steps: 0, cancelled: false };
+
+      while self.active_run.is_some() {
+         let mut pending_queue = Vec::new();
+         let Some(latest) = self.send_run_chunk_of(&mut pending_queue, device, u64::MAX) else {break};
+         progress = latest;
+         self.submit(queue, pending_queue);
+         if let Err(e) = device.poll(wgpu::PollType::wait_indefinitely()) {
+            self.active_run = None;
+            anyhow::bail!("poll failed after {} of {} steps: {}", progress.done_steps, total_steps, e)
+         }
+         if !progress.is_finished() && !on_progress(&progress) {
+            self.cancel_chunked_run();
+         }
+      }
+
+      Ok(progress)
+   }
+
+   /// runs until one of `until`'s conditions holds, waiting for each chunk to finish before
+   ///    sending the next like run_chunked
+   #[cfg(not(target_arch = "wasm32"))]
+   pub fn run_controlled(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, until: RunUntil) -> anyhow::Result<crate::runcontrol::RunReport> {
+      self.begin_controlled_run(device, until)?;
+      loop {
+         let mut pending_queue = Vec::new();
+         let status = self.send_controlled_chunk(&mut pending_queue, device);
+         self.submit(queue, pending_queue);
+         device.poll(wgpu::PollType::wait_indefinitely())?;
+         match status {
+            Some(RunStatus::Finished(report)) => return Ok(report),
+            Some(RunStatus::Running) => {}
+            None => anyhow::bail!("the controlled run was replaced before it stopped")
+         }
+      }
+   }
+
+   /// turns on adaptive stepping with these tolerances, keeping the controller's current
+   ///    delta_t if it was already on.
+   pub fn enable_adaptive(&mut self, device: &wgpu::Device, atol: f32, rtol: f32) -> anyhow::Result<()> {
+      self.untiled("adaptive stepping")?;
+      match &mut self.adaptive {
+         Some(adaptive) => {adaptive.atol = atol; adaptive.rtol = rtol;}
+         None => self.adaptive = Some(AdaptiveStepper::new(self, device, atol, rtol))
+      }
+      Ok(())
+   }
+
+   /// one adaptive attempt of at most `max_delta_t`, sent straight to the queue. Anything
+   ///    still in a pending queue should be submitted before this.
+   pub async fn adaptive_step(
+      &mut self,
+      device: &wgpu::Device,
+      queue: &wgpu::Queue,
+      max_delta_t: Option<f64>,
+   ) -> anyhow::Result<AdaptiveOutcome> {
+      let Some(adaptive) = self.adaptive.take() else {
+         anyhow::bail!("adaptive stepping is not enabled")
+      };
+
+      let stability_limit = AdaptiveStepper::max_delta_t(self);
+      let proposed = adaptive.delta_t.min(stability_limit);
+      let delta_t = proposed.min(max_delta_t.unwrap_or(f64::INFINITY));
+      let attempt = adaptive.attempt(self, device, queue, delta_t).await;
+      let mut adaptive = adaptive;
+
+      let outcome = attempt.map(|(accepted, error)| {
+         let next_delta_t = AdaptiveStepper::next_delta_t(delta_t, error, stability_limit);
+         if accepted {
+            self.sim_time += delta_t;
+            self.accepted_steps += 1;
+            if self.edge_flux.is_some() || self.accumulators.is_some() {
+               // this one step is tracked over its own delta_t, then back to the fixed one
+               self.set_step_tracker_delta_t(queue, delta_t as f32);
+               let mut encoder = device.create_command_encoder(&Default::default());
+               {
+                  let mut gputodo = encoder.begin_compute_pass(&Default::default());
+                  self.record_step_trackers(&mut gputodo);
+               }
+               queue.submit([encoder.finish()]);
+               self.sync_step_trackers(queue);
+            }
+            if let Some(probes) = &mut self.probes && self.accepted_steps.is_multiple_of(probes.every as u64) {
+               let mut encoder = device.create_command_encoder(&Default::default());
+               {
+                  let mut gputodo = encoder.begin_compute_pass(&Default::default());
+                  probes.record_sample(&mut gputodo);
+               }
+               queue.submit([encoder.finish()]);
+               probes.note_sample(self.sim_time);
+            }
+         } else {
+            self.rejected_steps += 1;
+         }
+         // a step cut short to land on a target time is no reason to shrink the next one
+         adaptive.delta_t = if accepted && delta_t < proposed {next_delta_t.max(proposed)} else {next_delta_t};
+         AdaptiveOutcome {accepted, delta_t, error, next_delta_t: adaptive.delta_t}
+      });
+
+      self.adaptive = Some(adaptive);
+      outcome
+   }
+
+   /// adaptive steps until sim_time reaches `end_time`, landing on it exactly. Gives up
+   ///    after `max_attempts` attempts, accepted or not. Gives back how many were accepted.
+   pub async fn adaptive_advance_to(
+      &mut self,
+      device: &wgpu::Device,
+      queue: &wgpu::Queue,
+      end_time: f64,
+      max_attempts: u32,
+   ) -> anyhow::Result<u32> {
+      let mut accepted = 0;
+      for _ in 0..max_attempts {
+         let remaining = end_time - self.sim_time;
+         // float round off can leave a sliver of time behind, that is close enough
+         if remaining <= end_time.abs() * 1e-12 {break}
+         if self.adaptive_step(device, queue, Some(remaining)).await?.accepted {accepted += 1}
+      }
+      Ok(accepted)
+   }
+
+   /// sum, min, max, mean, norms and centroid of the current field, reduced on the gpu.
+   ///    Anything still in a pending queue should be submitted before this.
+   pub async fn field_stats(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<FieldStats> {
+      self.untiled("reducing the field on the gpu")?;
+      let reducer = self.reducer.get_or_insert_with(
+         || FieldReducer::new(device, self.width, self.height, &self.data_buffer)
+      );
+      reducer.read_stats(device, queue).await
+   }
+
+   /// starts counting the heat through each edge from the current field, forgetting any
+   ///    earlier totals. Anything still in a pending queue should be submitted before this.
+   pub async fn track_edge_flux(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<()> {
+      self.untiled("tracking the edge flux")?;
+      let edge_flux = EdgeFluxTracker::new(self, device);
+      let mut encoder = device.create_command_encoder(&Default::default());
       {
          let mut gputodo = encoder.begin_compute_pass(&Default::default());
+         self.record_boundary_conditions(&mut gputodo);
+         edge_flux.record(&mut gputodo);
+      }
+      queue.submit([encoder.finish()]);
 
-         // this is also where we define the steps the gpu should take. so far as
-         //    i can tell, this is similar to sending an io monad to the gpu
+      self.edge_flux = Some(edge_flux);
+      let start = self.edge_flux_report(device, queue).await?;
+      if let Some(edge_flux) = &mut self.edge_flux {
+         edge_flux.initial_interior_energy = start.interior_energy;
+      }
+      Ok(())
+   }
 
-         let workgroup_quantity = (self.width * self.height).div_ceil(64) as u32;
-         let x_workgroup_quantity = self.width.div_ceil(8) as u32;
-         let y_workgroup_quantity = self.height.div_ceil(8) as u32;
-         let boundary_conds_wg_quant = (self.width*2 + self.height*2).div_ceil(self.workgroup_size);
+   /// the heat through each edge since track_edge_flux, and how well that balances the
+   ///    change in energy. Anything still in a pending queue should be submitted before this.
+   pub async fn edge_flux_report(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<EdgeFluxReport> {
+      let stats = self.field_stats(device, queue).await?;
+      let Some(edge_flux) = &self.edge_flux else {anyhow::bail!("edge flux is not being tracked")};
+      let tally = edge_flux.read_tally(device, queue).await?;
+      let cell_area = 1. / (self.width * self.height) as f32;
+      Ok(EdgeFluxReport::from_tally(&tally, stats.sum, cell_area, edge_flux.initial_interior_energy))
+   }
 
-         for _ in 0..self.iteration_quantity {
-         gputodo.set_pipeline(&self.fix_boundary_conditions_ppln);
-         gputodo.set_bind_group(0, &self.fix_boundary_conditions_bg, &[]);
-         gputodo.dispatch_workgroups(boundary_conds_wg_quant, 1 , 1);
+   /// isotherms of the current field at each level, from a readback. Anything still in a
+   ///    pending queue should be submitted first.
+   pub async fn isotherms(&self, device: &wgpu::Device, queue: &wgpu::Queue, levels: &[f32]) -> anyhow::Result<Vec<Isotherm>> {
+      let grid = self.read_field(device, queue).await?;
+      Ok(extract_isotherms(&grid, levels))
+   }
 
-         gputodo.set_pipeline(&self.laplacian_pipeline);
-         gputodo.set_bind_group(0, &self.stage_one_bind_group, &[]);
-         gputodo.dispatch_workgroups(x_workgroup_quantity, y_workgroup_quantity, 1);
+   /// draws the isotherms at these levels over the heatmap from the next color job on,
+   ///    or stops drawing them if there are none
+   pub fn set_isotherm_overlay(&mut self, device: &wgpu::Device, levels: Vec<f32>) -> anyhow::Result<()> {
+      if !levels.is_empty() {
+         self.untiled("an isotherm overlay")?;
+      }
+      self.isotherm_overlay = if levels.is_empty() {None} else {Some(IsothermOverlay::new(self, device, levels)?)};
+      Ok(())
+   }
 
-         gputodo.set_pipeline(&self.iterate_pipeline);
-         gputodo.set_bind_group(0, &self.stage_two_bind_group, &[]);
-         gputodo.dispatch_workgroups(workgroup_quantity, 1, 1);
+   /// how the colormap range is picked from now on. Anything but Fixed overwrites
+   ///    vis_minT and vis_maxT every time the field is colored, and going back to Fixed
+   ///    puts back the range last set by hand. Anything still in a pending queue should be
+   ///    submitted before this.
+   pub fn set_color_range(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, mode: ColorRange) -> anyhow::Result<()> {
+      if mode == ColorRange::Fixed {
+         self.color_range = mode;
+         // the auto ranges wrote over the buffers, put the hand set range back
+         self.set_vis_range(queue, self.vis_min_t, self.vis_max_t);
+         return Ok(())
+      }
 
-         gputodo.set_pipeline(&self.laplacian_pipeline);
-         gputodo.set_bind_group(0, &self.stage_three_bind_group, &[]);
-         gputodo.dispatch_workgroups(x_workgroup_quantity, y_workgroup_quantity, 1);
+      self.untiled("an automatic color range")?;
+      self.color_range = mode;
+      match &mut self.auto_ranger {
+         Some(ranger) => ranger.set_mode(queue, mode),
+         None => self.auto_ranger = Some(AutoRanger::new(self, self.viewed_buffer(), device, mode))
+      }
+      Ok(())
+   }
 
-         gputodo.set_pipeline(&self.iterate_pipeline);
-         gputodo.set_bind_group(0, &self.stage_four_bind_group, &[]);
-         gputodo.dispatch_workgroups(workgroup_quantity, 1, 1);
+   /// the buffer the heatmap is colored from
+   pub fn viewed_buffer(&self) -> &wgpu::Buffer {
+      let flux = self.flux.as_ref().and_then(|flux| flux.buffer(self.view_field));
+      let accumulated = self.accumulators.as_ref().and_then(|accumulators| accumulators.buffer(self.view_field));
+      flux.or(accumulated).unwrap_or(&self.data_buffer)
+   }
 
-         gputodo.set_pipeline(&self.buffer_move_pipeline);
-         gputodo.set_bind_group(0, &self.stage_five_bind_group, &[]);
-         gputodo.dispatch_workgroups(workgroup_quantity, 1, 1);
-         }
+   /// which field the heatmap shows. The flux views compute the flux each time they are
+   ///    colored, with conductivity 1 unless set_conductivity says otherwise. The
+   ///    accumulated views need start_accumulators first.
+   pub fn set_view_field(&mut self, device: &wgpu::Device, view: ViewField) -> anyhow::Result<()> {
+      if view != ViewField::Temperature {
+         self.untiled(&format!("showing {:?}", view))?;
+      }
+      if view.is_accumulated() && self.accumulators.is_none() {
+         anyhow::bail!("accumulators have not been started, so there is no {:?} to show", view)
       }
+      if view.is_flux() && self.flux.is_none() {
+         self.flux = Some(FluxComputer::new(self, device, 1.));
+      }
+      self.view_field = view;
+      self.follow_viewed_buffer(device);
+      Ok(())
+   }
 
-      // #[cfg(target_arch = "wasm32")]
-      // let progress_ref = {
-      //    let temp_ref = self.progress.clone();
-      //    {
-      //       let mut progress = temp_ref.lock().unwrap();
-      //       *progress = Some(ComputeRelevantEvent::ComputeIsWorking);
-      //    }
-      //    temp_ref
-      // };
+   // an auto range has to follow the field that is actually shown
+   fn follow_viewed_buffer(&mut self, device: &wgpu::Device) {
+      if let Some(ranger) = &self.auto_ranger {
+         let mode = ranger.mode;
+         self.auto_ranger = Some(AutoRanger::new(self, self.viewed_buffer(), device, mode));
+      }
+   }
 
-      pending_queue.push(encoder.finish())
-      //queue.submit([encoder.finish()]);
+   /// starts the peak temperature, time above `threshold` and first arrival maps from the
+   ///    field as it is now, throwing away any earlier ones. Anything still in a pending
+   ///    queue should be submitted first.
+   pub fn start_accumulators(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, threshold: f32) -> anyhow::Result<()> {
+      self.untiled("the accumulated maps")?;
+      let mut accumulators = AccumulatorMaps::new(self, device, queue, threshold);
+      accumulators.set_delta_t(self, queue, self.delta_t);
+      self.accumulators = Some(accumulators);
+      if self.view_field.is_accumulated() {
+         self.follow_viewed_buffer(device);
+      }
+      Ok(())
+   }
 
-      // #[cfg(not(target_arch = "wasm32"))]
-      // {
-      //    let (sender,receiver) = channel();
+   /// stops updating the maps and drops them, going back to temperature if they were shown
+   pub fn stop_accumulators(&mut self, device: &wgpu::Device) {
+      self.accumulators = None;
+      if self.view_field.is_accumulated() {
+         self.view_field = ViewField::Temperature;
+         self.follow_viewed_buffer(device);
+      }
+   }
+
+   /// replaces any ensemble with these members. Anything still in a pending queue should be
+   ///    submitted first.
+   pub fn start_ensemble(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, members: &[EnsembleMember]) -> anyhow::Result<()> {
+      self.ensemble = Some(Ensemble::new(self, device, queue, members)?);
+      Ok(())
+   }
+
+   /// queues `steps` steps of every ensemble member, split into submissions of at most
+   ///    max_steps_per_submission. Our own field and clock are left alone.
+   pub fn send_ensemble_job(
+      &mut self,
+      pending_queue: &mut Vec<wgpu::CommandBuffer>,
+      device: &wgpu::Device,
+      steps: u64,
+   ) -> anyhow::Result<()> {
+      let Some(mut ensemble) = self.ensemble.take() else {anyhow::bail!("no ensemble started")};
+      let mut remaining = steps;
+      while remaining > 0 {
+         let chunk = remaining.min(self.max_steps_per_submission.max(1) as u64);
+         ensemble.send_steps(self, pending_queue, device, chunk as u32);
+         remaining -= chunk;
+      }
+      self.ensemble = Some(ensemble);
+      Ok(())
+   }
+
+   pub async fn read_ensemble_member(&self, device: &wgpu::Device, queue: &wgpu::Queue, member: u32) -> anyhow::Result<RectGrid> {
+      let Some(ensemble) = &self.ensemble else {anyhow::bail!("no ensemble started")};
+      ensemble.read_member(self, device, queue, member).await
+   }
 
-      //    queue.on_submitted_work_done(move || sender.send( ComputeRelevantEvent::ComputeDoneNowColor ).unwrap());
-      //    self.progress = Some((ComputeRelevantEvent::ComputeIsWorking, receiver));
-      // }
+   pub async fn read_ensemble(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<Vec<RectGrid>> {
+      let Some(ensemble) = &self.ensemble else {anyhow::bail!("no ensemble started")};
+      ensemble.read_all(self, device, queue).await
+   }
+
+   pub fn set_conductivity(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, conductivity: f32) -> anyhow::Result<()> {
+      self.untiled("the heat flux")?;
+      match &mut self.flux {
+         Some(flux) => flux.set_conductivity(queue, conductivity),
+         None => self.flux = Some(FluxComputer::new(self, device, conductivity))
+      }
+      Ok(())
+   }
+
+   /// reads back any of the viewable fields, computing the flux first if that is asked for
+   pub async fn read_view_field(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, view: ViewField) -> anyhow::Result<RectGrid> {
+      if view == ViewField::Temperature {return self.read_field(device, queue).await}
+      self.untiled(&format!("reading {:?}", view))?;
+      if view.is_accumulated() {
+         let Some(accumulators) = &self.accumulators else {
+            anyhow::bail!("accumulators have not been started, so there is no {:?} to read", view)
+         };
+         return self.read_buffer_as_grid(device, queue, accumulators.buffer(view).expect("accumulated")).await
+      }
+
+      if self.flux.is_none() {
+         self.flux = Some(FluxComputer::new(self, device, 1.));
+      }
+      let flux = self.flux.as_ref().expect("flux was just made");
+      let mut encoder = device.create_command_encoder(&Default::default());
+      {
+         let mut gputodo = encoder.begin_compute_pass(&Default::default());
+         flux.record(self, &mut gputodo);
+      }
+      queue.submit([encoder.finish()]);
 
-      // #[cfg(target_arch = "wasm32")]
-      // queue.on_submitted_work_done(move || {
-      //    let mut progress = progress_ref.lock().unwrap();
-      //    *progress = Some(ComputeRelevantEvent::ComputeDoneNowColor);
-      // });
+      self.read_buffer_as_grid(device, queue, flux.buffer(view).expect("not temperature")).await
    }
 
    pub fn send_color_job(
@@ -441,50 +1551,49 @@ impl HeatComputer {
       device: &wgpu::Device,
    ) {
       let mut encoder = device.create_command_encoder(&Default::default());
+      if let Some(tiling) = &self.tiling {
+         tiling.record_color(&mut encoder);
+         self.recorded.color = true;
+         pending_queue.push(encoder.finish());
+         return
+      }
 
       let x_workgroup_quantity = self.width.div_ceil(8) as u32;
       let y_workgroup_quantity = self.height.div_ceil(8) as u32;
 
+      let flux_view = self.flux.as_ref().and_then(|flux| Some((flux, flux.heat_hue_bind_group(self.view_field)?)));
+      let accumulated_view = self.accumulators.as_ref().and_then(|accumulators| accumulators.heat_hue_bind_group(self.view_field));
+      if let Some((flux, _)) = flux_view {
+         let mut gputodo = encoder.begin_compute_pass(&Default::default());
+         flux.record(self, &mut gputodo);
+      }
+
+      if let (false, Some(ranger)) = (self.color_range == ColorRange::Fixed, &self.auto_ranger) {
+         ranger.record(self, &mut encoder);
+      }
+
       {
          let mut gputodo = encoder.begin_compute_pass(&Default::default());
 
          gputodo.set_pipeline(&self.heat_hue_pipeline);
-         gputodo.set_bind_group(0, &self.heat_hue_bind_group, &[]);
+         let bind_group = flux_view.map(|(_, bind_group)| bind_group).or(accumulated_view);
+         gputodo.set_bind_group(0, bind_group.unwrap_or(&self.heat_hue_bind_group), &[]);
          gputodo.dispatch_workgroups(x_workgroup_quantity, y_workgroup_quantity, 1);
-      }
 
-      #[cfg(target_arch = "wasm32")]
-      let progress_ref = {
-         let temp_ref = self.progress.clone();
-         {
-            let mut progress = temp_ref.lock().unwrap();
-            *progress = Some(ComputeRelevantEvent::ColorIsWorking);
+         if let Some(overlay) = &self.isotherm_overlay {
+            overlay.record(self, &mut gputodo);
          }
-         temp_ref
-      };
+      }
 
+      self.recorded.color = true;
       pending_queue.push(encoder.finish())
-      //queue.submit([encoder.finish()]);
-
-      // #[cfg(not(target_arch = "wasm32"))]
-      // {
-      //    let (sender,receiver) = channel();
-
-      //    queue.on_submitted_work_done(move || sender.send( ComputeRelevantEvent::ColorIsDone ).unwrap());
-      //    self.progress = Some((ComputeRelevantEvent::ColorIsWorking, receiver));
-      // }
-
-      // #[cfg(target_arch = "wasm32")]
-      // queue.on_submitted_work_done(move || {
-      //    let mut progress = progress_ref.lock().unwrap();
-      //    *progress = Some(ComputeRelevantEvent::ColorIsDone);
-      // });
    }
 
    pub fn unsafe_queue_color_job(
       &self,
       encoder: &mut wgpu::CommandEncoder
    ) {
+      if let Some(tiling) = &self.tiling {return tiling.record_color(encoder)}
       let mut gputodo = encoder.begin_compute_pass(&Default::default());
 
       let x_workgroup_quantity = self.width.div_ceil(8) as u32;
@@ -502,49 +1611,10 @@ impl HeatComputer {
       textureBuffer: &wgpu::Texture,
    ) {
       let mut encoder = device.create_command_encoder(&Default::default());
-      encoder.copy_buffer_to_texture(
-         wgpu::TexelCopyBufferInfo {
-            buffer: &self.heat_map_buffer,
-            layout: wgpu::TexelCopyBufferLayout {
-               offset: 0,
-               bytes_per_row: Some(256 * self.width.div_ceil(64)),
-               rows_per_image: Some(self.height)
-            }
-         },
-         wgpu::TexelCopyTextureInfo {
-            aspect: wgpu::TextureAspect::All,
-            texture: textureBuffer,
-            mip_level: 0,
-            origin: wgpu::Origin3d::ZERO
-         },
-         textureBuffer.size()
-      );
-
-      #[cfg(target_arch = "wasm32")]
-      let progress_ref = {
-         let temp_ref = self.progress.clone();
-         {
-            let mut progress = temp_ref.lock().unwrap();
-            *progress = Some(ComputeRelevantEvent::ColorIsCopying);
-         }
-         temp_ref
-      };
+      self.unsafe_color_to_texture_queue(&mut encoder, textureBuffer);
 
+      self.recorded.copy = true;
       pending_queue.push(encoder.finish());
-
-      // #[cfg(not(target_arch = "wasm32"))]
-      // {
-      //    let (sender,receiver) = channel();
-
-      //    queue.on_submitted_work_done(move || sender.send( ComputeRelevantEvent::ComputeDoneNowColor ).unwrap());
-      //    self.progress = Some((ComputeRelevantEvent::ColorIsCopying ,receiver));
-      // }
-
-      // #[cfg(target_arch = "wasm32")]
-      // queue.on_submitted_work_done(move || {
-      //    let mut progress = progress_ref.lock().unwrap();
-      //    *progress = None;
-      // });
    }
 
    pub fn unsafe_color_to_texture_queue(
@@ -552,6 +1622,7 @@ impl HeatComputer {
       encoder: &mut wgpu::CommandEncoder,
       textureBuffer: &wgpu::Texture,
    ) {
+      if let Some(tiling) = &self.tiling {return tiling.record_copy_to_texture(encoder, textureBuffer)}
       encoder.copy_buffer_to_texture(
          wgpu::TexelCopyBufferInfo {
             buffer: &self.heat_map_buffer,
@@ -574,6 +1645,7 @@ impl HeatComputer {
    #[cfg(not(target_arch = "wasm32"))]
    pub async fn export_heatmap_buffer(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Option<()> {
     use crate::aspng::PngConfig;
+      if self.tiling.is_some() {return None}
 
       let mut encoder = device.create_command_encoder(&Default::default());
 
@@ -607,3 +1679,20 @@ impl HeatComputer {
       Some(())
    }
 }
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn linear_workgroups_cover_every_item_in_rows() {
+      assert_eq!(linear_workgroups(1), (1, 1));
+      assert_eq!(linear_workgroups(64 * 1000), (1000, 1));
+      assert_eq!(linear_workgroups(64 * WORKGROUPS_PER_ROW), (WORKGROUPS_PER_ROW, 1));
+      // one item more needs a second row
+      assert_eq!(linear_workgroups(64 * WORKGROUPS_PER_ROW + 1), (WORKGROUPS_PER_ROW, 2));
+      let (row, rows) = linear_workgroups(4_305_000);
+      assert!(row as u64 * rows as u64 * 64 >= 4_305_000);
+      assert!(row <= WORKGROUPS_PER_ROW);
+   }
+}
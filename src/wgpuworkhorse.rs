@@ -87,6 +87,51 @@ const VERTICES: &[Vertex] = &[
 // our rectangle is two triangles bisecting the screen on its anti-diagonal
 const INDICES: &[u16] = &[0, 1, 2, 0, 2, 3, ];// /* padding */ 0,];
 
+/// the downlevel limits, which gl adapters can give too, except for the texture sizes and
+///    the limits that decide how big a grid fits in one buffer, which we raise to whatever
+///    the adapter will give us. Some kernels bind six storage buffers, more than downlevel
+///    promises, so that one comes from the adapter as well.
+pub fn negotiated_limits(adapter: &wgpu::Adapter) -> wgpu::Limits {
+   let offered = adapter.limits();
+   wgpu::Limits {
+      max_storage_buffer_binding_size: offered.max_storage_buffer_binding_size,
+      max_buffer_size: offered.max_buffer_size,
+      max_storage_buffers_per_shader_stage: offered.max_storage_buffers_per_shader_stage,
+      ..wgpu::Limits::downlevel_defaults().using_resolution(offered)
+   }
+}
+
+/// a device with no window or surface behind it, for running the solver unattended. Build
+///    servers often have no vulkan, so gl is allowed too, and WGPU_BACKEND picks one outright.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn headless_device() -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+   let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+      backends: wgpu::Backends::from_env().unwrap_or(wgpu::Backends::PRIMARY | wgpu::Backends::GL),
+      ..Default::default()
+   });
+
+   let adapter = instance
+      .request_adapter(&wgpu::RequestAdapterOptions {
+         power_preference: wgpu::PowerPreference::HighPerformance,
+         compatible_surface: None,
+         force_fallback_adapter: false,
+      })
+      .await?;
+
+   let (device, queue) = adapter
+      .request_device(&wgpu::DeviceDescriptor {
+         label: Some("headless"),
+         required_features: wgpu::Features::empty(),
+         experimental_features: wgpu::ExperimentalFeatures::disabled(),
+         required_limits: negotiated_limits(&adapter),
+         memory_hints: Default::default(),
+         trace: wgpu::Trace::Off,
+      })
+      .await?;
+
+   Ok((device, queue))
+}
+
 pub struct WgpuState {
    pub surface: wgpu::Surface<'static>,
    pub device: wgpu::Device,
@@ -176,7 +221,7 @@ impl WgpuState {
                //wgpu::Limits::downlevel_defaults()
                //wgpu::Limits::downlevel_webgl2_defaults()
             } else {
-               wgpu::Limits::defaults()
+               negotiated_limits(&adapter)
             },
             memory_hints: Default::default(),
             trace: wgpu::Trace::Off,
@@ -185,6 +230,8 @@ impl WgpuState {
 
       gen_print("device okay");
 
+      HeatComputer::check_size(width, height, &device.limits())?;
+
       fn error_capture(error: wgpu::Error) {
          gen_print(error.to_string());
       }
@@ -381,6 +428,17 @@ impl WgpuState {
 
       gen_print("heat compute okay");
 
+      // on the web the listeners outlive any one state, as it is rebuilt on every resize
+      #[cfg(target_arch = "wasm32")]
+      {
+         heateq.events = crate::web_app::WEB_EVENTS.with(|events| events.clone());
+      }
+      let events = heateq.events.clone();
+      device.on_uncaptured_error(Arc::new(move |error: wgpu::Error| {
+         gen_print(error.to_string());
+         events.emit(ComputeRelevantEvent::Error(error.to_string()));
+      }));
+
       let delta_t: f32 = 1. / (4. * (width.pow(2) + height.pow(2)) as f32); // safety factor 0.5
       heateq.update_values(&queue, 100, 1., delta_t, 0., 400.);
 
@@ -463,7 +521,7 @@ impl WgpuState {
 
       let mut pending_queue =  self.pending_queue.replace(Vec::new());
       pending_queue.push(encoder.finish());
-      self.queue.submit(pending_queue.into_boxed_slice());
+      self.heateq.submit(&self.queue, pending_queue);
 
       output.present();
 